@@ -4,8 +4,40 @@ layout (location = 0) in vec3 co;
 
 uniform mat4 inst;
 
+// locations of the vertex layouts – see VertexLayout; morphing and skinning can be combined
+#ifdef SPECTRA_SKINNING
+layout (location = 4) in uvec4 joints;
+layout (location = 5) in vec4 weights;
+
+uniform mat4 spectra_joints[64];
+#endif
+
+#ifdef SPECTRA_MORPH
+layout (location = 6) in vec3 target0;
+layout (location = 8) in vec3 target1;
+
+uniform vec2 spectra_morph_weights;
+#endif
+
 void main() {
-  gl_Position = inst * vec4(co, 1.);
+  vec4 p = vec4(co, 1.);
+
+#ifdef SPECTRA_MORPH
+  p.xyz += spectra_morph_weights.x * target0 + spectra_morph_weights.y * target1;
+#endif
+
+#ifdef SPECTRA_SKINNING
+  // vertices without weights aren’t skinned
+  if (dot(weights, vec4(1.)) > 0.) {
+    mat4 skin = weights.x * spectra_joints[joints.x]
+              + weights.y * spectra_joints[joints.y]
+              + weights.z * spectra_joints[joints.z]
+              + weights.w * spectra_joints[joints.w];
+    p = skin * p;
+  }
+#endif
+
+  gl_Position = inst * p;
 }
 
 #gs
@@ -13,19 +45,19 @@ void main() {
 layout (triangles) in;
 layout (triangle_strip, max_vertices = 3) out;
 
-uniform mat4 proj;
-uniform mat4 view;
+uniform mat4 spectra_proj;
+uniform mat4 spectra_view;
 
 out vec3 g_baryctr;
 
 void main() {
-  gl_Position = proj * view * gl_in[0].gl_Position;
+  gl_Position = spectra_proj * spectra_view * gl_in[0].gl_Position;
   g_baryctr = vec3(1., 0., 0.);
   EmitVertex();
-  gl_Position = proj * view * gl_in[1].gl_Position;
+  gl_Position = spectra_proj * spectra_view * gl_in[1].gl_Position;
   g_baryctr = vec3(0., 1., 0.);
   EmitVertex();
-  gl_Position = proj * view * gl_in[2].gl_Position;
+  gl_Position = spectra_proj * spectra_view * gl_in[2].gl_Position;
   g_baryctr = vec3(0., 0., 1.);
   EmitVertex();
 
@@ -1,10 +1,18 @@
 #[macro_use]
 extern crate clap;
+extern crate spectra;
 
-use clap::{App, AppSettings, SubCommand};
+use clap::{App, AppSettings, Arg, SubCommand};
+use spectra::glsl::validate_program;
+use spectra::gltf::Gltf;
+use spectra::mesh::{MeshData, binary, optimize};
+use spectra::model::{ModelData, Normals, Vertex};
+use spectra::resource::LoadError;
+use spectra::shader::{Defines, SHADERS_ROOT};
 use std::fs::{File, create_dir_all};
 use std::io::Write;
 use std::path::Path;
+use std::process::exit;
 
 include!(concat!(env!("OUT_DIR"), "/resources.rs"));
 
@@ -16,6 +24,47 @@ fn main() {
     .setting(AppSettings::SubcommandRequired)
     .subcommand(SubCommand::with_name("bootstrap")
          .about("Create default resources in your end-user project"))
+    .subcommand(SubCommand::with_name("check")
+         .about("Check shader programs without any GPU")
+         .arg(Arg::with_name("root")
+              .long("root")
+              .takes_value(true)
+              .help("Directory includes are relative to (defaults to data/shaders)"))
+         .arg(Arg::with_name("define")
+              .short("D")
+              .takes_value(true)
+              .multiple(true)
+              .number_of_values(1)
+              .help("Preprocessor definition, as NAME or NAME=VALUE"))
+         .arg(Arg::with_name("PROGRAMS")
+              .required(true)
+              .multiple(true)
+              .help("Program files to check")))
+    .subcommand(SubCommand::with_name("convert")
+         .about("Convert Wavefront models into binary models")
+         .arg(Arg::with_name("output")
+              .short("o")
+              .long("output")
+              .takes_value(true)
+              .help("Output file; only with a single model (defaults to the model with a .smesh extension)"))
+         .arg(Arg::with_name("normals")
+              .long("normals")
+              .takes_value(true)
+              .help("How missing normals are generated: flat, smooth or a maximum angle in degrees (defaults to smooth)"))
+         .arg(Arg::with_name("MODELS")
+              .required(true)
+              .multiple(true)
+              .help("Wavefront .obj files to convert")))
+    .subcommand(SubCommand::with_name("info")
+         .about("Print statistics about models and what optimizing them would give")
+         .arg(Arg::with_name("tolerance")
+              .long("tolerance")
+              .takes_value(true)
+              .help("Tolerance used to weld vertices (defaults to 0)"))
+         .arg(Arg::with_name("MODELS")
+              .required(true)
+              .multiple(true)
+              .help("Model files (.obj, .smesh, .gltf or .glb)")))
     .get_matches();
 
   if options.subcommand_matches("bootstrap").is_some() {
@@ -25,9 +74,146 @@ fn main() {
       println!("--> {:?}", resource.0);
       copy_file(resource);
     }
+  } else if let Some(options) = options.subcommand_matches("check") {
+    let root = options.value_of("root").unwrap_or(SHADERS_ROOT);
+    let mut defines = Defines::new();
+
+    for define in options.values_of("define").into_iter().flat_map(|defines| defines) {
+      let mut parts = define.splitn(2, '=');
+      let name = parts.next().unwrap_or("");
+      defines.insert(name.to_owned(), parts.next().unwrap_or("").to_owned());
+    }
+
+    let mut failed = false;
+
+    for program in options.values_of("PROGRAMS").unwrap() {
+      match validate_program(program, root, &defines) {
+        Ok(ref diagnostics) if diagnostics.is_empty() => {
+          println!("{}: ok", program);
+        },
+        Ok(diagnostics) => {
+          // only errors fail the check; warnings come from heuristics
+          failed = failed || diagnostics.iter().any(|diagnostic| diagnostic.is_error());
+
+          for diagnostic in diagnostics {
+            println!("{}", diagnostic);
+          }
+        },
+        Err(e) => {
+          failed = true;
+          println!("{}: {:?}", program, e);
+        }
+      }
+    }
+
+    if failed {
+      exit(1);
+    }
+  } else if let Some(options) = options.subcommand_matches("convert") {
+    let normals = match options.value_of("normals") {
+      None | Some("smooth") => Normals::Smooth,
+      Some("flat") => Normals::Flat,
+      Some(angle) => {
+        match angle.parse() {
+          Ok(angle) => Normals::Angle(angle),
+          Err(_) => {
+            println!("invalid normals: {}", angle);
+            exit(1);
+          }
+        }
+      }
+    };
+
+    let models: Vec<_> = options.values_of("MODELS").unwrap().collect();
+
+    if options.is_present("output") && models.len() > 1 {
+      println!("--output can only be used with a single model");
+      exit(1);
+    }
+
+    let mut failed = false;
+
+    for model in models {
+      let output = options.value_of("output").map_or_else(|| Path::new(model).with_extension(binary::EXTENSION), PathBuf::from);
+
+      match binary::convert_obj_file(model, &output, normals) {
+        Ok(_) => {
+          println!("{} -> {}", model, output.display());
+        },
+        Err(e) => {
+          failed = true;
+          println!("{}: {:?}", model, e);
+        }
+      }
+    }
+
+    if failed {
+      exit(1);
+    }
+  } else if let Some(options) = options.subcommand_matches("info") {
+    let tolerance = match options.value_of("tolerance").map(|tolerance| tolerance.parse()) {
+      None => 0.,
+      Some(Ok(tolerance)) => tolerance,
+      Some(Err(_)) => {
+        println!("invalid tolerance");
+        exit(1);
+      }
+    };
+
+    let mut failed = false;
+
+    for model in options.values_of("MODELS").unwrap() {
+      match model_meshes(model) {
+        Ok(meshes) => {
+          println!("{}: {} parts", model, meshes.len());
+
+          for (i, mesh) in meshes.iter().enumerate() {
+            print_mesh_info(i, mesh, tolerance);
+          }
+        },
+        Err(e) => {
+          failed = true;
+          println!("{}: {:?}", model, e);
+        }
+      }
+    }
+
+    if failed {
+      exit(1);
+    }
+  }
+}
+
+// CPU-side meshes of a model file.
+fn model_meshes(path: &str) -> Result<Vec<MeshData<Vertex>>, LoadError> {
+  let path = Path::new(path);
+
+  match path.extension().and_then(|ext| ext.to_str()) {
+    Some("gltf") | Some("glb") => {
+      let gltf = Gltf::open(path)?;
+      let mut meshes = Vec::new();
+
+      for mesh in 0..gltf.mesh_count() {
+        for primitive in 0..gltf.primitive_count(mesh) {
+          meshes.push(gltf.primitive(mesh, primitive)?.mesh);
+        }
+      }
+
+      Ok(meshes)
+    },
+    Some(binary::EXTENSION) => binary::read_file(path).map(|(data, _)| data.parts.into_iter().map(|(mesh, _)| mesh).collect()),
+    _ => ModelData::from_obj(path, Normals::Smooth).map(|(data, _)| data.parts.into_iter().map(|(mesh, _)| mesh).collect())
   }
 }
 
+fn print_mesh_info(i: usize, mesh: &MeshData<Vertex>, tolerance: f32) {
+  let (_, stats) = optimize::optimize(mesh, tolerance);
+
+  println!("  part {}: {:?}, {} vertices, {} indices", i, mesh.mode, mesh.vertices.len(), mesh.index_count());
+  println!("    bounds: {:?} – {:?}", mesh.bounds.min, mesh.bounds.max);
+  println!("    optimized: {}", stats);
+}
+
 fn copy_file(entry: &(PathBuf, &'static [u8])) {
   let path = entry.0.as_path();
   let parent = path.parent().unwrap_or(&Path::new("."));
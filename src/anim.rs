@@ -1,8 +1,20 @@
+use std::borrow::Borrow;
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::rc::Rc;
+
+use id::Id;
+use resource::{Get, Reload};
+use spline::{Interpolate, Interpolation, Key, Sampler, Spline, Time};
+
 // FIXME: not sure we need mutability here, because it would lead into unreproductible effects
 /// Continuous value.
 ///
 /// This type wraps a `A` as a function of time `f32`. It has a simple semantic: `at`, giving the
 /// value at the wished time.
+///
+/// Continuous values compose: you can map over them, zip them, remap their time, sequence them or
+/// blend them. All combinators consume their inputs and give back a new `Cont`.
 pub struct Cont<'a, A> {
   closure: Box<FnMut(f32) -> A + 'a>
 }
@@ -14,18 +26,195 @@ impl<'a, A> Cont<'a, A> {
     }
   }
 
+  /// A continuous value that doesn’t depend on time.
+  pub fn constant(a: A) -> Self where A: 'a + Clone {
+    Cont::new(move |_| a.clone())
+  }
+
   /// Turn a set of discret values that happen at given moments into a continuous step value.
+  ///
+  /// Before the first moment, `def` is used. Afterwards, the value of the latest moment that has
+  /// happened is used. Moments don’t have to be sorted.
   pub fn from_discrete(def: A, mut moments: Vec<(f32, A)>) -> Self where A: 'a + Clone {
-    moments.sort_by(|_, b| b.0.partial_cmp(&b.0).unwrap());
+    moments.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
 
-    Cont {
-      closure: Box::new(move |t| {
-        moments.binary_search_by(|a| a.0.partial_cmp(&t).unwrap()).ok().map_or(def.clone(), |i| moments[i].1.clone())
-      })
-    }
+    Cont::new(move |t| {
+      // number of moments that have already happened at t
+      let happened = match moments.binary_search_by(|m| if m.0 <= t { Ordering::Less } else { Ordering::Greater }) {
+        Ok(i) | Err(i) => i
+      };
+
+      if happened == 0 {
+        def.clone()
+      } else {
+        moments[happened - 1].1.clone()
+      }
+    })
   }
 
   pub fn at(&mut self, t: f32) -> A {
     (self.closure)(t)
   }
+
+  /// Apply a function to the values.
+  pub fn map<B, F>(self, mut f: F) -> Cont<'a, B> where A: 'a, F: 'a + FnMut(A) -> B {
+    let mut c = self;
+    Cont::new(move |t| f(c.at(t)))
+  }
+
+  /// Pair the values with the ones of another continuous value.
+  pub fn zip<B>(self, other: Cont<'a, B>) -> Cont<'a, (A, B)> where A: 'a, B: 'a {
+    self.zip_with(other, |a, b| (a, b))
+  }
+
+  /// Combine the values with the ones of another continuous value.
+  pub fn zip_with<B, C, F>(self, other: Cont<'a, B>, mut f: F) -> Cont<'a, C>
+      where A: 'a,
+            B: 'a,
+            F: 'a + FnMut(A, B) -> C {
+    let mut a = self;
+    let mut b = other;
+    Cont::new(move |t| f(a.at(t), b.at(t)))
+  }
+
+  /// Apply a continuous function to a continuous argument.
+  pub fn apply<B, C>(self, arg: Cont<'a, B>) -> Cont<'a, C> where A: 'a + FnMut(B) -> C, B: 'a {
+    self.zip_with(arg, |mut f, x| f(x))
+  }
+
+  /// Delay the continuous value by `dt`: the value at `t` is the original value at `t - dt`.
+  pub fn offset(self, dt: f32) -> Self where A: 'a {
+    let mut c = self;
+    Cont::new(move |t| c.at(t - dt))
+  }
+
+  /// Change the speed of the continuous value: the value at `t` is the original value at `t * k`.
+  pub fn scale(self, k: f32) -> Self where A: 'a {
+    let mut c = self;
+    Cont::new(move |t| c.at(t * k))
+  }
+
+  /// Restrict time to `[start; end]`. Out of that range, the values at the bounds are held.
+  pub fn clamp(self, start: f32, end: f32) -> Self where A: 'a {
+    let mut c = self;
+    Cont::new(move |t| c.at(t.max(start).min(end)))
+  }
+
+  /// Repeat the `[0; period[` portion of the continuous value forever, in both directions.
+  pub fn looped(self, period: f32) -> Self where A: 'a {
+    let mut c = self;
+    Cont::new(move |t| {
+      let u = t % period;
+      c.at(if u < 0. { u + period } else { u })
+    })
+  }
+
+  /// Play the continuous value backwards from `end`: the value at `t` is the original value at
+  /// `end - t`.
+  pub fn reverse(self, end: f32) -> Self where A: 'a {
+    let mut c = self;
+    Cont::new(move |t| c.at(end - t))
+  }
+
+  /// Use this continuous value until `at`, then switch to `next`.
+  ///
+  /// `next` is sampled with the same time as `self`; use `offset` on it if you want it to start at
+  /// its own origin.
+  pub fn switch(self, at: f32, next: Self) -> Self where A: 'a {
+    let mut before = self;
+    let mut after = next;
+    Cont::new(move |t| if t < at { before.at(t) } else { after.at(t) })
+  }
+
+  /// Blend two continuous values with a continuous weight. A weight of `0` gives `a` and a weight
+  /// of `1` gives `b`.
+  pub fn blend(a: Self, b: Self, weight: Cont<'a, f32>) -> Self where A: 'a + Interpolate {
+    let mut a = a;
+    let mut b = b;
+    let mut weight = weight;
+    Cont::new(move |t| Interpolate::lerp(a.at(t), b.at(t), weight.at(t)))
+  }
+}
+
+/// Value used by a spline-based continuous value when the spline cannot be sampled – i.e. when
+/// the time is before the first control point or after the last one.
+#[derive(Clone, Copy, Debug)]
+pub enum Boundary<T> {
+  /// Use the given value.
+  Default(T),
+  /// Hold the value of the nearest control point. The given value is only used if the spline
+  /// doesn’t have any control point at all.
+  Hold(T)
+}
+
+impl<'a, T> Cont<'a, T> where T: 'a + Interpolate {
+  /// Turn a spline into a continuous value.
+  ///
+  /// The spline can be passed by value or through any kind of pointer to it (`&Spline`,
+  /// `Rc<Spline>`, etc.). The continuous value owns its own `Sampler`.
+  pub fn from_spline<S>(spline: S, boundary: Boundary<T>) -> Self where S: 'a + Borrow<Spline<T>> {
+    let mut sampler = Sampler::new();
+
+    Cont::new(move |t| {
+      let spline: &Spline<T> = spline.borrow();
+      sampler.sample(t, spline, false).unwrap_or_else(|| out_of_bounds(spline, t, &boundary))
+    })
+  }
+
+  /// Turn a cached spline into a continuous value.
+  ///
+  /// The spline is fetched from the cache every time the continuous value is sampled, so that hot
+  /// reloading is followed.
+  pub fn from_spline_id<C>(cache: Rc<RefCell<C>>, id: Id<'a, Spline<T>>, boundary: Boundary<T>) -> Self
+      where C: 'a + Get<'a, Spline<T>>,
+            Spline<T>: 'a + Reload<'a> {
+    let mut sampler = Sampler::new();
+
+    Cont::new(move |t| {
+      let spline = cache.borrow_mut().get_by_id(&id);
+
+      match spline {
+        // random sampling because a reloaded spline might have less control points than the one
+        // the sampler’s cursor was set for
+        Some(spline) => sampler.sample(t, &spline, true).unwrap_or_else(|| out_of_bounds(&spline, t, &boundary)),
+        None => boundary_value(&boundary)
+      }
+    })
+  }
+
+  /// Bake the continuous value into a spline by sampling it every `step` in `[start; end]`.
+  pub fn bake(&mut self, start: Time, end: Time, step: Time, interpolation: Interpolation) -> Spline<T> {
+    assert!(step > 0.);
+
+    let n = ((end - start) / step).ceil().max(0.) as usize;
+    let mut keys = Vec::with_capacity(n + 1);
+
+    for i in 0..n + 1 {
+      let t = (start + i as Time * step).min(end);
+      keys.push(Key::new(t, self.at(t), interpolation));
+    }
+
+    Spline::new(keys)
+  }
+}
+
+// Value to use when a spline cannot be sampled at a given time.
+fn out_of_bounds<T>(spline: &Spline<T>, t: Time, boundary: &Boundary<T>) -> T where T: Interpolate {
+  match *boundary {
+    Boundary::Default(x) => x,
+    Boundary::Hold(x) => {
+      let keys = spline.keys();
+
+      match (keys.first(), keys.last()) {
+        (Some(first), Some(last)) => if t <= first.t { first.value } else { last.value },
+        _ => x
+      }
+    }
+  }
+}
+
+fn boundary_value<T>(boundary: &Boundary<T>) -> T where T: Copy {
+  match *boundary {
+    Boundary::Default(x) | Boundary::Hold(x) => x
+  }
 }
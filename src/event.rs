@@ -0,0 +1,264 @@
+use serde_json::from_reader;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::f32::consts;
+use std::fs::File;
+use std::path::Path;
+use std::rc::Rc;
+
+use resource::{Cache, Get, Load, LoadError};
+use spline::Time;
+use tempo::TempoMap;
+
+/// Shape of the attack and decay phases of an `Envelope`.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+pub enum EnvelopeShape {
+  /// Straight ramps.
+  #[serde(rename = "linear")]
+  Linear,
+  /// Exponential ramps: fast at the event, slow afterwards.
+  #[serde(rename = "exponential")]
+  Exponential,
+  /// Cosine ramps.
+  #[serde(rename = "smooth")]
+  Smooth
+}
+
+impl Default for EnvelopeShape {
+  fn default() -> Self {
+    EnvelopeShape::Linear
+  }
+}
+
+/// Impulse-and-decay envelope.
+///
+/// The envelope rises from `0` to `1` in `attack` seconds after an event, then falls back to `0`
+/// in `decay` seconds. A null attack jumps to `1` right at the event. Negative and non-finite
+/// durations are taken as null.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+pub struct Envelope {
+  #[serde(default)]
+  pub attack: Time,
+  pub decay: Time,
+  #[serde(default)]
+  pub shape: EnvelopeShape
+}
+
+impl Envelope {
+  pub fn new(attack: Time, decay: Time, shape: EnvelopeShape) -> Self {
+    Envelope {
+      attack: clamp_duration(attack),
+      decay: clamp_duration(decay),
+      shape: shape
+    }
+  }
+
+  /// Value of the envelope `dt` seconds after an event.
+  pub fn value(&self, dt: Time) -> f32 {
+    // the fields are public – and deserialized – so they’re clamped here too
+    let attack = clamp_duration(self.attack);
+    let decay = clamp_duration(self.decay);
+
+    if dt < 0. {
+      0.
+    } else if dt < attack {
+      1. - self.fall(dt / attack)
+    } else if dt - attack < decay {
+      self.fall((dt - attack) / decay)
+    } else {
+      0.
+    }
+  }
+
+  // Falling ramp from 1 to 0 for x in [0;1].
+  fn fall(&self, x: f32) -> f32 {
+    match self.shape {
+      EnvelopeShape::Linear => 1. - x,
+      // rescaled so that it actually reaches 0 at x = 1
+      EnvelopeShape::Exponential => ((-5. * x).exp() - (-5f32).exp()) / (1. - (-5f32).exp()),
+      EnvelopeShape::Smooth => (1. + (x * consts::PI).cos()) * 0.5
+    }
+  }
+}
+
+// Negative and non-finite durations are null.
+fn clamp_duration(d: Time) -> Time {
+  if d > 0. && d.is_finite() { d } else { 0. }
+}
+
+/// Event track.
+///
+/// An event track holds named sets of trigger times – kicks, snares, palette switches, etc. It
+/// answers questions such as “how long ago was the last kick?” or “how many snares so far?”.
+///
+/// Queries don’t keep any state, so seeking around is fine. If the track has a period, the events
+/// repeat forever every period – the event times must then lie in `[0; period[`.
+///
+/// If the track is retrieved from the cache, the path must point to a JSON file of the form:
+///
+/// ```json
+/// {
+///   "tempo": "main.json",
+///   "loop": 16,
+///   "events": {
+///     "kick": [0, 1, 2, 3],
+///     "palette": [8]
+///   }
+/// }
+/// ```
+///
+/// `"tempo"` and `"loop"` are optional. If a tempo map is given, times and the period are in beats
+/// and are converted into seconds with it.
+#[derive(Clone, Debug)]
+pub struct EventTrack {
+  events: HashMap<String, Vec<Time>>,
+  period: Option<Time>
+}
+
+impl EventTrack {
+  /// Panics if the period isn’t positive.
+  pub fn new(mut events: HashMap<String, Vec<Time>>, period: Option<Time>) -> Self {
+    assert!(period.map_or(true, |period| period > 0. && period.is_finite()), "the period of an event track must be positive");
+
+    for times in events.values_mut() {
+      times.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    }
+
+    EventTrack {
+      events: events,
+      period: period
+    }
+  }
+
+  /// Trigger times of a given event.
+  pub fn times(&self, name: &str) -> Option<&[Time]> {
+    self.events.get(name).map(|times| times.as_slice())
+  }
+
+  pub fn period(&self) -> Option<Time> {
+    self.period
+  }
+
+  /// Number of times an event has been triggered at a given time.
+  pub fn count(&self, name: &str, t: Time) -> usize {
+    let times = match self.events.get(name) {
+      Some(times) => times,
+      None => return 0
+    };
+
+    if t < 0. {
+      return 0;
+    }
+
+    match self.period {
+      Some(period) => {
+        let (iterations, u) = split_period(t, period);
+        iterations * times.len() + happened(times, u)
+      },
+      None => happened(times, t)
+    }
+  }
+
+  /// Time elapsed since the last time an event was triggered, if it was ever triggered.
+  pub fn since_last(&self, name: &str, t: Time) -> Option<Time> {
+    let times = match self.events.get(name) {
+      Some(times) if !times.is_empty() => times,
+      _ => return None
+    };
+
+    if t < 0. {
+      return None;
+    }
+
+    match self.period {
+      Some(period) => {
+        let (iterations, u) = split_period(t, period);
+
+        match happened(times, u) {
+          0 if iterations > 0 => Some(u + period - times[times.len() - 1]),
+          0 => None,
+          i => Some(u - times[i - 1])
+        }
+      },
+      None => {
+        match happened(times, t) {
+          0 => None,
+          i => Some(t - times[i - 1])
+        }
+      }
+    }
+  }
+
+  /// Value of an envelope triggered by the last occurrence of an event. If the event hasn’t been
+  /// triggered yet, the envelope is `0`.
+  pub fn envelope(&self, name: &str, t: Time, envelope: &Envelope) -> f32 {
+    self.since_last(name, t).map_or(0., |dt| envelope.value(dt))
+  }
+}
+
+// Number of times that are lower or equal to t.
+fn happened(times: &[Time], t: Time) -> usize {
+  match times.binary_search_by(|x| if *x <= t { Ordering::Less } else { Ordering::Greater }) {
+    Ok(i) | Err(i) => i
+  }
+}
+
+// Split a positive time into a number of whole periods and the time left in the current period.
+fn split_period(t: Time, period: Time) -> (usize, Time) {
+  let iterations = (t / period).floor();
+  (iterations as usize, t - iterations * period)
+}
+
+#[derive(Deserialize)]
+struct Manifest {
+  #[serde(default)]
+  tempo: Option<String>,
+  #[serde(default, rename = "loop")]
+  period: Option<Time>,
+  events: HashMap<String, Vec<Time>>
+}
+
+impl<'a> Load<'a> for EventTrack {
+  type Args = ();
+
+  fn load<P>(path: P, cache: &mut Cache<'a>, _: Self::Args) -> Result<Self, LoadError> where P: AsRef<Path> {
+    let path = path.as_ref();
+
+    info!("loading event track: {:?}", path);
+
+    let file = File::open(path).map_err(|e| LoadError::FileNotFound(path.to_path_buf(), format!("{:?}", e)))?;
+    let mut manifest: Manifest = from_reader(file).map_err(|e| LoadError::ParseFailed(format!("{:?}", e)))?;
+
+    // convert beats into seconds
+    if let Some(ref tempo_name) = manifest.tempo {
+      let tempo: Rc<TempoMap> = cache.get(tempo_name, ()).ok_or(LoadError::ConversionFailed(format!("unable to find tempo map {} for event track at {:?}", tempo_name, path)))?;
+      cache.add_resource_dependency::<TempoMap>(tempo_name);
+
+      for times in manifest.events.values_mut() {
+        for t in times.iter_mut() {
+          *t = tempo.time_at_beat(*t);
+        }
+      }
+
+      manifest.period = manifest.period.map(|period| tempo.time_at_beat(period));
+    }
+
+    // iteration counts would be infinite or NaN otherwise
+    if let Some(period) = manifest.period {
+      if !(period > 0.) || !period.is_finite() {
+        return Err(LoadError::ParseFailed(format!("the loop of the event track at {:?} must be positive; got {}", path, period)));
+      }
+    }
+
+    // queries assume the times lie in the track
+    for (name, times) in &manifest.events {
+      for &t in times {
+        if !(t >= 0.) || !t.is_finite() || manifest.period.map_or(false, |period| t >= period) {
+          return Err(LoadError::ParseFailed(format!("event {} of the event track at {:?} is out of the track: {}", name, path, t)));
+        }
+      }
+    }
+
+    Ok(EventTrack::new(manifest.events, manifest.period))
+  }
+}
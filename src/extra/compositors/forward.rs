@@ -1,55 +1,86 @@
 use luminance::{Dim2, Flat, Mode, RGBA32F, Unit};
-use luminance_gl::gl33::{Framebuffer, Pipe, Pipeline, RenderCommand, ShadingCommand, Tess, Texture,
-                         Uniform};
+use luminance_gl::gl33::{Framebuffer, Pipe, Pipeline, RenderCommand, ShadingCommand, Tess, Texture};
 
 use compositor::{Compositor, Screen};
 use id::Id;
 use scene::Scene;
-use shader::Program;
+use shader::{Program, ProgramArgs, UniformInterface};
 
 pub type Texture2D<A> = Texture<Flat, Dim2, A>;
 
-const FORWARD_SOURCE: Uniform<Unit> = Uniform::new(0);
+uniform_interface! {
+  /// Uniforms of the forward compositor program.
+  pub struct ForwardUniforms {
+    source: Unit
+  }
+}
 
 pub struct Forward<'a> {
   program: Id<'a, Program>,
+  uniforms: ForwardUniforms,
   quad: Tess,
   w: u32,
   h: u32
 }
 
 impl<'a> Forward<'a> {
-  pub fn new(w: u32, h: u32, scene: &mut Scene<'a>) -> Self {
-    let program = get_id!(scene, "spectra/compositors/forward.glsl", vec![Uniform::<Unit>::sem("source")]).unwrap();
+  /// Return `None` if the program cannot be found or doesn’t declare the expected uniforms. A
+  /// program that fails to compile is replaced by a magenta fallback until it’s fixed.
+  pub fn new(w: u32, h: u32, scene: &mut Scene<'a>) -> Option<Self> {
+    let args = ProgramArgs::discover().interface::<ForwardUniforms>().fullscreen();
+    let program = match get_id!(scene, "spectra/compositors/forward.glsl", args) {
+      Some(program) => program,
+      None => return None
+    };
 
-    // update the texture uniform once and for all
-    {
-      let program: &Program = &scene.get_by_id(&program).unwrap();
-      program.update(&FORWARD_SOURCE, Unit::new(0));
-    }
+    let uniforms = match scene.get_by_id(&program).map(|program| ForwardUniforms::from_program(&program)) {
+      Some(Ok(uniforms)) => uniforms,
+      Some(Err(e)) => {
+        err!("{}", e);
+        return None;
+      },
+      None => return None
+    };
 
-    Forward {
+    Some(Forward {
       program: program,
+      uniforms: uniforms,
       quad: Tess::attributeless(Mode::TriangleStrip, 4),
       w: w,
       h: h
-    }
+    })
   }
 }
 
 impl<'a, 'b> Compositor<'a, 'b, &'a Texture2D<RGBA32F>> for Forward<'b> {
   fn composite(&'a self, scene: &'a mut Scene<'b>, source: &'a Texture2D<RGBA32F>) -> Screen<'a> {
-    let program = scene.get_by_id(&self.program).unwrap();
+    scene.begin_frame();
+
+    let program = match scene.get_by_id(&self.program) {
+      Some(program) => program,
+      None => {
+        scene.end_frame();
+        return Screen::Display;
+      }
+    };
     let back_fb = Framebuffer::default((self.w, self.h));
     let textures = &[source.into()];
 
+    let mut builtins = scene.builtins;
+    builtins.resolution = [self.w as f32, self.h as f32];
+
     Pipeline::new(&back_fb, [0., 0., 0., 0.], textures, &[], vec![
-      Pipe::new(|_| {}, ShadingCommand::new(&program, vec![
+      Pipe::new(|_| {
+        program.update_builtins(&builtins);
+        program.update(&self.uniforms.source, Unit::new(0));
+      }, ShadingCommand::new(&program, vec![
         Pipe::new(|_| {}, RenderCommand::new(None, true, vec![
           Pipe::new(|_|{}, &self.quad)], 1, None))
         ]))
     ]).run();
 
+    scene.end_frame();
+
     Screen::Display
   }
 }
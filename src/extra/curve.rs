@@ -1,9 +1,10 @@
 use spline::{Spline, Key, Interpolation, Sampler};
 use luminance::Mode;
-use luminance_gl::gl33::Tess;
+
+use mesh::MeshData;
 
 // Build a curve connected by segments.
-pub fn new_curve_2d(gap: f32, interpolation: Interpolation, points: &[(f32, f32)]) -> Tess {
+pub fn new_curve_2d(gap: f32, interpolation: Interpolation, points: &[(f32, f32)]) -> MeshData<[f32; 2]> {
   // convert 2D points into cps
   let cps = points.iter().map(|&(t, x)| Key::new(t, x, interpolation)).collect();
   let param = Spline::new(cps);
@@ -18,6 +19,6 @@ pub fn new_curve_2d(gap: f32, interpolation: Interpolation, points: &[(f32, f32)
     t += gap;
   }
 
-  Tess::new(Mode::LineStrip, &vertices, None)
+  MeshData::new(Mode::LineStrip, vertices, None)
 }
 
@@ -1,12 +1,18 @@
 pub mod compositors;
-pub mod cube;
 pub mod curve;
 pub mod plane;
 pub mod renderers;
 pub mod shaders;
+pub mod shadertoy;
+pub mod terrain;
 
-pub use self::cube::new_cube;
+// the generators live in the core mesh module, which the built-in models use
+pub use mesh::{cube, shapes};
+pub use mesh::cube::{new_cube, new_cube_corners};
+pub use mesh::shapes::{new_capsule, new_cone, new_cylinder, new_grid, new_icosphere, new_torus, new_uv_sphere};
 pub use self::curve::new_curve_2d;
 pub use self::plane::new_plane;
 pub use self::renderers::simple::SimpleRenderer;
 pub use self::shaders::*;
+pub use self::shadertoy::{Channel, Shadertoy, ShadertoyProgram};
+pub use self::terrain::{Heightmap, Terrain, TerrainArgs, TerrainChunk, fractal_noise};
@@ -1,14 +1,15 @@
 use luminance::Mode;
-use luminance_gl::gl33::Tess;
+
+use mesh::MeshData;
 
 /// A unit plane, aligned with the (x,y) plane.
-pub fn new_plane() -> Tess {
-  let vertices = [
+pub fn new_plane() -> MeshData<[f32; 3]> {
+  let vertices = vec![
     [ 1., -1., 0.],
     [ 1.,  1., 0.],
     [-1., -1., 0.],
     [-1.,  1., 0.]
   ];
 
-  Tess::new(Mode::TriangleStrip, &vertices, None)
+  MeshData::new(Mode::TriangleStrip, vertices, None)
 }
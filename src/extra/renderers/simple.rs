@@ -2,7 +2,7 @@ use luminance::{Depth32F, Dim2, Flat, RGBA32F};
 use luminance_gl::gl33::{Framebuffer, Pipe, Pipeline, RenderCommand, ShadingCommand, Texture};
 
 use camera::{Camera, Freefly};
-use extra::shaders::default::{DEFAULT_3D_INST, DEFAULT_3D_PROJ, DEFAULT_3D_VIEW, DefaultProgram3D};
+use extra::shaders::default::DefaultProgram3D;
 use object::Object;
 use projection::Projectable;
 use renderer::Renderer;
@@ -13,50 +13,82 @@ pub type Texture2D<A> = Texture<Flat, Dim2, A>;
 
 /// Simple renderer that takes a camera, a set of model and applies a shader on them. This renderer
 /// outputs a single color map along with the depth map.
+///
+/// Built-in uniforms are filled in with the camera and the size of the color map. The frame is
+/// started if it isn’t yet – see `Scene::begin_frame` – but it’s not ended, since a compositor
+/// usually follows: without one, call `Scene::end_frame` once the frame is displayed.
 pub struct SimpleRenderer<'a> {
   program: DefaultProgram3D<'a>,
-  framebuffer: Framebuffer<Flat, Dim2, Texture2D<RGBA32F>, Texture2D<Depth32F>>
+  framebuffer: Framebuffer<Flat, Dim2, Texture2D<RGBA32F>, Texture2D<Depth32F>>,
+  w: u32,
+  h: u32
 }
 
 impl<'a> SimpleRenderer<'a> {
-  pub fn new(w: u32, h: u32, scene: &mut Scene<'a>) -> Self {
-    SimpleRenderer {
-      program: DefaultProgram3D::new(scene).unwrap(),
-      framebuffer: Framebuffer::new((w, h), 0).unwrap()
-    }
+  /// Return `None` if the default 3D program cannot be found or the framebuffer cannot be created.
+  /// A program that fails to compile is replaced by a fallback until it’s fixed.
+  pub fn new(w: u32, h: u32, scene: &mut Scene<'a>) -> Option<Self> {
+    let program = match DefaultProgram3D::new(scene) {
+      Some(program) => program,
+      None => return None
+    };
+
+    let framebuffer = match Framebuffer::new((w, h), 0) {
+      Ok(framebuffer) => framebuffer,
+      Err(e) => {
+        err!("cannot create the framebuffer of the simple renderer: {:?}", e);
+        return None;
+      }
+    };
+
+    Some(SimpleRenderer {
+      program: program,
+      framebuffer: framebuffer,
+      w: w,
+      h: h
+    })
   }
 }
 
 impl<'a, 'b, 'c> Renderer<'a, 'b, 'c, (&'a Camera<Freefly>, &'a [&'a Object<'c>]), (&'a Texture2D<RGBA32F>, &'a Texture2D<Depth32F>)> for SimpleRenderer<'c> {
   fn render(&'a self, scene: &'b mut Scene<'c>, input: (&'a Camera<Freefly>, &'a [&'a Object<'c>])) -> (&'a Texture2D<RGBA32F>, &'a Texture2D<Depth32F>) {
     let (camera, objects) = input;
-    let program = scene.get_by_id(&self.program).unwrap();
 
-    // reify objects
-    let objects: Vec<_> = objects.iter().map(|object| {
-      (object, scene.get_by_id(&object.model).unwrap())
+    scene.begin_frame();
+
+    let output = (&self.framebuffer.color_slot, &self.framebuffer.depth_slot);
+    let program = match scene.get_by_id(&self.program) {
+      Some(program) => program,
+      None => return output
+    };
+    let uniforms = &self.program.uniforms;
+
+    let mut builtins = scene.builtins;
+    builtins.resolution = [self.w as f32, self.h as f32];
+    builtins.view = *camera.transform().as_ref();
+    builtins.proj = *camera.projection().as_ref();
+
+    // reify objects; the ones which model is gone are skipped
+    let objects: Vec<_> = objects.iter().filter_map(|object| {
+      scene.get_by_id(&object.model).map(|model| (object, model))
     }).collect();
 
-    let tessellations = objects.iter().flat_map(|&(object, ref model)| {
+    let tessellations = objects.iter().flat_map(move |&(object, ref model)| {
       model.parts.iter().map(move |part| {
         Pipe::new(move |program| {
-                    program.update(&DEFAULT_3D_INST, *object.transform().as_ref());
+                    program.update(&uniforms.inst, *object.transform().as_ref());
                   },
                   &part.tess)
       })
     }).collect();
 
     Pipeline::new(&self.framebuffer, [0., 0., 0., 0.], &[], &[], vec![
-      Pipe::new(|program| {
-                  // update the camera
-                  program.update(&DEFAULT_3D_PROJ, *camera.projection().as_ref());
-                  program.update(&DEFAULT_3D_VIEW, *camera.transform().as_ref());
-                },
+      Pipe::new(|_| program.update_builtins(&builtins),
                 ShadingCommand::new(&program, vec![
                   Pipe::new(|_| {}, RenderCommand::new(None, true, tessellations, 1, None))
                 ]))
     ]).run();
 
-    (&self.framebuffer.color_slot, &self.framebuffer.depth_slot)
+    output
   }
 }
@@ -1,54 +1,114 @@
 //! Default shaders.
 
 use luminance::M44;
-use luminance_gl::gl33::Uniform;
 use std::ops::Deref;
 
 use id::Id;
-use shader::Program;
+use morph::MORPH_DEFINE;
+use shader::{Program, ProgramArgs, UniformInterface};
 use scene::Scene;
+use skeleton::SKINNING_DEFINE;
 
-pub type ColorUniform = Uniform<[f32; 3]>;
-pub type Mat44Uniform = Uniform<M44>;
-
-pub const DEFAULT_2D_COLOR: ColorUniform = Uniform::new(0);
+uniform_interface! {
+  /// Uniforms of the default 2D program.
+  pub struct Default2DUniforms {
+    color: [f32; 3]
+  }
+}
 
-pub struct DefaultProgram2D<'a>(Id<'a, Program>);
+pub struct DefaultProgram2D<'a> {
+  id: Id<'a, Program>,
+  pub uniforms: Default2DUniforms
+}
 
 impl<'a> Deref for DefaultProgram2D<'a> {
   type Target = Id<'a, Program>;
 
   fn deref(&self) -> &Self::Target {
-    &self.0
+    &self.id
   }
 }
 
 impl<'a> DefaultProgram2D<'a> {
   pub fn new(scene: &mut Scene<'a>) -> Option<Self> {
-    get_id!(scene, "spectra/default_2d.glsl", vec![ColorUniform::sem("color")]).map(DefaultProgram2D)
+    let id = get_id!(scene, "spectra/default_2d.glsl", ProgramArgs::discover().interface::<Default2DUniforms>());
+
+    id.and_then(|id| {
+      interface(scene, &id).map(|uniforms| {
+        DefaultProgram2D {
+          id: id,
+          uniforms: uniforms
+        }
+      })
+    })
   }
 }
 
-pub const DEFAULT_3D_PROJ: Mat44Uniform = Uniform::new(0);
-pub const DEFAULT_3D_VIEW: Mat44Uniform = Uniform::new(1);
-pub const DEFAULT_3D_INST: Mat44Uniform = Uniform::new(2);
+uniform_interface! {
+  /// Uniforms of the default 3D program.
+  pub struct Default3DUniforms {
+    inst: M44
+  }
+}
 
-pub struct DefaultProgram3D<'a>(Id<'a, Program>);
+pub struct DefaultProgram3D<'a> {
+  id: Id<'a, Program>,
+  pub uniforms: Default3DUniforms
+}
 
 impl<'a> Deref for DefaultProgram3D<'a> {
   type Target = Id<'a, Program>;
 
   fn deref(&self) -> &Self::Target {
-    &self.0
+    &self.id
   }
 }
 
 impl<'a> DefaultProgram3D<'a> {
   pub fn new(scene: &mut Scene<'a>) -> Option<Self> {
-    get_id!(scene, "spectra/default_3d.glsl", vec![
-              Mat44Uniform::sem("proj"),
-              Mat44Uniform::sem("view"),
-              Mat44Uniform::sem("inst")])
-      .map(DefaultProgram3D)
+    DefaultProgram3D::with_args(scene, ProgramArgs::discover())
+  }
+
+  /// Variant deforming vertices of the skinned layout with the joint palette – see
+  /// `Skeleton::palette` and `JOINTS_UNIFORM`.
+  pub fn skinned(scene: &mut Scene<'a>) -> Option<Self> {
+    DefaultProgram3D::with_args(scene, ProgramArgs::discover().define(SKINNING_DEFINE, ""))
   }
+
+  /// Variant blending the morph targets of vertices of the morph layout – see `WEIGHTS_UNIFORM`.
+  pub fn morph(scene: &mut Scene<'a>) -> Option<Self> {
+    DefaultProgram3D::with_args(scene, ProgramArgs::discover().define(MORPH_DEFINE, ""))
+  }
+
+  /// Variant blending the morph targets of vertices of the morph layout, then deforming them with
+  /// the joint palette.
+  pub fn skinned_morph(scene: &mut Scene<'a>) -> Option<Self> {
+    DefaultProgram3D::with_args(scene, ProgramArgs::discover().define(MORPH_DEFINE, "").define(SKINNING_DEFINE, ""))
+  }
+
+  fn with_args(scene: &mut Scene<'a>, args: ProgramArgs) -> Option<Self> {
+    let id = get_id!(scene, "spectra/default_3d.glsl", args.interface::<Default3DUniforms>());
+
+    id.and_then(|id| {
+      interface(scene, &id).map(|uniforms| {
+        DefaultProgram3D {
+          id: id,
+          uniforms: uniforms
+        }
+      })
+    })
+  }
+}
+
+// Get the uniform interface of a program.
+fn interface<'a, I>(scene: &mut Scene<'a>, id: &Id<'a, Program>) -> Option<I> where I: UniformInterface {
+  scene.get_by_id(id).and_then(|program| {
+    match I::from_program(&program) {
+      Ok(uniforms) => Some(uniforms),
+      Err(e) => {
+        err!("{}", e);
+        None
+      }
+    }
+  })
 }
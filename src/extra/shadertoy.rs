@@ -0,0 +1,320 @@
+//! Shadertoy-compatible fullscreen effects.
+
+use luminance::{Dim2, Flat, Mode, RGBA32F, Sampler, Unit};
+use luminance_gl::gl33::{Framebuffer, Pipe, Pipeline, RenderCommand, ShadingCommand, Tess, Texture,
+                         Uniform};
+use std::fs::File;
+use std::io::Read;
+use std::ops::Deref;
+use std::path::Path;
+use std::rc::Rc;
+
+use id::Id;
+use resource::{Cache, Extension, Load, LoadError};
+use scene::Scene;
+use shader::{Program, ProgramArgs, StageSources, UniformError};
+use texture::TextureImage;
+
+pub type Texture2D<A> = Texture<Flat, Dim2, A>;
+
+type ShadertoyFramebuffer = Framebuffer<Flat, Dim2, Texture2D<RGBA32F>, ()>;
+
+const QUAD_VS: &'static str = "\
+vec2[4] CO = vec2[](
+  vec2( 1., -1.),
+  vec2( 1.,  1.),
+  vec2(-1., -1.),
+  vec2(-1.,  1.)
+);
+
+void main() {
+  gl_Position = vec4(CO[gl_VertexID], 0., 1.);
+}
+";
+
+const PRELUDE: &'static str = "\
+uniform float iTime;
+uniform vec3 iResolution;
+uniform vec4 iMouse;
+uniform int iFrame;
+uniform sampler2D iChannel0;
+uniform sampler2D iChannel1;
+uniform sampler2D iChannel2;
+uniform sampler2D iChannel3;
+
+out vec4 spectra_frag;
+";
+
+const FALLBACK: &'static str = "\
+void mainImage(out vec4 fragColor, in vec2 fragCoord) {
+  fragColor = vec4(1., 0., 1., 1.);
+}
+";
+
+const MAIN: &'static str = "
+void main() {
+  mainImage(spectra_frag, gl_FragCoord.xy);
+}
+";
+
+/// Shadertoy program.
+///
+/// If the program is retrieved from the cache, the path must point to a fragment shader written
+/// for Shadertoy – i.e. defining `void mainImage(out vec4 fragColor, in vec2 fragCoord)` and using
+/// `iTime`, `iResolution`, `iMouse`, `iFrame` and `iChannel0..3` freely. No stage pragma is needed.
+///
+/// A program that fails to load the first time renders magenta until it’s fixed.
+pub struct ShadertoyProgram(Program);
+
+impl Deref for ShadertoyProgram {
+  type Target = Program;
+
+  fn deref(&self) -> &Self::Target {
+    &self.0
+  }
+}
+
+impl<'a> Load<'a> for ShadertoyProgram {
+  type Args = ();
+
+  fn load<P>(path: P, _: &mut Cache<'a>, _: Self::Args) -> Result<Self, LoadError> where P: AsRef<Path> {
+    let path = path.as_ref();
+
+    info!("loading shadertoy: {:?}", path);
+
+    let mut src = String::new();
+    let mut file = File::open(path).map_err(|e| LoadError::FileNotFound(path.to_path_buf(), format!("{:?}", e)))?;
+    file.read_to_string(&mut src).map_err(|e| LoadError::ParseFailed(format!("{:?}", e)))?;
+
+    ShadertoyProgram::from_source(&src, path)
+  }
+
+  fn fallback(_: &Self::Args) -> Option<Self> {
+    ShadertoyProgram::from_source(FALLBACK, Path::new("<fallback>")).ok()
+  }
+}
+
+impl Extension for ShadertoyProgram {
+  fn directory() -> &'static str {
+    "shadertoys"
+  }
+}
+
+impl ShadertoyProgram {
+  /// Program sources of a Shadertoy fragment shader: a fullscreen quad vertex shader, and the
+  /// shader wrapped between the Shadertoy uniforms and a `main` calling `mainImage`. Lines of the
+  /// shader are numbered from the start of `path`.
+  pub fn sources(src: &str, path: &Path) -> StageSources {
+    StageSources {
+      vs: QUAD_VS.to_owned(),
+      fs: format!("{}#line 1 0\n{}\n{}", PRELUDE, src, MAIN),
+      files: vec![path.to_owned()],
+      ..StageSources::default()
+    }
+  }
+
+  fn from_source(src: &str, path: &Path) -> Result<Self, LoadError> {
+    Program::from_sources(ShadertoyProgram::sources(src, path), ProgramArgs::discover()).map(ShadertoyProgram)
+  }
+}
+
+/// Input of a Shadertoy pass, bound to an `iChannelN`.
+#[derive(Clone, Debug)]
+pub enum Channel<'a> {
+  /// Nothing – reads as black.
+  Empty,
+  /// A texture image.
+  Texture(Id<'a, TextureImage>),
+  /// The output of a pass of the same effect. If the pass is the reading one or comes after it,
+  /// its output of the previous frame is read instead, which gives feedback.
+  Pass(usize),
+  /// A texture given when rendering the effect – the output of another effect, for instance.
+  Input(usize)
+}
+
+impl<'a> Channel<'a> {
+  /// For a `Pass` channel read by pass `reader` in a frame writing the framebuffers at `write`, the
+  /// pass read along with the framebuffer holding its output.
+  pub fn pass_output(&self, reader: usize, write: usize) -> Option<(usize, usize)> {
+    match *self {
+      // passes that haven’t been rendered yet this frame give their previous output
+      Channel::Pass(j) => Some((j, if j < reader { write } else { 1 - write })),
+      _ => None
+    }
+  }
+}
+
+struct ShadertoyPass<'a> {
+  program: Id<'a, ShadertoyProgram>,
+  channels: Vec<Channel<'a>>,
+  // ping-pong framebuffers; one is written while the other holds the previous frame
+  framebuffers: [ShadertoyFramebuffer; 2]
+}
+
+/// Shadertoy effect.
+///
+/// The effect is made of passes – the buffers and the image of Shadertoy – rendered in order on a
+/// fullscreen quad. The output of the effect is the output of its last pass. `iTime` and `iFrame`
+/// come from the scene built-in uniforms; engine built-ins can be used as well.
+pub struct Shadertoy<'a> {
+  passes: Vec<ShadertoyPass<'a>>,
+  quad: Tess,
+  black: Texture2D<RGBA32F>,
+  mouse: [f32; 4],
+  // number of renders so far, used to flip the framebuffers
+  renders: usize,
+  w: u32,
+  h: u32
+}
+
+impl<'a> Shadertoy<'a> {
+  pub fn new(w: u32, h: u32) -> Self {
+    let black = Texture::new((1, 1), 0, &Sampler::default()).unwrap();
+    black.upload_raw(false, &[0., 0., 0., 1.]);
+
+    Shadertoy {
+      passes: Vec::new(),
+      quad: Tess::attributeless(Mode::TriangleStrip, 4),
+      black: black,
+      mouse: [0., 0., 0., 0.],
+      renders: 0,
+      w: w,
+      h: h
+    }
+  }
+
+  /// Add a pass running a Shadertoy program from the cache, with at most four channels. Return the
+  /// index of the pass, to be used with `Channel::Pass`.
+  pub fn add_pass(&mut self, scene: &mut Scene<'a>, name: &str, channels: Vec<Channel<'a>>) -> Option<usize> {
+    assert!(channels.len() <= 4, "a shadertoy pass has at most four channels");
+
+    let (w, h) = (self.w, self.h);
+
+    get_id!(scene, name).map(|program| {
+      self.passes.push(ShadertoyPass {
+        program: program,
+        channels: channels,
+        framebuffers: [Framebuffer::new((w, h), 0).unwrap(), Framebuffer::new((w, h), 0).unwrap()]
+      });
+
+      self.passes.len() - 1
+    })
+  }
+
+  /// Set `iMouse`: the current position of the mouse when a button is down, and the position of the
+  /// last click.
+  pub fn set_mouse(&mut self, mouse: [f32; 4]) {
+    self.mouse = mouse;
+  }
+
+  /// Render all the passes and return the output of the last one. `inputs` are read by the
+  /// `Channel::Input` channels. The frame is started if it isn’t yet, but not ended – see
+  /// `Scene::end_frame`.
+  pub fn render(&mut self, scene: &mut Scene<'a>, inputs: &[&Texture2D<RGBA32F>]) -> &Texture2D<RGBA32F> {
+    scene.begin_frame();
+
+    let write = self.renders % 2;
+    let builtins = {
+      let mut builtins = scene.builtins;
+      builtins.resolution = [self.w as f32, self.h as f32];
+      builtins
+    };
+
+    for (i, pass) in self.passes.iter().enumerate() {
+      let program = match scene.get_by_id(&pass.program) {
+        Some(program) => program,
+        None => continue
+      };
+
+      let uniforms = match ShadertoyUniforms::new(&program) {
+        Ok(uniforms) => uniforms,
+        Err(e) => {
+          err!("{}", e);
+          continue;
+        }
+      };
+
+      // keep the texture images alive while they’re bound
+      let images: Vec<Option<Rc<TextureImage>>> = pass.channels.iter().map(|channel| {
+        match *channel {
+          Channel::Texture(ref id) => scene.get_by_id(id),
+          _ => None
+        }
+      }).collect();
+
+      let channel = |n: usize| {
+        match pass.channels.get(n) {
+          Some(&Channel::Texture(_)) => images[n].as_ref().map_or(&self.black, |image| &image.texture),
+          Some(channel@&Channel::Pass(_)) => {
+            let (j, read) = channel.pass_output(i, write).unwrap();
+            self.passes.get(j).map_or(&self.black, |pass| &pass.framebuffers[read].color_slot)
+          },
+          Some(&Channel::Input(j)) => inputs.get(j).map_or(&self.black, |input| *input),
+          Some(&Channel::Empty) | None => &self.black
+        }
+      };
+
+      let textures = &[channel(0).into(), channel(1).into(), channel(2).into(), channel(3).into()];
+
+      Pipeline::new(&pass.framebuffers[write], [0., 0., 0., 1.], textures, &[], vec![
+        Pipe::new(|_| {
+          program.update_builtins(&builtins);
+          program.update(&uniforms.time, builtins.time);
+          program.update(&uniforms.resolution, [self.w as f32, self.h as f32, 1.]);
+          program.update(&uniforms.mouse, self.mouse);
+          program.update(&uniforms.frame, builtins.frame as i32);
+          program.update(&uniforms.channels[0], Unit::new(0));
+          program.update(&uniforms.channels[1], Unit::new(1));
+          program.update(&uniforms.channels[2], Unit::new(2));
+          program.update(&uniforms.channels[3], Unit::new(3));
+        }, ShadingCommand::new(&program, vec![
+          Pipe::new(|_| {}, RenderCommand::new(None, true, vec![
+            Pipe::new(|_| {}, &self.quad)], 1, None))
+        ]))
+      ]).run();
+    }
+
+    self.renders += 1;
+
+    self.output_at(write)
+  }
+
+  /// Output of the last render.
+  pub fn output(&self) -> &Texture2D<RGBA32F> {
+    if self.renders == 0 {
+      &self.black
+    } else {
+      self.output_at((self.renders - 1) % 2)
+    }
+  }
+
+  fn output_at(&self, index: usize) -> &Texture2D<RGBA32F> {
+    self.passes.last().map_or(&self.black, |pass| &pass.framebuffers[index].color_slot)
+  }
+}
+
+// Handles on the Shadertoy uniforms.
+struct ShadertoyUniforms {
+  time: Uniform<f32>,
+  resolution: Uniform<[f32; 3]>,
+  mouse: Uniform<[f32; 4]>,
+  frame: Uniform<i32>,
+  channels: [Uniform<Unit>; 4]
+}
+
+impl ShadertoyUniforms {
+  fn new(program: &Program) -> Result<Self, UniformError> {
+    Ok(ShadertoyUniforms {
+      time: program.uniform("iTime")?,
+      resolution: program.uniform("iResolution")?,
+      mouse: program.uniform("iMouse")?,
+      frame: program.uniform("iFrame")?,
+      channels: [
+        program.uniform("iChannel0")?,
+        program.uniform("iChannel1")?,
+        program.uniform("iChannel2")?,
+        program.uniform("iChannel3")?
+      ]
+    })
+  }
+}
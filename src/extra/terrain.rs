@@ -0,0 +1,384 @@
+//! Heightmap terrains.
+//!
+//! A `Terrain` is a grid displaced by a `Heightmap` – read from a grayscale image or sampled from a
+//! function, such as `fractal_noise`. It spans `TerrainArgs::size` on the XZ plane, centered on the
+//! origin, and rises from zero up to `TerrainArgs::height`. Texture coordinates span the whole
+//! terrain.
+//!
+//! A terrain can be split into chunks, each having several levels of detail – see `Terrain::parts`.
+//! Chunks with levels of detail hang skirts along their borders, hiding the cracks between chunks of
+//! different levels.
+//!
+//! If the terrain is retrieved from the cache, the path must point to a heightmap image –
+//! `data/terrains/<name>` – and editing the image reloads the terrain.
+
+use image;
+use luminance::Mode;
+use std::path::Path;
+
+use mesh::{Bounds, MeshData};
+use mesh::vector::normalize;
+use model::{Model, Part, Vertex, VertexLayout, upload_mesh};
+use resource::{Cache, Extension, Load, LoadError, Reload};
+use transform::Position;
+
+/// Heights sampled on a regular grid, in *[0; 1]*.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Heightmap {
+  /// Number of samples along X.
+  pub columns: u32,
+  /// Number of samples along Z.
+  pub rows: u32,
+  /// Heights, row by row.
+  pub heights: Vec<f32>
+}
+
+impl Heightmap {
+  /// Return `None` if there aren’t `columns * rows` heights or if there are fewer than two samples
+  /// along an axis.
+  pub fn new(columns: u32, rows: u32, heights: Vec<f32>) -> Option<Self> {
+    if columns < 2 || rows < 2 || heights.len() != (columns * rows) as usize {
+      return None;
+    }
+
+    Some(Heightmap {
+      columns: columns,
+      rows: rows,
+      heights: heights
+    })
+  }
+
+  /// Read a grayscale image – colored images are converted. The first row of the image is at the
+  /// far end of the terrain, along -Z.
+  pub fn from_image<P>(path: P) -> Result<Self, LoadError> where P: AsRef<Path> {
+    let path = path.as_ref();
+    let image = image::open(path).map_err(|e| LoadError::ConversionFailed(format!("{:?}", e)))?.to_luma();
+    let (columns, rows) = image.dimensions();
+    let heights = image.into_raw().into_iter().map(|h| h as f32 / 255.).collect();
+
+    Heightmap::new(columns, rows, heights).ok_or(LoadError::ConversionFailed(format!("heightmap {:?} is smaller than 2×2", path)))
+  }
+
+  /// Sample a function of the texture coordinates – in *[0; 1]* – at `columns` by `rows` points.
+  pub fn from_fn<F>(columns: u32, rows: u32, f: F) -> Self where F: Fn(f32, f32) -> f32 {
+    let (columns, rows) = (columns.max(2), rows.max(2));
+    let mut heights = Vec::with_capacity((columns * rows) as usize);
+
+    for i in 0..rows {
+      for j in 0..columns {
+        heights.push(f(j as f32 / (columns - 1) as f32, i as f32 / (rows - 1) as f32));
+      }
+    }
+
+    Heightmap {
+      columns: columns,
+      rows: rows,
+      heights: heights
+    }
+  }
+
+  /// Height of a sample. Samples out of the map are clamped to its borders.
+  pub fn sample(&self, column: i32, row: i32) -> f32 {
+    let column = column.max(0).min(self.columns as i32 - 1) as u32;
+    let row = row.max(0).min(self.rows as i32 - 1) as u32;
+
+    self.heights[(row * self.columns + column) as usize]
+  }
+
+  /// Height at texture coordinates, interpolated between the samples around.
+  pub fn height_at(&self, u: f32, v: f32) -> f32 {
+    let x = u.max(0.).min(1.) * (self.columns - 1) as f32;
+    let z = v.max(0.).min(1.) * (self.rows - 1) as f32;
+    let (j, i) = (x.floor() as i32, z.floor() as i32);
+    let (fx, fz) = (x - j as f32, z - i as f32);
+
+    let near = lerp(self.sample(j, i), self.sample(j + 1, i), fx);
+    let far = lerp(self.sample(j, i + 1), self.sample(j + 1, i + 1), fx);
+
+    lerp(near, far, fz)
+  }
+}
+
+/// Arguments of a terrain.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TerrainArgs {
+  /// Size of the terrain along X and Z.
+  pub size: [f32; 2],
+  /// Height of the highest samples of the heightmap.
+  pub height: f32,
+  /// Number of chunks along each axis.
+  pub chunks: u32,
+  /// Number of levels of detail of each chunk, each one having half the resolution of the previous
+  /// one.
+  pub lods: u32,
+  /// Distance to a chunk beyond which it drops one level of detail, then two beyond twice that
+  /// distance, and so on.
+  pub lod_distance: f32
+}
+
+impl Default for TerrainArgs {
+  fn default() -> Self {
+    TerrainArgs {
+      size: [100., 100.],
+      height: 10.,
+      chunks: 1,
+      lods: 1,
+      lod_distance: 50.
+    }
+  }
+}
+
+/// A chunk of a terrain.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TerrainChunk {
+  pub bounds: Bounds,
+  /// Index of the first part of the chunk in the model of the terrain. Its levels of detail are the
+  /// parts following it, finest first.
+  pub first_part: usize,
+  pub lods: usize
+}
+
+pub struct Terrain {
+  /// Model holding the levels of detail of all the chunks as parts – see `TerrainChunk`.
+  pub model: Model,
+  pub chunks: Vec<TerrainChunk>,
+  pub heightmap: Heightmap,
+  args: TerrainArgs
+}
+
+impl Terrain {
+  pub fn new(heightmap: Heightmap, args: TerrainArgs) -> Self {
+    let mut parts = Vec::new();
+    let mut chunks = Vec::new();
+
+    for lods in terrain_meshes(&heightmap, &args) {
+      chunks.push(TerrainChunk {
+        bounds: lods[0].bounds,
+        first_part: parts.len(),
+        lods: lods.len()
+      });
+
+      parts.extend(lods.iter().map(|mesh| Part::new(upload_mesh(mesh, VertexLayout::Basic))));
+    }
+
+    Terrain {
+      model: Model::from_parts(parts),
+      chunks: chunks,
+      heightmap: heightmap,
+      args: args
+    }
+  }
+
+  pub fn args(&self) -> &TerrainArgs {
+    &self.args
+  }
+
+  /// Level of detail of a chunk seen from a point.
+  pub fn lod(&self, chunk: &TerrainChunk, eye: &Position) -> usize {
+    let eye = [eye.x, eye.y, eye.z];
+    let distance = (0..3).map(|i| {
+      let d = (chunk.bounds.min[i] - eye[i]).max(eye[i] - chunk.bounds.max[i]).max(0.);
+      d * d
+    }).sum::<f32>().sqrt();
+
+    ((distance / self.args.lod_distance.max(::std::f32::EPSILON)) as usize).min(chunk.lods - 1)
+  }
+
+  /// Parts to render for a point of view – a level of detail of each chunk.
+  pub fn parts(&self, eye: &Position) -> Vec<&Part> {
+    self.chunks.iter().map(|chunk| &self.model.parts[chunk.first_part + self.lod(chunk, eye)]).collect()
+  }
+
+  /// Height of the ground at a point of the XZ plane. Points off the terrain get the height of its
+  /// closest border.
+  pub fn height_at(&self, x: f32, z: f32) -> f32 {
+    let size = self.args.size;
+    self.heightmap.height_at(x / size[0] + 0.5, z / size[1] + 0.5) * self.args.height
+  }
+}
+
+impl<'a> Load<'a> for Terrain {
+  type Args = TerrainArgs;
+
+  fn cache_key(name: &str, args: &Self::Args) -> String {
+    format!("{}?size={}x{}&height={}&chunks={}&lods={}&lod_distance={}", name, args.size[0], args.size[1], args.height, args.chunks, args.lods, args.lod_distance)
+  }
+
+  fn load<P>(path: P, _: &mut Cache<'a>, args: Self::Args) -> Result<Self, LoadError> where P: AsRef<Path> {
+    let path = path.as_ref();
+
+    info!("loading terrain: {:?}", path);
+
+    Ok(Terrain::new(Heightmap::from_image(path)?, args))
+  }
+}
+
+impl<'a> Reload<'a> for Terrain {
+  fn reload_args(&self) -> Self::Args {
+    self.args
+  }
+}
+
+impl Extension for Terrain {
+  fn directory() -> &'static str {
+    "terrains"
+  }
+}
+
+/// Meshes of a terrain: the levels of detail of each chunk, finest first. Chunks are ordered row by
+/// row, along X first.
+pub fn terrain_meshes(heightmap: &Heightmap, args: &TerrainArgs) -> Vec<Vec<MeshData<Vertex>>> {
+  let (cells_x, cells_z) = (heightmap.columns - 1, heightmap.rows - 1);
+  let (chunks_x, chunks_z) = (args.chunks.max(1).min(cells_x), args.chunks.max(1).min(cells_z));
+  let lods = args.lods.max(1);
+  let mut meshes = Vec::with_capacity((chunks_x * chunks_z) as usize);
+
+  for cz in 0..chunks_z {
+    for cx in 0..chunks_x {
+      let (x0, x1) = (cells_x * cx / chunks_x, cells_x * (cx + 1) / chunks_x);
+      let (z0, z1) = (cells_z * cz / chunks_z, cells_z * (cz + 1) / chunks_z);
+
+      meshes.push((0..lods).map(|lod| {
+        let step = 1 << lod.min(31);
+        chunk_mesh(heightmap, args, &samples(x0, x1, step), &samples(z0, z1, step), lods > 1)
+      }).collect());
+    }
+  }
+
+  meshes
+}
+
+/// Fractal value noise in *[0; 1]*: `octaves` layers of smooth noise, each one having twice the
+/// frequency and half the amplitude of the previous one. Noise varies over a unit of `x` and `y`.
+pub fn fractal_noise(x: f32, y: f32, octaves: u32, seed: u32) -> f32 {
+  let (mut sum, mut total, mut amplitude, mut frequency) = (0., 0., 1., 1.);
+
+  for octave in 0..octaves.max(1) {
+    sum += amplitude * value_noise(x * frequency, y * frequency, seed.wrapping_add(octave));
+    total += amplitude;
+    amplitude *= 0.5;
+    frequency *= 2.;
+  }
+
+  sum / total
+}
+
+// Indices of the samples from `first` to `last` every `step`. The last one is always included so
+// that neighbor chunks share their borders.
+fn samples(first: u32, last: u32, step: u32) -> Vec<u32> {
+  let mut samples = Vec::new();
+  let mut i = first;
+
+  while i < last {
+    samples.push(i);
+    i += step;
+  }
+
+  samples.push(last);
+  samples
+}
+
+// Grid of the given samples. Normals come from the full heightmap, so that all the levels of detail
+// are lit alike.
+fn chunk_mesh(heightmap: &Heightmap, args: &TerrainArgs, xs: &[u32], zs: &[u32], skirts: bool) -> MeshData<Vertex> {
+  let (cells_x, cells_z) = ((heightmap.columns - 1) as f32, (heightmap.rows - 1) as f32);
+  let (dx, dz) = (args.size[0] / cells_x, args.size[1] / cells_z);
+  let (nx, nz) = (xs.len() as u32, zs.len() as u32);
+  let mut vertices = Vec::with_capacity((nx * nz) as usize);
+
+  for &i in zs {
+    for &j in xs {
+      let (i, j) = (i as i32, j as i32);
+      let (u, v) = (j as f32 / cells_x, i as f32 / cells_z);
+
+      // central differences, one-sided on the borders
+      let (j0, j1) = ((j - 1).max(0), (j + 1).min(cells_x as i32));
+      let (i0, i1) = ((i - 1).max(0), (i + 1).min(cells_z as i32));
+      let sx = (heightmap.sample(j1, i) - heightmap.sample(j0, i)) * args.height / ((j1 - j0) as f32 * dx);
+      let sz = (heightmap.sample(j, i1) - heightmap.sample(j, i0)) * args.height / ((i1 - i0) as f32 * dz);
+
+      vertices.push((
+        [(u - 0.5) * args.size[0], heightmap.sample(j, i) * args.height, (v - 0.5) * args.size[1]],
+        normalize([-sx, 1., -sz]),
+        [u, v]
+      ));
+    }
+  }
+
+  let mut indices = Vec::with_capacity(((nx - 1) * (nz - 1) * 6) as usize);
+
+  for i in 0..nz - 1 {
+    for j in 0..nx - 1 {
+      let a = i * nx + j;
+      let (b, c, d) = (a + 1, a + nx, a + nx + 1);
+
+      indices.extend_from_slice(&[a, c, b, b, c, d]);
+    }
+  }
+
+  if skirts {
+    add_skirts(&mut vertices, &mut indices, nx, nz, dx.min(dz));
+  }
+
+  MeshData::new(Mode::Triangle, vertices, Some(indices))
+}
+
+// Hang a strip facing outwards along the borders of a grid of `nx` by `nz` vertices. The strip is
+// deep enough to cover any crack with the neighbor chunks, which cannot be deeper than the height
+// range of the chunk.
+fn add_skirts(vertices: &mut Vec<Vertex>, indices: &mut Vec<u32>, nx: u32, nz: u32, min_depth: f32) {
+  let (low, high) = vertices.iter().fold((::std::f32::INFINITY, ::std::f32::NEG_INFINITY), |(low, high), v| {
+    (low.min(v.0[1]), high.max(v.0[1]))
+  });
+  let depth = high - low + min_depth;
+
+  // walk the borders with the outside on the left when looking down
+  let mut border: Vec<u32> = (0..nx - 1).collect();
+  border.extend((0..nz - 1).map(|i| i * nx + nx - 1));
+  border.extend((1..nx).rev().map(|j| (nz - 1) * nx + j));
+  border.extend((1..nz).rev().map(|i| i * nx));
+
+  let first = vertices.len() as u32;
+  let count = border.len() as u32;
+
+  for &k in &border {
+    let (p, n, uv) = vertices[k as usize];
+    vertices.push(([p[0], p[1] - depth, p[2]], n, uv));
+  }
+
+  for k in 0..count {
+    let (p, q) = (border[k as usize], border[((k + 1) % count) as usize]);
+    let (bp, bq) = (first + k, first + (k + 1) % count);
+
+    indices.extend_from_slice(&[p, q, bp, q, bq, bp]);
+  }
+}
+
+fn value_noise(x: f32, y: f32, seed: u32) -> f32 {
+  let (x0, y0) = (x.floor(), y.floor());
+  let (fx, fy) = (smooth(x - x0), smooth(y - y0));
+  let (i, j) = (x0 as i32, y0 as i32);
+
+  let near = lerp(hash(i, j, seed), hash(i + 1, j, seed), fx);
+  let far = lerp(hash(i, j + 1, seed), hash(i + 1, j + 1, seed), fx);
+
+  lerp(near, far, fy)
+}
+
+// Pseudo-random value in [0; 1] of a lattice point.
+fn hash(x: i32, y: i32, seed: u32) -> f32 {
+  let mut h = (x as u32).wrapping_mul(0x8da6b343) ^ (y as u32).wrapping_mul(0xd8163841) ^ seed.wrapping_mul(0xcb1ab31f);
+  h ^= h >> 13;
+  h = h.wrapping_mul(0x5bd1e995);
+  h ^= h >> 15;
+
+  (h & 0xffffff) as f32 / 0xffffff as f32
+}
+
+fn smooth(t: f32) -> f32 {
+  t * t * (3. - 2. * t)
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+  a + (b - a) * t
+}
@@ -0,0 +1,775 @@
+//! GLSL validation.
+//!
+//! This module checks preprocessed shader sources without any GL context – or on machines without
+//! GPU at all – and reports problems with the file, stage and line they come from. Preprocessor
+//! conditionals are evaluated, along with the defines of the program variant, so that only the
+//! active code is checked.
+//!
+//! Errors are problems the driver would reject: unbalanced brackets, unterminated comments and
+//! conditionals, malformed or unknown directives, invalid `#if` expressions, `#error` directives,
+//! non-ASCII characters and stages without a `main` function. They fail the load of a program.
+//! Warnings come from heuristics – such as a missing `;` – and are only logged: there’s no grammar
+//! nor type checking here, so the driver has the final say on everything else.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use resource::LoadError;
+use shader::{Defines, StageKind, StageSources, preprocess};
+
+/// Severity of a GLSL diagnostic.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Severity {
+  /// The driver would reject the program.
+  Error,
+  /// Likely mistake, found by a heuristic.
+  Warning
+}
+
+/// A located GLSL diagnostic.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct GlslDiagnostic {
+  pub severity: Severity,
+  /// File the diagnostic is in.
+  pub file: PathBuf,
+  /// Stage the diagnostic is in.
+  pub stage: StageKind,
+  /// Line in `file`.
+  pub line: usize,
+  /// Description of the issue.
+  pub message: String,
+  /// Line of source the diagnostic is on.
+  pub snippet: String
+}
+
+impl GlslDiagnostic {
+  pub fn is_error(&self) -> bool {
+    self.severity == Severity::Error
+  }
+}
+
+impl fmt::Display for GlslDiagnostic {
+  fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+    let severity = match self.severity {
+      Severity::Error => "error",
+      Severity::Warning => "warning"
+    };
+
+    write!(f, "{}:{}: {}: [{}] {}\n  | {}", self.file.display(), self.line, severity, self.stage.name(), self.message, self.snippet)
+  }
+}
+
+/// Validate all the stages of a program.
+pub fn validate(sources: &StageSources) -> Vec<GlslDiagnostic> {
+  let mut diagnostics = Vec::new();
+
+  for (stage, src) in sources.stages() {
+    diagnostics.extend(validate_stage(stage, src, &sources.files));
+  }
+
+  diagnostics
+}
+
+/// Validate the source of a single stage. `files` maps the source string numbers used in the
+/// `#line` directives to files.
+pub fn validate_stage(stage: StageKind, src: &str, files: &[PathBuf]) -> Vec<GlslDiagnostic> {
+  let lexed = lex(src);
+  let mut diagnostics: Vec<_> = lexed.diagnostics;
+
+  check_brackets(&lexed.tokens, &mut diagnostics);
+  check_main(stage, &lexed.tokens, &mut diagnostics);
+  check_missing_semicolons(&lexed.tokens, &lexed.macros, &mut diagnostics);
+
+  diagnostics.into_iter().map(|(loc, severity, message)| {
+    GlslDiagnostic {
+      severity: severity,
+      file: files.get(loc.file_nb).map_or(PathBuf::from("<unknown>"), |file| file.clone()),
+      stage: stage,
+      line: loc.line,
+      message: message,
+      snippet: loc.snippet.trim().to_owned()
+    }
+  }).collect()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Token<'s> {
+  Ident(&'s str),
+  Number(&'s str),
+  Punct(char)
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Loc<'s> {
+  file_nb: usize,
+  line: usize,
+  snippet: &'s str
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Located<'s> {
+  token: Token<'s>,
+  loc: Loc<'s>
+}
+
+type Diagnostics<'s> = Vec<(Loc<'s>, Severity, String)>;
+
+// Macros by name, with their body; function-like macros have none.
+type Macros<'s> = HashMap<&'s str, Option<&'s str>>;
+
+struct Lexed<'s> {
+  // tokens of the active code only
+  tokens: Vec<Located<'s>>,
+  macros: Macros<'s>,
+  diagnostics: Diagnostics<'s>
+}
+
+// A conditional being lexed.
+struct Conditional {
+  // is the code around the conditional active?
+  outer: bool,
+  // has a branch been taken yet?
+  taken: bool,
+  // is the current branch active?
+  active: bool,
+  past_else: bool
+}
+
+const DIRECTIVES: &'static [&'static str] = &[
+  "define", "undef", "if", "ifdef", "ifndef", "else", "elif", "endif", "error", "pragma",
+  "extension", "version", "line"
+];
+
+// Split a source into tokens, tracking locations through #line directives and evaluating the
+// conditionals. #line directives are followed even in inactive branches, as includes are inlined
+// whatever the conditionals.
+fn lex<'s>(src: &'s str) -> Lexed<'s> {
+  let mut lexed = Lexed {
+    tokens: Vec::new(),
+    macros: HashMap::new(),
+    diagnostics: Vec::new()
+  };
+  let mut file_nb = 0;
+  let mut line_nb = 1;
+  let mut in_comment: Option<Loc> = None;
+  let mut conditionals: Vec<Conditional> = Vec::new();
+
+  for line in src.lines() {
+    let loc = Loc { file_nb: file_nb, line: line_nb, snippet: line };
+    let trimmed = line.trim();
+    let active = conditionals.last().map_or(true, |cond| cond.active);
+
+    line_nb += 1;
+
+    if in_comment.is_none() && trimmed.starts_with('#') {
+      let directive_line = trimmed[1..].trim_left();
+      let name_len = directive_line.find(|c: char| !c.is_alphanumeric() && c != '_').unwrap_or(directive_line.len());
+      let directive = &directive_line[..name_len];
+      // arguments of the directive, without trailing comment
+      let rest = directive_line[name_len..].split("//").next().unwrap_or("").trim();
+      let mut words = rest.split_whitespace();
+
+      match directive {
+        "line" => {
+          match (words.next().and_then(|n| n.parse().ok()), words.next().map(|f| f.parse())) {
+            (Some(n), None) => line_nb = n,
+            (Some(n), Some(Ok(f))) => {
+              line_nb = n;
+              file_nb = f;
+            },
+            _ => lexed.diagnostics.push((loc, Severity::Error, "malformed #line directive".to_owned()))
+          }
+        },
+        "if" | "ifdef" | "ifndef" => {
+          // the conditions of inactive code are not evaluated
+          let taken = active && match directive {
+            "if" => evaluate_condition(rest, &lexed.macros, loc, &mut lexed.diagnostics),
+            _ => {
+              match words.next() {
+                Some(name) => lexed.macros.contains_key(name) == (directive == "ifdef"),
+                None => {
+                  lexed.diagnostics.push((loc, Severity::Error, format!("#{} without a macro name", directive)));
+                  false
+                }
+              }
+            }
+          };
+
+          conditionals.push(Conditional { outer: active, taken: taken, active: taken, past_else: false });
+        },
+        "elif" => {
+          match conditionals.last_mut() {
+            Some(ref cond) if cond.past_else => lexed.diagnostics.push((loc, Severity::Error, "#elif after #else".to_owned())),
+            Some(cond) => {
+              cond.active = cond.outer && !cond.taken && evaluate_condition(rest, &lexed.macros, loc, &mut lexed.diagnostics);
+              cond.taken = cond.taken || cond.active;
+            },
+            None => lexed.diagnostics.push((loc, Severity::Error, "#elif without #if".to_owned()))
+          }
+        },
+        "else" => {
+          match conditionals.last_mut() {
+            Some(ref cond) if cond.past_else => lexed.diagnostics.push((loc, Severity::Error, "#else after #else".to_owned())),
+            Some(cond) => {
+              cond.active = cond.outer && !cond.taken;
+              cond.taken = true;
+              cond.past_else = true;
+            },
+            None => lexed.diagnostics.push((loc, Severity::Error, "#else without #if".to_owned()))
+          }
+        },
+        "endif" => {
+          if conditionals.pop().is_none() {
+            lexed.diagnostics.push((loc, Severity::Error, "#endif without #if".to_owned()));
+          }
+        },
+        // the other directives only matter in active code
+        _ if !active => {},
+        "define" => {
+          let name_len = rest.find(|c: char| !c.is_alphanumeric() && c != '_').unwrap_or(rest.len());
+          let name = &rest[..name_len];
+
+          if name.is_empty() {
+            lexed.diagnostics.push((loc, Severity::Error, "#define without a macro name".to_owned()));
+          } else if rest[name_len..].starts_with('(') {
+            lexed.macros.insert(name, None);
+          } else {
+            lexed.macros.insert(name, Some(rest[name_len..].trim()));
+          }
+        },
+        "undef" => {
+          if let Some(name) = words.next() {
+            lexed.macros.remove(name);
+          }
+        },
+        "version" => {
+          if let Some(version) = words.next() {
+            lexed.macros.insert("__VERSION__", Some(version));
+          }
+
+          if words.next() != Some("compatibility") {
+            lexed.macros.insert("GL_core_profile", Some("1"));
+          }
+        },
+        "error" => lexed.diagnostics.push((loc, Severity::Error, format!("#error {}", rest))),
+        "" => {}, // null directive
+        _ if DIRECTIVES.contains(&directive) => {},
+        _ => lexed.diagnostics.push((loc, Severity::Error, format!("unknown preprocessor directive #{}", directive)))
+      }
+
+      continue;
+    }
+
+    let bytes = line.as_bytes();
+    let mut i = 0;
+
+    while i < bytes.len() {
+      if in_comment.is_some() {
+        match line[i..].find("*/") {
+          Some(j) => {
+            in_comment = None;
+            i += j + 2;
+            continue;
+          },
+          None => break
+        }
+      }
+
+      let c = bytes[i] as char;
+
+      if c.is_whitespace() {
+        i += 1;
+      } else if line[i..].starts_with("//") {
+        break;
+      } else if line[i..].starts_with("/*") {
+        in_comment = Some(loc);
+        i += 2;
+      } else if !active {
+        // skip inactive code, only looking for comments
+        i += 1;
+      } else if bytes[i] >= 128 {
+        lexed.diagnostics.push((loc, Severity::Error, "non-ASCII character".to_owned()));
+        break;
+      } else if c.is_alphabetic() || c == '_' {
+        let start = i;
+        while i < bytes.len() && ((bytes[i] as char).is_alphanumeric() || bytes[i] == b'_') {
+          i += 1;
+        }
+
+        lexed.tokens.push(Located { token: Token::Ident(&line[start..i]), loc: loc });
+      } else if c.is_digit(10) || (c == '.' && i + 1 < bytes.len() && (bytes[i + 1] as char).is_digit(10)) {
+        let start = i;
+        while i < bytes.len() && ((bytes[i] as char).is_alphanumeric() || bytes[i] == b'.' || ((bytes[i] == b'+' || bytes[i] == b'-') && (bytes[i - 1] == b'e' || bytes[i - 1] == b'E'))) {
+          i += 1;
+        }
+
+        lexed.tokens.push(Located { token: Token::Number(&line[start..i]), loc: loc });
+      } else {
+        lexed.tokens.push(Located { token: Token::Punct(c), loc: loc });
+        i += 1;
+      }
+    }
+  }
+
+  if let Some(loc) = in_comment {
+    lexed.diagnostics.push((loc, Severity::Error, "unterminated comment".to_owned()));
+  }
+
+  if !conditionals.is_empty() {
+    let loc = Loc { file_nb: file_nb, line: line_nb - 1, snippet: src.lines().last().unwrap_or("") };
+    lexed.diagnostics.push((loc, Severity::Error, "unterminated conditional: missing #endif".to_owned()));
+  }
+
+  lexed
+}
+
+// Evaluate the condition of an #if or #elif directive. An invalid condition is reported and is
+// false.
+fn evaluate_condition<'s>(expr: &str, macros: &Macros, loc: Loc<'s>, diagnostics: &mut Diagnostics<'s>) -> bool {
+  match evaluate(expr, macros) {
+    Ok(value) => value != 0,
+    Err(e) => {
+      diagnostics.push((loc, Severity::Error, format!("invalid #if expression: {}", e)));
+      false
+    }
+  }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum ExprToken {
+  Number(i64),
+  Ident(String),
+  Op(&'static str)
+}
+
+const OPERATORS: &'static [&'static str] = &[
+  "&&", "||", "==", "!=", "<=", ">=", "<<", ">>", "+", "-", "*", "/", "%", "<", ">", "&", "|", "^",
+  "!", "~", "(", ")"
+];
+
+/// Evaluate a preprocessor expression – the condition of an `#if` – with the given macros, as
+/// `name => body` pairs. Function-like macros have no body.
+///
+/// As GLSL requires, identifiers that are not macros are errors – except in operands that are not
+/// evaluated, such as in `defined(N) && N > 1`. Undefined `GL_*` macros – extensions the driver may
+/// not support – are `0`.
+pub fn evaluate(expr: &str, macros: &HashMap<&str, Option<&str>>) -> Result<i64, String> {
+  let tokens = expand(&expr_tokens(expr)?, macros, 0)?;
+  let mut parser = ExprParser { tokens: &tokens, pos: 0 };
+  let value = parser.binary(1, true)?;
+
+  match tokens.get(parser.pos) {
+    Some(token) => Err(format!("unexpected {:?}", token)),
+    None => Ok(value)
+  }
+}
+
+fn expr_tokens(expr: &str) -> Result<Vec<ExprToken>, String> {
+  let mut tokens = Vec::new();
+  let mut i = 0;
+
+  while i < expr.len() {
+    let rest = &expr[i..];
+    let c = rest.chars().next().unwrap_or(' ');
+    let word_len = rest.find(|c: char| !c.is_alphanumeric() && c != '_').unwrap_or(rest.len());
+
+    if c.is_whitespace() {
+      i += c.len_utf8();
+    } else if c.is_digit(10) {
+      let word = rest[..word_len].trim_right_matches(|c| c == 'u' || c == 'U');
+      let parsed = if word.starts_with("0x") || word.starts_with("0X") {
+        i64::from_str_radix(&word[2..], 16)
+      } else if word.len() > 1 && word.starts_with('0') {
+        i64::from_str_radix(&word[1..], 8)
+      } else {
+        word.parse()
+      };
+
+      tokens.push(ExprToken::Number(parsed.map_err(|_| format!("invalid number {}", &rest[..word_len]))?));
+      i += word_len;
+    } else if c.is_alphabetic() || c == '_' {
+      tokens.push(ExprToken::Ident(rest[..word_len].to_owned()));
+      i += word_len;
+    } else {
+      match OPERATORS.iter().find(|op| rest.starts_with(*op)) {
+        Some(op) => {
+          tokens.push(ExprToken::Op(op));
+          i += op.len();
+        },
+        None => return Err(format!("unexpected character {}", c))
+      }
+    }
+  }
+
+  Ok(tokens)
+}
+
+// Replace the macros by their bodies and `defined` operators by their values.
+fn expand(tokens: &[ExprToken], macros: &HashMap<&str, Option<&str>>, depth: u32) -> Result<Vec<ExprToken>, String> {
+  if depth > 64 {
+    return Err("macros expand recursively".to_owned());
+  }
+
+  let mut expanded = Vec::with_capacity(tokens.len());
+  let mut i = 0;
+
+  while i < tokens.len() {
+    match tokens[i] {
+      ExprToken::Ident(ref ident) if *ident == "defined" => {
+        let (name, next) = match (tokens.get(i + 1), tokens.get(i + 2), tokens.get(i + 3)) {
+          (Some(&ExprToken::Op("(")), Some(&ExprToken::Ident(ref name)), Some(&ExprToken::Op(")"))) => (name, i + 4),
+          (Some(&ExprToken::Ident(ref name)), _, _) => (name, i + 2),
+          _ => return Err("defined without a macro name".to_owned())
+        };
+
+        expanded.push(ExprToken::Number(macros.contains_key(name.as_str()) as i64));
+        i = next;
+      },
+      ExprToken::Ident(ref ident) => {
+        match macros.get(ident.as_str()) {
+          Some(&Some(body)) => expanded.extend(expand(&expr_tokens(body)?, macros, depth + 1)?),
+          Some(&None) => return Err(format!("function-like macro {}", ident)),
+          None if ident.starts_with("GL_") => expanded.push(ExprToken::Number(0)),
+          // only an error if evaluated
+          None => expanded.push(ExprToken::Ident(ident.clone()))
+        }
+
+        i += 1;
+      },
+      ref token => {
+        expanded.push(token.clone());
+        i += 1;
+      }
+    }
+  }
+
+  Ok(expanded)
+}
+
+// Precedence climbing parser of expanded expressions. Operands that aren’t evaluated – `live` is
+// false – are only parsed.
+struct ExprParser<'t> {
+  tokens: &'t [ExprToken],
+  pos: usize
+}
+
+impl<'t> ExprParser<'t> {
+  fn binary(&mut self, min_precedence: u32, live: bool) -> Result<i64, String> {
+    let mut lhs = self.unary(live)?;
+
+    loop {
+      let (op, precedence) = match self.tokens.get(self.pos) {
+        Some(&ExprToken::Op(op)) => {
+          match binary_precedence(op) {
+            Some(precedence) if precedence >= min_precedence => (op, precedence),
+            _ => break
+          }
+        },
+        _ => break
+      };
+
+      self.pos += 1;
+
+      let rhs_live = match op {
+        "&&" => live && lhs != 0,
+        "||" => live && lhs == 0,
+        _ => live
+      };
+      let rhs = self.binary(precedence + 1, rhs_live)?;
+
+      lhs = if live { apply(op, lhs, rhs, rhs_live)? } else { 0 };
+    }
+
+    Ok(lhs)
+  }
+
+  fn unary(&mut self, live: bool) -> Result<i64, String> {
+    let token = self.tokens.get(self.pos).cloned();
+    self.pos += 1;
+
+    match token {
+      Some(ExprToken::Number(n)) => Ok(n),
+      Some(ExprToken::Ident(ref ident)) if live => Err(format!("undefined macro {}", ident)),
+      Some(ExprToken::Ident(_)) => Ok(0),
+      Some(ExprToken::Op("(")) => {
+        let value = self.binary(1, live)?;
+
+        match self.tokens.get(self.pos) {
+          Some(&ExprToken::Op(")")) => {
+            self.pos += 1;
+            Ok(value)
+          },
+          _ => Err("missing `)`".to_owned())
+        }
+      },
+      Some(ExprToken::Op("+")) => self.unary(live),
+      Some(ExprToken::Op("-")) => self.unary(live).map(|value| value.wrapping_neg()),
+      Some(ExprToken::Op("~")) => self.unary(live).map(|value| !value),
+      Some(ExprToken::Op("!")) => self.unary(live).map(|value| (value == 0) as i64),
+      Some(ExprToken::Op(op)) => Err(format!("unexpected `{}`", op)),
+      None => Err("missing operand".to_owned())
+    }
+  }
+}
+
+fn binary_precedence(op: &str) -> Option<u32> {
+  match op {
+    "||" => Some(1),
+    "&&" => Some(2),
+    "|" => Some(3),
+    "^" => Some(4),
+    "&" => Some(5),
+    "==" | "!=" => Some(6),
+    "<" | ">" | "<=" | ">=" => Some(7),
+    "<<" | ">>" => Some(8),
+    "+" | "-" => Some(9),
+    "*" | "/" | "%" => Some(10),
+    _ => None
+  }
+}
+
+// Apply a binary operator. `rhs_live` tells whether the right operand was evaluated.
+fn apply(op: &str, a: i64, b: i64, rhs_live: bool) -> Result<i64, String> {
+  let value = match op {
+    "||" => (a != 0 || (rhs_live && b != 0)) as i64,
+    "&&" => (a != 0 && rhs_live && b != 0) as i64,
+    "|" => a | b,
+    "^" => a ^ b,
+    "&" => a & b,
+    "==" => (a == b) as i64,
+    "!=" => (a != b) as i64,
+    "<" => (a < b) as i64,
+    ">" => (a > b) as i64,
+    "<=" => (a <= b) as i64,
+    ">=" => (a >= b) as i64,
+    "<<" => a.wrapping_shl(b as u32),
+    ">>" => a.wrapping_shr(b as u32),
+    "+" => a.wrapping_add(b),
+    "-" => a.wrapping_sub(b),
+    "*" => a.wrapping_mul(b),
+    "/" | "%" if b == 0 => return Err("division by zero".to_owned()),
+    "/" => a.wrapping_div(b),
+    "%" => a.wrapping_rem(b),
+    _ => return Err(format!("unknown operator `{}`", op))
+  };
+
+  Ok(value)
+}
+
+fn check_brackets<'s>(tokens: &[Located<'s>], diagnostics: &mut Diagnostics<'s>) {
+  let mut open: Vec<(char, Loc)> = Vec::new();
+
+  for tok in tokens {
+    match tok.token {
+      Token::Punct(c@'(') | Token::Punct(c@'[') | Token::Punct(c@'{') => open.push((c, tok.loc)),
+      Token::Punct(c@')') | Token::Punct(c@']') | Token::Punct(c@'}') => {
+        let expected = match c { ')' => '(', ']' => '[', _ => '{' };
+
+        match open.pop() {
+          Some((o, _)) if o == expected => {},
+          Some((o, _)) => {
+            diagnostics.push((tok.loc, Severity::Error, format!("`{}` closes `{}`", c, o)));
+            return;
+          },
+          None => {
+            diagnostics.push((tok.loc, Severity::Error, format!("unexpected `{}`", c)));
+            return;
+          }
+        }
+      },
+      _ => {}
+    }
+  }
+
+  if let Some(&(c, loc)) = open.last() {
+    diagnostics.push((loc, Severity::Error, format!("unclosed `{}`", c)));
+  }
+}
+
+fn check_main<'s>(stage: StageKind, tokens: &[Located<'s>], diagnostics: &mut Diagnostics<'s>) {
+  let has_main = tokens.windows(3).any(|w| {
+    w[0].token == Token::Ident("void") && w[1].token == Token::Ident("main") && w[2].token == Token::Punct('(')
+  });
+
+  if !has_main {
+    if let Some(last) = tokens.last() {
+      diagnostics.push((last.loc, Severity::Error, format!("no main function in the {}", stage.name())));
+    }
+  }
+}
+
+const KEYWORDS: &'static [&'static str] = &[
+  "attribute", "break", "buffer", "case", "centroid", "coherent", "const", "continue", "default",
+  "discard", "do", "else", "flat", "for", "highp", "if", "in", "inout", "invariant", "layout",
+  "lowp", "mediump", "noperspective", "out", "patch", "precise", "precision", "readonly",
+  "restrict", "return", "sample", "shared", "smooth", "struct", "subroutine", "switch", "uniform",
+  "varying", "volatile", "while", "writeonly"
+];
+
+/// Is the identifier a built-in GLSL type?
+pub fn is_builtin_type(ident: &str) -> bool {
+  match ident {
+    "void" | "bool" | "int" | "uint" | "float" | "double" => true,
+    _ => {
+      let vector = ["vec", "ivec", "uvec", "bvec", "dvec"].iter().any(|p| {
+        ident.starts_with(p) && ["2", "3", "4"].contains(&&ident[p.len()..])
+      });
+      let matrix = ["mat", "dmat"].iter().any(|p| {
+        ident.starts_with(p) && ["2", "3", "4", "2x2", "2x3", "2x4", "3x2", "3x3", "3x4", "4x2", "4x3", "4x4"].contains(&&ident[p.len()..])
+      });
+      let opaque = ["sampler", "isampler", "usampler", "image", "iimage", "uimage"].iter().any(|p| {
+        ident.starts_with(p) && ident[p.len()..].chars().next().map_or(false, |c| c.is_digit(10) || c.is_uppercase())
+      });
+
+      vector || matrix || opaque
+    }
+  }
+}
+
+// A value immediately followed by a declaration is a statement missing its semicolon:
+//
+//   float a = 1.
+//   float b = 2.;
+fn check_missing_semicolons<'s>(tokens: &[Located<'s>], macros: &Macros<'s>, diagnostics: &mut Diagnostics<'s>) {
+  for w in tokens.windows(3) {
+    let ends_value = match w[0].token {
+      Token::Number(_) | Token::Punct(']') => true,
+      Token::Ident(ident) => !KEYWORDS.contains(&ident) && !is_builtin_type(ident) && !macros.contains_key(ident),
+      _ => false
+    };
+    let starts_declaration = match (w[1].token, w[2].token) {
+      (Token::Ident(ty), Token::Ident(_)) => is_builtin_type(ty),
+      _ => false
+    };
+
+    if ends_value && starts_declaration {
+      diagnostics.push((w[0].loc, Severity::Warning, "missing `;`".to_owned()));
+    }
+  }
+}
+
+/// Validate a program file as `Program::load` does, but without any GL context.
+pub fn validate_program<P, R>(path: P, root: R, defines: &Defines) -> Result<Vec<GlslDiagnostic>, LoadError>
+    where P: AsRef<Path>,
+          R: AsRef<Path> {
+  let mut sources = preprocess(path, root)?;
+  sources.define(defines);
+
+  Ok(validate(&sources))
+}
+
+/// A uniform declaration.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct UniformDecl {
+  /// Name of the uniform.
+  pub name: String,
+  /// GLSL type of the uniform. Arrays are suffixed with their size, as in `float[4]`.
+  pub ty: String
+}
+
+impl UniformDecl {
+  pub fn new(name: &str, ty: &str) -> Self {
+    UniformDecl {
+      name: name.to_owned(),
+      ty: ty.to_owned()
+    }
+  }
+}
+
+/// Extract the uniform declarations of a stage source, in declaration order. Uniform blocks are
+/// ignored.
+pub fn uniforms(src: &str) -> Vec<UniformDecl> {
+  let lexed = lex(src);
+  let tokens: Vec<_> = lexed.tokens.iter().map(|tok| tok.token).collect();
+  let mut decls = Vec::new();
+  let mut depth = 0;
+  let mut i = 0;
+
+  while i < tokens.len() {
+    match tokens[i] {
+      Token::Punct('{') => depth += 1,
+      Token::Punct('}') => depth -= 1,
+      Token::Ident("uniform") if depth == 0 => {
+        i += 1;
+
+        // precision qualifier
+        match tokens.get(i) {
+          Some(&Token::Ident("highp")) | Some(&Token::Ident("mediump")) | Some(&Token::Ident("lowp")) => i += 1,
+          _ => {}
+        }
+
+        let ty = match (tokens.get(i), tokens.get(i + 1)) {
+          (Some(&Token::Ident(ty)), Some(&Token::Ident(_))) => ty,
+          _ => continue // uniform block or garbage
+        };
+
+        i += 1;
+
+        // declarators
+        while let Some(&Token::Ident(name)) = tokens.get(i) {
+          i += 1;
+
+          let decl = match (tokens.get(i), tokens.get(i + 1), tokens.get(i + 2)) {
+            (Some(&Token::Punct('[')), Some(&Token::Number(n)), Some(&Token::Punct(']'))) => {
+              i += 3;
+              UniformDecl::new(name, &format!("{}[{}]", ty, n))
+            },
+            _ => UniformDecl::new(name, ty)
+          };
+
+          if !decls.contains(&decl) {
+            decls.push(decl);
+          }
+
+          // skip the initializer, if any
+          let mut parens = 0;
+          while i < tokens.len() {
+            match tokens[i] {
+              Token::Punct('(') => parens += 1,
+              Token::Punct(')') => parens -= 1,
+              Token::Punct(',') | Token::Punct(';') if parens == 0 => break,
+              _ => {}
+            }
+
+            i += 1;
+          }
+
+          match tokens.get(i) {
+            Some(&Token::Punct(',')) => i += 1,
+            _ => break
+          }
+        }
+
+        continue;
+      },
+      _ => {}
+    }
+
+    i += 1;
+  }
+
+  decls
+}
+
+/// Extract the uniform declarations of all the stages of a program. A uniform declared in several
+/// stages must have the same type in all of them.
+pub fn program_uniforms(sources: &StageSources) -> Result<Vec<UniformDecl>, String> {
+  let mut decls: Vec<UniformDecl> = Vec::new();
+
+  for (stage, src) in sources.stages() {
+    for decl in uniforms(src) {
+      match decls.iter().find(|d| d.name == decl.name) {
+        Some(d) if d.ty != decl.ty => {
+          return Err(format!("uniform {} is declared as {} in the {} but as {} in another stage", decl.name, decl.ty, stage.name(), d.ty));
+        },
+        Some(_) => continue,
+        None => {}
+      }
+
+      decls.push(decl);
+    }
+  }
+
+  Ok(decls)
+}
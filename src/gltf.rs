@@ -0,0 +1,1544 @@
+//! glTF 2.0 support.
+//!
+//! Both the JSON form – `.gltf` – and the binary container – `.glb` – are read. Buffers can be
+//! embedded as base64 data URIs, stored in the binary chunk of a `.glb` file or live in files next
+//! to the model; the latter are hot-reloaded along with it.
+//!
+//! Meshes become `Model`s – see `Model` and `ModelArgs::mesh` – the node hierarchy becomes `Node`s
+//! that can be turned into `Object`s and animations become `Spline`s driving those nodes. Skins
+//! become `Skeleton`s, which joints are driven by the same animations – see `Skeleton::clip`.
+
+use luminance::{Sampler, tess};
+use serde_json::from_slice;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use id::Id;
+use linear::{Quaternion, UnitQuaternion, Vector3};
+use material::Material;
+use mesh::MeshData;
+use mesh::optimize;
+use mesh::vector::{add, cross, dot, normalize, scale, sub};
+use morph::{MorphMesh, MorphTarget};
+use model::{Model, ModelArgs, Part, TangentVertex, Vertex, VertexJoints, VertexLayout, VertexTangent, VertexWeights,
+            skinned_mesh, upload_mesh};
+use object::Object;
+use resource::{Cache, Load, LoadError};
+use skeleton::{Joint, JointTransform, MAX_JOINTS, Skeleton};
+use spline::{Interpolate, Interpolation, Key, Sampler as SplineSampler, Spline, Time};
+use texture::TextureImage;
+use transform::{Orientation, Scale, Translation};
+
+const GLB_MAGIC: u32 = 0x46546C67; // glTF
+const GLB_JSON: u32 = 0x4E4F534A; // JSON
+const GLB_BIN: u32 = 0x004E4942; // BIN
+
+// Component types of accessors.
+const BYTE: u32 = 5120;
+const UNSIGNED_BYTE: u32 = 5121;
+const SHORT: u32 = 5122;
+const UNSIGNED_SHORT: u32 = 5123;
+const UNSIGNED_INT: u32 = 5125;
+const FLOAT: u32 = 5126;
+
+/// A node of the hierarchy of a glTF file.
+///
+/// The transform of a node is relative to its parent.
+#[derive(Clone, Debug)]
+pub struct Node {
+  pub name: Option<String>,
+  /// Indices of the children nodes.
+  pub children: Vec<usize>,
+  /// Index of the mesh of the node, if any – see `ModelArgs::mesh`.
+  pub mesh: Option<usize>,
+  /// Index of the skin deforming the mesh of the node, if any – see `Model::skins`.
+  pub skin: Option<usize>,
+  /// Weights of the morph targets of the mesh of the node – see `Part::morph`.
+  pub weights: Vec<f32>,
+  pub translation: Translation,
+  pub rotation: Orientation,
+  pub scale: Scale
+}
+
+impl Node {
+  fn trs(&self) -> Trs {
+    let q = self.rotation.quaternion();
+
+    Trs {
+      t: [self.translation.x, self.translation.y, self.translation.z],
+      r: [q.i, q.j, q.k, q.w],
+      s: [self.scale.x, self.scale.y, self.scale.z]
+    }
+  }
+}
+
+/// What an animation channel drives.
+#[derive(Clone, Debug)]
+pub enum ChannelTarget {
+  Translation(Spline<Translation>),
+  Rotation(Spline<Orientation>),
+  Scale(Spline<Vector3<f32>>),
+  /// Weight of a morph target, by index.
+  Weight(usize, Spline<f32>)
+}
+
+/// Animation of a property of a node.
+#[derive(Clone, Debug)]
+pub struct Channel {
+  /// Index of the animated node.
+  pub node: usize,
+  pub target: ChannelTarget
+}
+
+/// A glTF animation.
+///
+/// Step interpolation maps to `Interpolation::Step(1.)` and linear interpolation to
+/// `Interpolation::Linear`; cubic splines are interpolated linearly through their values, their
+/// tangents being dropped. An animation of the morph target weights of a node becomes a channel per
+/// target.
+#[derive(Clone, Debug)]
+pub struct Animation {
+  pub name: Option<String>,
+  pub channels: Vec<Channel>
+}
+
+impl Animation {
+  /// Time of the last key of the animation, in seconds.
+  pub fn duration(&self) -> Time {
+    self.channels.iter().map(|channel| channel.target.duration()).fold(0., f32::max)
+  }
+
+  /// Set the animated properties of nodes at a given time. Before its first key and after its last
+  /// one, a channel holds the value of that key.
+  pub fn apply(&self, t: Time, nodes: &mut [Node]) {
+    for channel in &self.channels {
+      if let Some(node) = nodes.get_mut(channel.node) {
+        channel.target.apply(t, &mut node.translation, &mut node.rotation, &mut node.scale, &mut node.weights);
+      }
+    }
+  }
+}
+
+impl ChannelTarget {
+  /// Time of the last key of the channel, in seconds.
+  pub fn duration(&self) -> Time {
+    match *self {
+      ChannelTarget::Translation(ref spline) => last_time(spline),
+      ChannelTarget::Rotation(ref spline) => last_time(spline),
+      ChannelTarget::Scale(ref spline) => last_time(spline),
+      ChannelTarget::Weight(_, ref spline) => last_time(spline)
+    }
+  }
+
+  /// Set the property the channel drives at a given time, holding the first and last keys outside
+  /// of the spline. Weights of unknown targets are ignored.
+  pub fn apply(&self, t: Time, translation: &mut Translation, rotation: &mut Orientation, scale: &mut Scale, weights: &mut [f32]) {
+    match *self {
+      ChannelTarget::Translation(ref spline) => {
+        if let Some(value) = sample_clamped(spline, t) {
+          *translation = value;
+        }
+      },
+      ChannelTarget::Rotation(ref spline) => {
+        if let Some(value) = sample_clamped(spline, t) {
+          *rotation = value;
+        }
+      },
+      ChannelTarget::Scale(ref spline) => {
+        if let Some(value) = sample_clamped(spline, t) {
+          *scale = Scale::new(value.x, value.y, value.z);
+        }
+      },
+      ChannelTarget::Weight(target, ref spline) => {
+        match (weights.get_mut(target), sample_clamped(spline, t)) {
+          (Some(weight), Some(value)) => *weight = value,
+          _ => {}
+        }
+      }
+    }
+  }
+}
+
+// Value of a key of an animation sampler. Cubic spline samplers store an in-tangent, the value and
+// an out-tangent per key.
+fn key_value(values: &[f32], i: usize, stride: usize, skip: usize, components: usize) -> &[f32] {
+  let start = (i * stride + skip) * components;
+  &values[start..start + components]
+}
+
+fn last_time<T>(spline: &Spline<T>) -> Time {
+  spline.keys().last().map_or(0., |key| key.t)
+}
+
+fn sample_clamped<T>(spline: &Spline<T>, t: Time) -> Option<T> where T: Interpolate {
+  let keys = spline.keys();
+
+  match (keys.first(), keys.last()) {
+    (Some(first), _) if t <= first.t => Some(first.value),
+    (_, Some(last)) if t >= last.t => Some(last.value),
+    (Some(_), Some(_)) => SplineSampler::new().sample(t, spline, true),
+    _ => None
+  }
+}
+
+/// Build objects out of nodes, one per node holding a mesh, with their world transform.
+///
+/// The model of each object is the mesh of its node, retrieved from the cache with `name` and
+/// `args` – which `mesh` is overridden. Animate a copy of the nodes of a model and call this
+/// function again to animate the objects.
+///
+/// `Object` scales before rotating, so non-uniform scales combined with rotations are only
+/// approximated.
+pub fn objects<'a>(cache: &mut Cache<'a>, name: &str, args: ModelArgs, nodes: &[Node], roots: &[usize]) -> Vec<Object<'a>> {
+  let mut objects = Vec::new();
+
+  for (node, world) in flatten(nodes, roots) {
+    let mesh = match nodes[node].mesh {
+      Some(mesh) => mesh,
+      None => continue
+    };
+
+    let model: Option<Id<Model>> = cache.get_id(name, args.mesh(mesh));
+
+    match model {
+      Some(model) => {
+        let position = Vector3::new(-world.t[0], -world.t[1], -world.t[2]);
+        let orientation = UnitQuaternion::new(&Quaternion::new(world.r[3], world.r[0], world.r[1], world.r[2]));
+        let scale = Scale::new(world.s[0], world.s[1], world.s[2]);
+
+        objects.push(Object::new(model, position, orientation, scale));
+      },
+      None => {
+        warn!("cannot instantiate mesh {} of {}", mesh, name);
+      }
+    }
+  }
+
+  objects
+}
+
+/// Build the objects of a glTF model in its rest pose – see `objects`.
+pub fn instantiate<'a>(cache: &mut Cache<'a>, name: &str, args: ModelArgs) -> Option<Vec<Object<'a>>> {
+  // every model of a glTF file carries the whole hierarchy; the first mesh is as good as any
+  let id: Id<Model> = match cache.get_id(name, args.mesh(0)) {
+    Some(id) => id,
+    None => return None
+  };
+
+  let model = match cache.get_by_id(&id) {
+    Some(model) => model,
+    None => return None
+  };
+
+  Some(objects(cache, name, args, &model.nodes, &model.roots))
+}
+
+// Translation, rotation – as a quaternion (x, y, z, w) – and scale.
+#[derive(Clone, Copy, Debug)]
+struct Trs {
+  t: [f32; 3],
+  r: [f32; 4],
+  s: [f32; 3]
+}
+
+impl Trs {
+  fn identity() -> Self {
+    Trs {
+      t: [0., 0., 0.],
+      r: [0., 0., 0., 1.],
+      s: [1., 1., 1.]
+    }
+  }
+
+  // Apply a transform after this one, as a child of it.
+  fn then(&self, child: &Trs) -> Trs {
+    let scaled = [self.s[0] * child.t[0], self.s[1] * child.t[1], self.s[2] * child.t[2]];
+    let t = add(self.t, rotate(self.r, scaled));
+
+    Trs {
+      t: t,
+      r: quat_mul(self.r, child.r),
+      s: [self.s[0] * child.s[0], self.s[1] * child.s[1], self.s[2] * child.s[2]]
+    }
+  }
+
+  // Column-major matrix.
+  fn matrix(&self) -> Mat4 {
+    let x = rotate(self.r, [self.s[0], 0., 0.]);
+    let y = rotate(self.r, [0., self.s[1], 0.]);
+    let z = rotate(self.r, [0., 0., self.s[2]]);
+
+    [
+      [x[0], x[1], x[2], 0.],
+      [y[0], y[1], y[2], 0.],
+      [z[0], z[1], z[2], 0.],
+      [self.t[0], self.t[1], self.t[2], 1.]
+    ]
+  }
+}
+
+type Mat4 = [[f32; 4]; 4];
+
+// Nodes reachable from the roots along with their world transforms, parents first. Nodes are
+// visited at most once, so that malformed hierarchies don’t loop.
+fn flatten(nodes: &[Node], roots: &[usize]) -> Vec<(usize, Trs)> {
+  let mut visited = vec![false; nodes.len()];
+  let mut flattened = Vec::new();
+  let mut stack: Vec<(usize, Trs)> = roots.iter().rev().map(|&root| (root, Trs::identity())).collect();
+
+  while let Some((node, parent)) = stack.pop() {
+    if node >= nodes.len() || visited[node] {
+      continue;
+    }
+
+    visited[node] = true;
+
+    let world = parent.then(&nodes[node].trs());
+    flattened.push((node, world));
+
+    for &child in nodes[node].children.iter().rev() {
+      stack.push((child, world));
+    }
+  }
+
+  flattened
+}
+
+/// A primitive of a glTF mesh.
+#[derive(Clone, Debug)]
+pub struct Primitive {
+  pub mesh: MeshData<Vertex>,
+  /// Tangents of the vertices, if the file has them – one per vertex.
+  pub tangents: Option<Vec<VertexTangent>>,
+  /// Joints and weights of the vertices, if the primitive is skinned. Weights sum to one.
+  pub skin: Option<Vec<(VertexJoints, VertexWeights)>>,
+  /// Morph targets of the primitive.
+  pub targets: Vec<MorphTarget>
+}
+
+/// A parsed glTF file along with its buffers.
+pub struct Gltf {
+  document: Document,
+  buffers: Vec<Vec<u8>>,
+  dir: PathBuf,
+  files: Vec<PathBuf>
+}
+
+impl Gltf {
+  /// Read a `.gltf` or `.glb` file and the buffers it refers to.
+  pub fn open<P>(path: P) -> Result<Self, LoadError> where P: AsRef<Path> {
+    let path = path.as_ref();
+    let bytes = read_file(path)?;
+
+    Gltf::from_slice(&bytes, path.parent().unwrap_or(Path::new("")))
+  }
+
+  /// Parse a `.gltf` or `.glb` file held in memory. External files are read relatively to `dir`.
+  pub fn from_slice(bytes: &[u8], dir: &Path) -> Result<Self, LoadError> {
+    let (json, bin) = if bytes.starts_with(b"glTF") {
+      parse_glb(bytes)?
+    } else {
+      (bytes, None)
+    };
+
+    let document: Document = from_slice(json).map_err(|e| LoadError::ParseFailed(format!("{:?}", e)))?;
+    let mut files = Vec::new();
+    let mut buffers = Vec::with_capacity(document.buffers.len());
+
+    for (i, buffer) in document.buffers.iter().enumerate() {
+      let data = match buffer.uri {
+        Some(ref uri) => read_uri(uri, dir, &mut files)?,
+        None if i == 0 => bin.map(|bin| bin.to_vec()).ok_or(LoadError::ParseFailed("buffer 0 has no uri nor binary chunk".to_owned()))?,
+        None => return Err(LoadError::ParseFailed(format!("buffer {} has no uri", i)))
+      };
+
+      if data.len() < buffer.byte_length {
+        return Err(LoadError::ParseFailed(format!("buffer {} is {} bytes long; expected {}", i, data.len(), buffer.byte_length)));
+      }
+
+      buffers.push(data);
+    }
+
+    Ok(Gltf {
+      document: document,
+      buffers: buffers,
+      dir: dir.to_owned(),
+      files: files
+    })
+  }
+
+  /// External files read so far.
+  pub fn files(&self) -> &[PathBuf] {
+    &self.files
+  }
+
+  pub fn mesh_count(&self) -> usize {
+    self.document.meshes.len()
+  }
+
+  pub fn primitive_count(&self, mesh: usize) -> usize {
+    self.document.meshes.get(mesh).map_or(0, |mesh| mesh.primitives.len())
+  }
+
+  /// Read an accessor as floats. Normalized integers are mapped to `[0; 1]` or `[-1; 1]`. Return the
+  /// values along with the number of components per element.
+  pub fn read_floats(&self, accessor: usize) -> Result<(Vec<f32>, usize), LoadError> {
+    let acc = self.document.accessors.get(accessor).ok_or(LoadError::ParseFailed(format!("unknown accessor {}", accessor)))?;
+    let components = component_count(&acc.ty)?;
+
+    let values = self.read_accessor(acc, components, |bytes| {
+      match (acc.component_type, acc.normalized) {
+        (FLOAT, _) => Ok(read_f32(bytes)),
+        (BYTE, true) => Ok((bytes[0] as i8 as f32 / 127.).max(-1.)),
+        (BYTE, false) => Ok(bytes[0] as i8 as f32),
+        (UNSIGNED_BYTE, true) => Ok(bytes[0] as f32 / 255.),
+        (UNSIGNED_BYTE, false) => Ok(bytes[0] as f32),
+        (SHORT, true) => Ok((read_u16(bytes) as i16 as f32 / 32767.).max(-1.)),
+        (SHORT, false) => Ok(read_u16(bytes) as i16 as f32),
+        (UNSIGNED_SHORT, true) => Ok(read_u16(bytes) as f32 / 65535.),
+        (UNSIGNED_SHORT, false) => Ok(read_u16(bytes) as f32),
+        (UNSIGNED_INT, _) => Ok(read_u32(bytes) as f32),
+        (ty, _) => Err(LoadError::ParseFailed(format!("unknown component type {}", ty)))
+      }
+    })?;
+
+    Ok((values, components))
+  }
+
+  /// Read an accessor of unsigned integers, such as indices.
+  pub fn read_indices(&self, accessor: usize) -> Result<Vec<u32>, LoadError> {
+    let acc = self.document.accessors.get(accessor).ok_or(LoadError::ParseFailed(format!("unknown accessor {}", accessor)))?;
+
+    self.read_accessor(acc, 1, |bytes| {
+      match acc.component_type {
+        UNSIGNED_BYTE => Ok(bytes[0] as u32),
+        UNSIGNED_SHORT => Ok(read_u16(bytes) as u32),
+        UNSIGNED_INT => Ok(read_u32(bytes)),
+        ty => Err(LoadError::ParseFailed(format!("component type {} cannot be used for indices", ty)))
+      }
+    })
+  }
+
+  fn read_accessor<T, F>(&self, acc: &AccessorDef, components: usize, read: F) -> Result<Vec<T>, LoadError>
+      where T: Default + Clone,
+            F: Fn(&[u8]) -> Result<T, LoadError> {
+    if acc.sparse.is_some() {
+      return Err(LoadError::ConversionFailed("sparse accessors are not supported".to_owned()));
+    }
+
+    let size = component_size(acc.component_type)?;
+
+    let view = match acc.buffer_view {
+      Some(view) => self.document.buffer_views.get(view).ok_or(LoadError::ParseFailed(format!("unknown buffer view {}", view)))?,
+      // accessors without buffer views are filled with zeros
+      None => return Ok(vec![T::default(); acc.count * components])
+    };
+
+    let buffer = self.buffers.get(view.buffer).ok_or(LoadError::ParseFailed(format!("unknown buffer {}", view.buffer)))?;
+    let stride = view.byte_stride.unwrap_or(size * components);
+    let start = view.byte_offset + acc.byte_offset;
+
+    if acc.count > 0 && start + stride * (acc.count - 1) + size * components > view.byte_offset + view.byte_length {
+      return Err(LoadError::ParseFailed("accessor out of the bounds of its buffer view".to_owned()));
+    }
+
+    if view.byte_offset + view.byte_length > buffer.len() {
+      return Err(LoadError::ParseFailed("buffer view out of the bounds of its buffer".to_owned()));
+    }
+
+    let mut values = Vec::with_capacity(acc.count * components);
+
+    for i in 0..acc.count {
+      for c in 0..components {
+        let offset = start + i * stride + c * size;
+        values.push(read(&buffer[offset..offset + size])?);
+      }
+    }
+
+    Ok(values)
+  }
+
+  /// Nodes of the file along with the roots of its scene – the default one or the first one. Without
+  /// any scene, every node without a parent is a root. Nodes without morph target weights get the
+  /// default ones of their mesh.
+  pub fn nodes(&self) -> Result<(Vec<Node>, Vec<usize>), LoadError> {
+    let mut nodes: Vec<Node> = self.document.nodes.iter().map(convert_node).collect();
+
+    for node in &mut nodes {
+      if node.weights.is_empty() {
+        if let Some(mesh) = node.mesh.and_then(|mesh| self.document.meshes.get(mesh)) {
+          node.weights = mesh.weights.clone();
+        }
+      }
+    }
+
+    for node in &nodes {
+      if let Some(&child) = node.children.iter().find(|&&child| child >= nodes.len()) {
+        return Err(LoadError::ParseFailed(format!("unknown node {}", child)));
+      }
+    }
+
+    let roots = match self.document.scenes.get(self.document.scene.unwrap_or(0)) {
+      Some(scene) => scene.nodes.clone(),
+      None => {
+        let mut is_child = vec![false; nodes.len()];
+
+        for &child in nodes.iter().flat_map(|node| &node.children) {
+          is_child[child] = true;
+        }
+
+        (0..nodes.len()).filter(|&i| !is_child[i]).collect()
+      }
+    };
+
+    Ok((nodes, roots))
+  }
+
+  pub fn animations(&self) -> Result<Vec<Animation>, LoadError> {
+    self.document.animations.iter().map(|animation| {
+      let mut channels = Vec::new();
+
+      for channel in &animation.channels {
+        let node = match channel.target.node {
+          Some(node) => node,
+          None => continue
+        };
+
+        let sampler = animation.samplers.get(channel.sampler).ok_or(LoadError::ParseFailed(format!("unknown animation sampler {}", channel.sampler)))?;
+        let (times, _) = self.read_floats(sampler.input)?;
+        let (values, components) = self.read_floats(sampler.output)?;
+
+        let (interpolation, stride, skip) = match sampler.interpolation.as_str() {
+          "STEP" => (Interpolation::Step(1.), 1, 0),
+          "CUBICSPLINE" => (Interpolation::Linear, 3, 1),
+          _ => (Interpolation::Linear, 1, 0)
+        };
+
+        if values.len() < times.len() * stride * components {
+          return Err(LoadError::ParseFailed("animation sampler with fewer values than keys".to_owned()));
+        }
+
+        // weights of all the targets are stored per key; give each target its own channel
+        if channel.target.path == "weights" && components == 1 && !times.is_empty() {
+          let targets = values.len() / (times.len() * stride);
+
+          for target in 0..targets {
+            let spline = Spline::new(times.iter().enumerate().map(|(i, &t)| {
+              Key::new(t, key_value(&values, i, stride, skip, targets)[target], interpolation)
+            }).collect());
+
+            channels.push(Channel {
+              node: node,
+              target: ChannelTarget::Weight(target, spline)
+            });
+          }
+
+          continue;
+        }
+
+        // value of each key
+        let value = |i: usize| key_value(&values, i, stride, skip, components);
+
+        let target = match (channel.target.path.as_str(), components) {
+          ("translation", 3) => {
+            ChannelTarget::Translation(Spline::new(times.iter().enumerate().map(|(i, &t)| {
+              let v = value(i);
+              Key::new(t, Vector3::new(v[0], v[1], v[2]), interpolation)
+            }).collect()))
+          },
+          ("rotation", 4) => {
+            ChannelTarget::Rotation(Spline::new(times.iter().enumerate().map(|(i, &t)| {
+              let v = value(i);
+              Key::new(t, UnitQuaternion::new(&Quaternion::new(v[3], v[0], v[1], v[2])), interpolation)
+            }).collect()))
+          },
+          ("scale", 3) => {
+            ChannelTarget::Scale(Spline::new(times.iter().enumerate().map(|(i, &t)| {
+              let v = value(i);
+              Key::new(t, Vector3::new(v[0], v[1], v[2]), interpolation)
+            }).collect()))
+          },
+          (path, _) => {
+            deb!("skipping animation channel {} of node {}", path, node);
+            continue;
+          }
+        };
+
+        channels.push(Channel {
+          node: node,
+          target: target
+        });
+      }
+
+      Ok(Animation {
+        name: animation.name.clone(),
+        channels: channels
+      })
+    }).collect()
+  }
+
+  /// Skins of the file, as skeletons which joints refer to the nodes of the file.
+  ///
+  /// The transforms of the nodes between a joint and its parent joint – or the root of the
+  /// hierarchy – are folded into the `offset` of the joint; animating such nodes has no effect on
+  /// the skeleton. Missing inverse bind matrices are identities.
+  pub fn skins(&self) -> Result<Vec<Skeleton>, LoadError> {
+    let (nodes, _) = self.nodes()?;
+    let mut parents = vec![None; nodes.len()];
+
+    for (i, node) in nodes.iter().enumerate() {
+      for &child in &node.children {
+        parents[child] = Some(i);
+      }
+    }
+
+    self.document.skins.iter().enumerate().map(|(i, skin)| {
+      if let Some(&node) = skin.joints.iter().find(|&&node| node >= nodes.len()) {
+        return Err(LoadError::ParseFailed(format!("unknown joint node {} in skin {}", node, i)));
+      }
+
+      if skin.joints.len() > MAX_JOINTS {
+        warn!("skin {} has {} joints; the default shader handles {}", i, skin.joints.len(), MAX_JOINTS);
+      }
+
+      let inverse_binds: Vec<Mat4> = match skin.inverse_bind_matrices {
+        Some(accessor) => {
+          let (values, components) = self.read_floats(accessor)?;
+
+          if components != 16 || values.len() < skin.joints.len() * 16 {
+            return Err(LoadError::ParseFailed(format!("invalid inverse bind matrices in skin {}", i)));
+          }
+
+          values.chunks(16).map(|m| [[m[0], m[1], m[2], m[3]], [m[4], m[5], m[6], m[7]], [m[8], m[9], m[10], m[11]], [m[12], m[13], m[14], m[15]]]).collect()
+        },
+        None => Vec::new()
+      };
+
+      let joints = skin.joints.iter().enumerate().map(|(j, &node)| {
+        // fold the nodes up to the parent joint
+        let mut between = Vec::new();
+        let mut ancestor = parents[node];
+
+        while let Some(a) = ancestor {
+          if skin.joints.contains(&a) || between.contains(&a) {
+            break;
+          }
+
+          between.push(a);
+          ancestor = parents[a];
+        }
+
+        let offset = between.iter().rev().fold(Trs::identity(), |offset, &a| offset.then(&nodes[a].trs()));
+        let parent = ancestor.and_then(|a| skin.joints.iter().position(|&joint| joint == a));
+        let rest = JointTransform::new(nodes[node].translation, nodes[node].rotation, nodes[node].scale);
+
+        let mut joint = Joint::new(nodes[node].name.clone(), parent, rest, inverse_binds.get(j).cloned().unwrap_or(Trs::identity().matrix()));
+        joint.offset = offset.matrix();
+        joint.node = Some(node);
+        joint
+      }).collect();
+
+      Skeleton::new(skin.name.clone(), joints).ok_or(LoadError::ParseFailed(format!("skin {} has a cyclic hierarchy", i)))
+    }).collect()
+  }
+
+  /// Materials of the file. Texture maps are named after the URI of their image, relative to the
+  /// file, or `#image<N>` for embedded images.
+  ///
+  /// The metallic-roughness model is approximated: metals get a specular color tinted by their
+  /// base color and the roughness drives the shininess. The metallic-roughness and occlusion
+  /// textures are ignored.
+  pub fn materials(&self) -> Vec<Material> {
+    self.document.materials.iter().enumerate().map(|(i, def)| {
+      let mut material = Material::new(&def.name.clone().unwrap_or_else(|| format!("material{}", i)));
+      let pbr = &def.pbr_metallic_roughness;
+      let base = pbr.base_color_factor;
+      let metallic = pbr.metallic_factor;
+      let alpha = pbr.roughness_factor * pbr.roughness_factor;
+
+      material.diffuse = [base[0], base[1], base[2]];
+      material.opacity = if def.alpha_mode == "OPAQUE" { 1. } else { base[3] };
+      material.specular = [
+        0.04 + (base[0] - 0.04) * metallic,
+        0.04 + (base[1] - 0.04) * metallic,
+        0.04 + (base[2] - 0.04) * metallic
+      ];
+      // Blinn-Phong exponent matching the GGX roughness
+      material.shininess = (2. / (alpha * alpha).max(1e-4) - 2.).max(0.).min(1024.);
+      material.emissive = def.emissive_factor;
+      material.diffuse_map = pbr.base_color_texture.as_ref().and_then(|tex| self.texture_name(tex.index));
+      material.emissive_map = def.emissive_texture.as_ref().and_then(|tex| self.texture_name(tex.index));
+      material.bump_map = def.normal_texture.as_ref().and_then(|tex| self.texture_name(tex.index));
+
+      material
+    }).collect()
+  }
+
+  fn texture_name(&self, texture: usize) -> Option<String> {
+    self.document.textures.get(texture).and_then(|texture| texture.source).map(|image| {
+      match self.document.images.get(image).and_then(|image| image.uri.as_ref()) {
+        Some(uri) if !uri.starts_with("data:") => uri.clone(),
+        _ => format!("#image{}", image)
+      }
+    })
+  }
+
+  // Encoded bytes of an image by its texture map name.
+  fn image_bytes(&mut self, name: &str) -> Result<Vec<u8>, LoadError> {
+    if name.starts_with("#image") {
+      let index: usize = name[6..].parse().map_err(|_| LoadError::ParseFailed(format!("unknown image {}", name)))?;
+      let image = self.document.images.get(index).cloned().ok_or(LoadError::ParseFailed(format!("unknown image {}", index)))?;
+
+      match (image.uri, image.buffer_view) {
+        (Some(uri), _) => read_uri(&uri, &self.dir, &mut self.files),
+        (None, Some(view)) => {
+          let view = self.document.buffer_views.get(view).ok_or(LoadError::ParseFailed(format!("unknown buffer view {}", view)))?;
+          let buffer = self.buffers.get(view.buffer).ok_or(LoadError::ParseFailed(format!("unknown buffer {}", view.buffer)))?;
+
+          buffer.get(view.byte_offset..view.byte_offset + view.byte_length)
+            .map(|bytes| bytes.to_vec())
+            .ok_or(LoadError::ParseFailed("buffer view out of the bounds of its buffer".to_owned()))
+        },
+        (None, None) => Err(LoadError::ParseFailed(format!("image {} has no data", index)))
+      }
+    } else {
+      read_uri(name, &self.dir, &mut self.files)
+    }
+  }
+
+  /// Mesh of a primitive, along with the tangents, joints and weights of its vertices if the file
+  /// has them, and its morph targets. Only the first set of joints and weights – `JOINTS_0` and
+  /// `WEIGHTS_0` – is read, and the tangent displacements of morph targets are ignored.
+  ///
+  /// Strips, fans and loops are turned into lists. Primitives without normals get flat normals, as
+  /// the format requires; their vertices are split, and their tangents and the normal displacements
+  /// of their targets are dropped.
+  pub fn primitive(&self, mesh: usize, primitive: usize) -> Result<Primitive, LoadError> {
+    let prim = self.document.meshes.get(mesh)
+      .and_then(|mesh| mesh.primitives.get(primitive))
+      .ok_or(LoadError::ParseFailed(format!("unknown primitive {} of mesh {}", primitive, mesh)))?;
+
+    let positions = match prim.attributes.get("POSITION") {
+      Some(&accessor) => self.read_vec(accessor, 3)?,
+      None => return Err(LoadError::ConversionFailed(format!("primitive {} of mesh {} has no positions", primitive, mesh)))
+    };
+
+    // per-vertex attributes must describe every vertex
+    let attribute = |name: &str, accessor: usize, components: usize| -> Result<Vec<Vec<f32>>, LoadError> {
+      let values = self.read_vec(accessor, components)?;
+
+      if values.len() != positions.len() {
+        return Err(LoadError::ParseFailed(format!("primitive {} of mesh {} doesn’t have as many {} as vertices", primitive, mesh, name)));
+      }
+
+      Ok(values)
+    };
+
+    let normals = match prim.attributes.get("NORMAL") {
+      Some(&accessor) => Some(attribute("normals", accessor, 3)?),
+      None => None
+    };
+
+    let uvs = match prim.attributes.get("TEXCOORD_0") {
+      Some(&accessor) => Some(attribute("texture coordinates", accessor, 2)?),
+      None => None
+    };
+
+    let tangents = match prim.attributes.get("TANGENT") {
+      Some(&accessor) if normals.is_some() => Some(attribute("tangents", accessor, 4)?),
+      _ => None
+    };
+
+    let skin = match (prim.attributes.get("JOINTS_0"), prim.attributes.get("WEIGHTS_0")) {
+      (Some(&joints), Some(&weights)) => {
+        let joints = self.read_vec(joints, 4)?;
+        let weights = self.read_vec(weights, 4)?;
+
+        if joints.len() != positions.len() || weights.len() != positions.len() {
+          return Err(LoadError::ParseFailed(format!("primitive {} of mesh {} doesn’t have as many joints and weights as vertices", primitive, mesh)));
+        }
+
+        Some(joints.iter().zip(&weights).map(|(j, w)| {
+          let sum = w[0] + w[1] + w[2] + w[3];
+          let k = if sum > 0. { 1. / sum } else { 0. };
+
+          ([j[0] as u32, j[1] as u32, j[2] as u32, j[3] as u32], [w[0] * k, w[1] * k, w[2] * k, w[3] * k])
+        }).collect())
+      },
+      _ => None
+    };
+
+    let mut targets = Vec::with_capacity(prim.targets.len());
+
+    for (t, target) in prim.targets.iter().enumerate() {
+      let displacements = |attribute: &str| -> Result<Option<Vec<[f32; 3]>>, LoadError> {
+        match target.get(attribute) {
+          Some(&accessor) => {
+            let values = self.read_vec(accessor, 3)?;
+
+            if values.len() != positions.len() {
+              return Err(LoadError::ParseFailed(format!("morph target {} of mesh {} doesn’t displace every vertex", t, mesh)));
+            }
+
+            Ok(Some(values.into_iter().map(|v| [v[0], v[1], v[2]]).collect()))
+          },
+          None => Ok(None)
+        }
+      };
+
+      targets.push(MorphTarget {
+        name: None,
+        positions: displacements("POSITION")?.unwrap_or_else(|| vec![[0., 0., 0.]; positions.len()]),
+        normals: displacements("NORMAL")?
+      });
+    }
+
+    let indices = match prim.indices {
+      Some(accessor) => self.read_indices(accessor)?,
+      None => (0..positions.len() as u32).collect()
+    };
+
+    if let Some(&i) = indices.iter().find(|&&i| i as usize >= positions.len()) {
+      return Err(LoadError::ParseFailed(format!("vertex index {} out of bounds", i)));
+    }
+
+    let (indices, mode) = list_indices(&indices, prim.mode)?;
+
+    let vertices: Vec<Vertex> = (0..positions.len()).map(|i| {
+      let p = &positions[i];
+      let n = normals.as_ref().map_or([0., 0., 0.], |normals| [normals[i][0], normals[i][1], normals[i][2]]);
+      let uv = uvs.as_ref().map_or([0., 0.], |uvs| [uvs[i][0], uvs[i][1]]);
+
+      ([p[0], p[1], p[2]], n, uv)
+    }).collect();
+
+    let tangents = tangents.map(|tangents| tangents.into_iter().map(|t| [t[0], t[1], t[2], t[3]]).collect());
+
+    match (normals, mode) {
+      (None, tess::Mode::Triangle) => {
+        let (vertices, indices, sources) = flat_normals(&vertices, &indices);
+
+        Ok(Primitive {
+          mesh: MeshData::new(mode, vertices, Some(indices)),
+          tangents: None,
+          skin: skin.map(|skin| sources.iter().map(|&i| skin[i as usize]).collect()),
+          targets: targets.into_iter().map(|target| {
+            MorphTarget {
+              name: target.name,
+              positions: sources.iter().map(|&i| target.positions[i as usize]).collect(),
+              normals: None
+            }
+          }).collect()
+        })
+      },
+      _ => {
+        Ok(Primitive {
+          mesh: MeshData::new(mode, vertices, Some(indices)),
+          tangents: tangents,
+          skin: skin,
+          targets: targets
+        })
+      }
+    }
+  }
+
+  // Read an accessor which elements have a given number of components.
+  fn read_vec(&self, accessor: usize, components: usize) -> Result<Vec<Vec<f32>>, LoadError> {
+    let (values, found) = self.read_floats(accessor)?;
+
+    if found != components {
+      return Err(LoadError::ParseFailed(format!("accessor {} has {} components; expected {}", accessor, found, components)));
+    }
+
+    Ok(values.chunks(components).map(|chunk| chunk.to_vec()).collect())
+  }
+}
+
+// Turn indices of any glTF mode into a list of points, lines or triangles.
+fn list_indices(indices: &[u32], mode: u32) -> Result<(Vec<u32>, tess::Mode), LoadError> {
+  let n = indices.len();
+
+  match mode {
+    0 => Ok((indices.to_vec(), tess::Mode::Point)),
+    1 => Ok((indices.to_vec(), tess::Mode::Line)),
+    // line loop
+    2 if n > 1 => Ok(((0..n).flat_map(|i| vec![indices[i], indices[(i + 1) % n]]).collect(), tess::Mode::Line)),
+    // line strip
+    3 if n > 1 => Ok(((1..n).flat_map(|i| vec![indices[i - 1], indices[i]]).collect(), tess::Mode::Line)),
+    2 | 3 => Ok((Vec::new(), tess::Mode::Line)),
+    4 => Ok((indices.to_vec(), tess::Mode::Triangle)),
+    // triangle strip; every other triangle is flipped to keep the winding
+    5 if n > 2 => {
+      Ok(((2..n).flat_map(|i| {
+        if i % 2 == 0 {
+          vec![indices[i - 2], indices[i - 1], indices[i]]
+        } else {
+          vec![indices[i - 1], indices[i - 2], indices[i]]
+        }
+      }).collect(), tess::Mode::Triangle))
+    },
+    // triangle fan
+    6 if n > 2 => Ok(((2..n).flat_map(|i| vec![indices[0], indices[i - 1], indices[i]]).collect(), tess::Mode::Triangle)),
+    5 | 6 => Ok((Vec::new(), tess::Mode::Triangle)),
+    _ => Err(LoadError::ParseFailed(format!("unknown primitive mode {}", mode)))
+  }
+}
+
+// Split the vertices of a triangle list and give each triangle its own normal. The index of the
+// source vertex of each new vertex is returned along with the new vertices and indices.
+fn flat_normals(vertices: &[Vertex], indices: &[u32]) -> (Vec<Vertex>, Vec<u32>, Vec<u32>) {
+  let mut flat = Vec::with_capacity(indices.len());
+  let mut sources = Vec::with_capacity(indices.len());
+
+  for triangle in indices.chunks(3).filter(|triangle| triangle.len() == 3) {
+    let (a, b, c) = (vertices[triangle[0] as usize], vertices[triangle[1] as usize], vertices[triangle[2] as usize]);
+    let n = normalize(cross(sub(b.0, a.0), sub(c.0, a.0)));
+
+    flat.push((a.0, n, a.2));
+    flat.push((b.0, n, b.2));
+    flat.push((c.0, n, c.2));
+    sources.extend_from_slice(triangle);
+  }
+
+  let indices = (0..flat.len() as u32).collect();
+  (flat, indices, sources)
+}
+
+/// Load a glTF file as a `Model`. See `ModelArgs::mesh` for what gets loaded.
+///
+/// With `VertexLayout::Skinned` or `VertexLayout::Morph`, skinned primitives get their joints and
+/// weights and are left in the space of their skin – the node transforms don’t apply to them – and
+/// they’re never optimized. Other primitives get null weights.
+///
+/// Primitives with morph targets keep them in their `Part::morph` and aren’t optimized either. With
+/// `VertexLayout::Morph`, their first targets are uploaded for the morphing shader, along with
+/// their skin.
+pub fn load_model<'a>(path: &Path, cache: &mut Cache<'a>, args: &ModelArgs) -> Result<Model, LoadError> {
+  let mut gltf = Gltf::open(path)?;
+  let (nodes, roots) = gltf.nodes()?;
+
+  // the primitives to convert, with the transform to apply
+  let primitives: Vec<(usize, Option<Mat4>)> = match args.mesh {
+    Some(mesh) if mesh < gltf.mesh_count() => vec![(mesh, None)],
+    Some(mesh) => return Err(LoadError::ConversionFailed(format!("unknown mesh {}", mesh))),
+    None => {
+      flatten(&nodes, &roots).into_iter()
+        .filter_map(|(node, world)| nodes[node].mesh.map(|mesh| (mesh, Some(world.matrix()))))
+        .collect()
+    }
+  };
+
+  let materials = gltf.materials();
+  let mut parts = Vec::new();
+
+  for (mesh, matrix) in primitives {
+    let mesh_def = gltf.document.meshes.get(mesh).ok_or(LoadError::ParseFailed(format!("unknown mesh {}", mesh)))?;
+
+    for (i, prim) in mesh_def.primitives.iter().enumerate() {
+      let Primitive { mesh: mut mesh_data, mut tangents, skin, mut targets } = gltf.primitive(mesh, i)?;
+      let skin = match args.layout {
+        VertexLayout::Skinned | VertexLayout::Morph => skin,
+        _ => None
+      };
+
+      // tangents would not follow welded vertices; they’re generated again if needed – skins and
+      // targets can’t be
+      match (args.optimize, skin.is_some() || !targets.is_empty()) {
+        (Some(_), true) => {
+          deb!("  not optimizing skinned or morphed primitive {} of mesh {}", i, mesh);
+        },
+        (Some(tolerance), false) => {
+          let (optimized, stats) = optimize::optimize(&mesh_data, tolerance);
+          info!("  optimized primitive {} of mesh {}: {}", i, mesh, stats);
+          mesh_data = optimized;
+          tangents = None;
+        },
+        (None, _) => {}
+      }
+
+      // skinned meshes are placed by their joints, not by their node
+      match (&matrix, &skin) {
+        (&Some(ref matrix), &None) => {
+          transform_vertices(matrix, &mut mesh_data.vertices, tangents.as_mut().map(|tangents| tangents.as_mut_slice()));
+          transform_targets(matrix, &mut targets);
+          mesh_data.update_bounds();
+        },
+        _ => {}
+      }
+
+      let morph = if targets.is_empty() {
+        None
+      } else {
+        MorphMesh::new(mesh_data.clone(), targets, mesh_def.weights.clone())
+      };
+
+      // `primitive` checked that there’s a tangent and a skin entry per vertex
+      let skin = skin.as_ref().map(|skin| skin.as_slice());
+      let tess = match (args.layout, morph.as_ref(), tangents) {
+        (VertexLayout::Morph, Some(morph), tangents) => morph.gpu_mesh(tangents, skin).upload(),
+        (VertexLayout::Tangent, _, Some(tangents)) => {
+          let vertices = mesh_data.vertices.iter().zip(tangents).map(|(&(p, n, uv), t)| (p, n, uv, t)).collect();
+          let mesh_data: MeshData<TangentVertex> = MeshData::new(mesh_data.mode, vertices, mesh_data.indices);
+          mesh_data.upload()
+        },
+        (VertexLayout::Skinned, _, tangents) => skinned_mesh(&mesh_data, tangents, skin).0.upload(),
+        (layout, _, _) => upload_mesh(&mesh_data, layout)
+      };
+
+      let material = prim.material.and_then(|material| {
+        if material >= materials.len() {
+          warn!("unknown material {} in mesh {}", material, mesh);
+          None
+        } else {
+          Some(material)
+        }
+      });
+
+      let mut part = Part::with_material(tess, material);
+      part.morph = morph;
+      parts.push(part);
+    }
+  }
+
+  let textures = load_textures(&mut gltf, &materials, cache);
+  let animations = gltf.animations()?;
+  let skins = gltf.skins()?;
+
+  for file in gltf.files() {
+    cache.add_dependency(file);
+  }
+
+  let mut model = Model::new(parts, materials, textures);
+  model.nodes = nodes;
+  model.roots = roots;
+  model.animations = animations;
+  model.skins = skins;
+
+  Ok(model)
+}
+
+// Load the texture maps of the materials. Missing textures are skipped.
+fn load_textures<'a>(gltf: &mut Gltf, materials: &[Material], cache: &mut Cache<'a>) -> HashMap<String, Rc<TextureImage>> {
+  let mut textures = HashMap::new();
+
+  for material in materials {
+    for map in material.maps() {
+      if textures.contains_key(map) {
+        continue;
+      }
+
+      // color maps are stored gamma-encoded
+      let linear = !material.is_color_map(map);
+
+      let texture = if map.starts_with("#image") {
+        gltf.image_bytes(map).and_then(|bytes| TextureImage::from_memory(&bytes, Sampler::default(), linear))
+      } else {
+        let path = gltf.dir.join(map);
+        cache.add_dependency(&path);
+        TextureImage::load(&path, cache, (Sampler::default(), linear))
+      };
+
+      match texture {
+        Ok(texture) => {
+          textures.insert(map.to_owned(), Rc::new(texture));
+        },
+        Err(e) => {
+          warn!("cannot load texture {} for material {}: {:?}", map, material.name, e);
+        }
+      }
+    }
+  }
+
+  textures
+}
+
+// Transform positions, normals and tangents.
+fn transform_vertices(m: &Mat4, vertices: &mut [Vertex], tangents: Option<&mut [[f32; 4]]>) {
+  let linear = |v: [f32; 3]| {
+    [
+      m[0][0] * v[0] + m[1][0] * v[1] + m[2][0] * v[2],
+      m[0][1] * v[0] + m[1][1] * v[1] + m[2][1] * v[2],
+      m[0][2] * v[0] + m[1][2] * v[1] + m[2][2] * v[2]
+    ]
+  };
+
+  // normals go through the inverse transpose, which is the cofactor matrix up to a scale
+  let (x, y, z) = ([m[0][0], m[0][1], m[0][2]], [m[1][0], m[1][1], m[1][2]], [m[2][0], m[2][1], m[2][2]]);
+  let cofactors = [cross(y, z), cross(z, x), cross(x, y)];
+  let sign = if dot(x, cofactors[0]) < 0. { -1. } else { 1. };
+  let normal = |n: [f32; 3]| {
+    let c = &cofactors;
+    normalize([
+      sign * (c[0][0] * n[0] + c[1][0] * n[1] + c[2][0] * n[2]),
+      sign * (c[0][1] * n[0] + c[1][1] * n[1] + c[2][1] * n[2]),
+      sign * (c[0][2] * n[0] + c[1][2] * n[1] + c[2][2] * n[2])
+    ])
+  };
+
+  for vertex in vertices {
+    vertex.0 = add(linear(vertex.0), [m[3][0], m[3][1], m[3][2]]);
+    vertex.1 = normal(vertex.1);
+  }
+
+  if let Some(tangents) = tangents {
+    for tangent in tangents {
+      let t = normalize(linear([tangent[0], tangent[1], tangent[2]]));
+      *tangent = [t[0], t[1], t[2], tangent[3] * sign];
+    }
+  }
+}
+
+// Transform the displacements of morph targets. Normal displacements have the scale of the
+// transform divided out, which is only exact for uniform scales.
+fn transform_targets(m: &Mat4, targets: &mut [MorphTarget]) {
+  let linear = |v: [f32; 3]| {
+    [
+      m[0][0] * v[0] + m[1][0] * v[1] + m[2][0] * v[2],
+      m[0][1] * v[0] + m[1][1] * v[1] + m[2][1] * v[2],
+      m[0][2] * v[0] + m[1][2] * v[1] + m[2][2] * v[2]
+    ]
+  };
+
+  let (x, y, z) = ([m[0][0], m[0][1], m[0][2]], [m[1][0], m[1][1], m[1][2]], [m[2][0], m[2][1], m[2][2]]);
+  let k = dot(x, cross(y, z)).abs().cbrt();
+  let k = if k > 0. { 1. / k } else { 1. };
+
+  for target in targets {
+    for p in &mut target.positions {
+      *p = linear(*p);
+    }
+
+    if let Some(ref mut normals) = target.normals {
+      for n in normals.iter_mut() {
+        *n = scale(linear(*n), k);
+      }
+    }
+  }
+}
+
+/// Split a `.glb` file into its JSON chunk and its binary chunk, if any.
+pub fn parse_glb(bytes: &[u8]) -> Result<(&[u8], Option<&[u8]>), LoadError> {
+  if bytes.len() < 12 || read_u32(&bytes[0..4]) != GLB_MAGIC {
+    return Err(LoadError::ParseFailed("not a binary glTF file".to_owned()));
+  }
+
+  let version = read_u32(&bytes[4..8]);
+
+  if version != 2 {
+    return Err(LoadError::ParseFailed(format!("unsupported binary glTF version {}", version)));
+  }
+
+  let length = (read_u32(&bytes[8..12]) as usize).min(bytes.len());
+  let mut offset = 12;
+  let mut json = None;
+  let mut bin = None;
+
+  while offset + 8 <= length {
+    let chunk_length = read_u32(&bytes[offset..offset + 4]) as usize;
+    let chunk_type = read_u32(&bytes[offset + 4..offset + 8]);
+    let start = offset + 8;
+
+    if start + chunk_length > length {
+      return Err(LoadError::ParseFailed("truncated binary glTF chunk".to_owned()));
+    }
+
+    let chunk = &bytes[start..start + chunk_length];
+
+    match chunk_type {
+      GLB_JSON if json.is_none() => json = Some(chunk),
+      GLB_BIN if bin.is_none() => bin = Some(chunk),
+      _ => {} // unknown chunks must be ignored
+    }
+
+    offset = start + chunk_length;
+  }
+
+  json.map(|json| (json, bin)).ok_or(LoadError::ParseFailed("binary glTF file without a JSON chunk".to_owned()))
+}
+
+/// Decode standard base64. Padding is optional and whitespace is ignored.
+pub fn decode_base64(src: &str) -> Result<Vec<u8>, LoadError> {
+  let mut bytes = Vec::with_capacity(src.len() * 3 / 4);
+  let mut acc = 0u32;
+  let mut bits = 0;
+
+  for c in src.bytes().filter(|c| !(*c as char).is_whitespace()).take_while(|&c| c != b'=') {
+    let value = match c {
+      b'A'...b'Z' => c - b'A',
+      b'a'...b'z' => c - b'a' + 26,
+      b'0'...b'9' => c - b'0' + 52,
+      b'+' => 62,
+      b'/' => 63,
+      _ => return Err(LoadError::ParseFailed(format!("invalid base64 character {:?}", c as char)))
+    };
+
+    acc = (acc << 6) | value as u32;
+    bits += 6;
+
+    if bits >= 8 {
+      bits -= 8;
+      bytes.push((acc >> bits) as u8);
+      acc &= (1 << bits) - 1;
+    }
+  }
+
+  Ok(bytes)
+}
+
+// Read the data of a URI: either a base64 data URI or a file relative to a directory.
+fn read_uri(uri: &str, dir: &Path, files: &mut Vec<PathBuf>) -> Result<Vec<u8>, LoadError> {
+  if uri.starts_with("data:") {
+    match uri.find(";base64,") {
+      Some(i) => decode_base64(&uri[i + 8..]),
+      None => Err(LoadError::ParseFailed("only base64 data URIs are supported".to_owned()))
+    }
+  } else {
+    let path = dir.join(uri);
+    let bytes = read_file(&path)?;
+    files.push(path);
+    Ok(bytes)
+  }
+}
+
+fn read_file(path: &Path) -> Result<Vec<u8>, LoadError> {
+  let mut bytes = Vec::new();
+  let mut file = File::open(path).map_err(|e| LoadError::FileNotFound(path.to_path_buf(), format!("{:?}", e)))?;
+  file.read_to_end(&mut bytes).map_err(|e| LoadError::ParseFailed(format!("{:?}", e)))?;
+
+  Ok(bytes)
+}
+
+fn read_u16(bytes: &[u8]) -> u16 {
+  bytes[0] as u16 | (bytes[1] as u16) << 8
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+  bytes[0] as u32 | (bytes[1] as u32) << 8 | (bytes[2] as u32) << 16 | (bytes[3] as u32) << 24
+}
+
+fn read_f32(bytes: &[u8]) -> f32 {
+  f32::from_bits(read_u32(bytes))
+}
+
+fn component_size(component_type: u32) -> Result<usize, LoadError> {
+  match component_type {
+    BYTE | UNSIGNED_BYTE => Ok(1),
+    SHORT | UNSIGNED_SHORT => Ok(2),
+    UNSIGNED_INT | FLOAT => Ok(4),
+    ty => Err(LoadError::ParseFailed(format!("unknown component type {}", ty)))
+  }
+}
+
+fn component_count(ty: &str) -> Result<usize, LoadError> {
+  match ty {
+    "SCALAR" => Ok(1),
+    "VEC2" => Ok(2),
+    "VEC3" => Ok(3),
+    "VEC4" | "MAT2" => Ok(4),
+    "MAT3" => Ok(9),
+    "MAT4" => Ok(16),
+    _ => Err(LoadError::ParseFailed(format!("unknown accessor type {}", ty)))
+  }
+}
+
+fn convert_node(def: &NodeDef) -> Node {
+  let trs = match def.matrix {
+    Some(ref m) => decompose(m),
+    None => {
+      Trs {
+        t: def.translation,
+        r: def.rotation,
+        s: def.scale
+      }
+    }
+  };
+
+  Node {
+    name: def.name.clone(),
+    children: def.children.clone(),
+    mesh: def.mesh,
+    skin: def.skin,
+    weights: def.weights.clone().unwrap_or_else(Vec::new),
+    translation: Vector3::new(trs.t[0], trs.t[1], trs.t[2]),
+    rotation: UnitQuaternion::new(&Quaternion::new(trs.r[3], trs.r[0], trs.r[1], trs.r[2])),
+    scale: Scale::new(trs.s[0], trs.s[1], trs.s[2])
+  }
+}
+
+// Decompose a column-major matrix into a translation, a rotation and a scale. Shearing is lost.
+fn decompose(m: &[f32; 16]) -> Trs {
+  let x = [m[0], m[1], m[2]];
+  let y = [m[4], m[5], m[6]];
+  let z = [m[8], m[9], m[10]];
+  let mut s = [dot(x, x).sqrt(), dot(y, y).sqrt(), dot(z, z).sqrt()];
+
+  // a negative determinant means a mirror, carried by the scale
+  if dot(x, cross(y, z)) < 0. {
+    s[0] = -s[0];
+  }
+
+  let unit = |v: [f32; 3], s: f32| if s != 0. { [v[0] / s, v[1] / s, v[2] / s] } else { v };
+  let (x, y, z) = (unit(x, s[0]), unit(y, s[1]), unit(z, s[2]));
+
+  // rotation matrix to quaternion
+  let trace = x[0] + y[1] + z[2];
+  let r = if trace > 0. {
+    let k = 0.5 / (trace + 1.).sqrt();
+    [(y[2] - z[1]) * k, (z[0] - x[2]) * k, (x[1] - y[0]) * k, 0.25 / k]
+  } else if x[0] > y[1] && x[0] > z[2] {
+    let k = 2. * (1. + x[0] - y[1] - z[2]).sqrt();
+    [0.25 * k, (y[0] + x[1]) / k, (z[0] + x[2]) / k, (y[2] - z[1]) / k]
+  } else if y[1] > z[2] {
+    let k = 2. * (1. + y[1] - x[0] - z[2]).sqrt();
+    [(y[0] + x[1]) / k, 0.25 * k, (z[1] + y[2]) / k, (z[0] - x[2]) / k]
+  } else {
+    let k = 2. * (1. + z[2] - x[0] - y[1]).sqrt();
+    [(z[0] + x[2]) / k, (z[1] + y[2]) / k, 0.25 * k, (x[1] - y[0]) / k]
+  };
+
+  Trs {
+    t: [m[12], m[13], m[14]],
+    r: r,
+    s: s
+  }
+}
+
+// Hamilton product of quaternions stored as (x, y, z, w).
+fn quat_mul(a: [f32; 4], b: [f32; 4]) -> [f32; 4] {
+  [
+    a[3] * b[0] + a[0] * b[3] + a[1] * b[2] - a[2] * b[1],
+    a[3] * b[1] - a[0] * b[2] + a[1] * b[3] + a[2] * b[0],
+    a[3] * b[2] + a[0] * b[1] - a[1] * b[0] + a[2] * b[3],
+    a[3] * b[3] - a[0] * b[0] - a[1] * b[1] - a[2] * b[2]
+  ]
+}
+
+fn rotate(q: [f32; 4], v: [f32; 3]) -> [f32; 3] {
+  let u = [q[0], q[1], q[2]];
+  let t = scale(cross(u, v), 2.);
+
+  add(add(v, scale(t, q[3])), cross(u, t))
+}
+
+// The subset of the glTF schema we use. Unknown properties are ignored.
+
+#[derive(Debug, Deserialize)]
+struct Document {
+  #[serde(default)]
+  scene: Option<usize>,
+  #[serde(default)]
+  scenes: Vec<SceneDef>,
+  #[serde(default)]
+  nodes: Vec<NodeDef>,
+  #[serde(default)]
+  meshes: Vec<MeshDef>,
+  #[serde(default)]
+  accessors: Vec<AccessorDef>,
+  #[serde(default, rename = "bufferViews")]
+  buffer_views: Vec<BufferViewDef>,
+  #[serde(default)]
+  buffers: Vec<BufferDef>,
+  #[serde(default)]
+  materials: Vec<MaterialDef>,
+  #[serde(default)]
+  textures: Vec<TextureDef>,
+  #[serde(default)]
+  images: Vec<ImageDef>,
+  #[serde(default)]
+  animations: Vec<AnimationDef>,
+  #[serde(default)]
+  skins: Vec<SkinDef>
+}
+
+#[derive(Debug, Deserialize)]
+struct SceneDef {
+  #[serde(default)]
+  nodes: Vec<usize>
+}
+
+#[derive(Debug, Deserialize)]
+struct NodeDef {
+  #[serde(default)]
+  name: Option<String>,
+  #[serde(default)]
+  children: Vec<usize>,
+  #[serde(default)]
+  mesh: Option<usize>,
+  #[serde(default)]
+  skin: Option<usize>,
+  #[serde(default)]
+  weights: Option<Vec<f32>>,
+  #[serde(default)]
+  matrix: Option<[f32; 16]>,
+  #[serde(default = "def_translation")]
+  translation: [f32; 3],
+  #[serde(default = "def_rotation")]
+  rotation: [f32; 4],
+  #[serde(default = "def_scale")]
+  scale: [f32; 3]
+}
+
+#[derive(Debug, Deserialize)]
+struct MeshDef {
+  primitives: Vec<PrimitiveDef>,
+  #[serde(default)]
+  weights: Vec<f32>
+}
+
+#[derive(Debug, Deserialize)]
+struct PrimitiveDef {
+  attributes: HashMap<String, usize>,
+  #[serde(default)]
+  indices: Option<usize>,
+  #[serde(default)]
+  material: Option<usize>,
+  #[serde(default = "def_mode")]
+  mode: u32,
+  #[serde(default)]
+  targets: Vec<HashMap<String, usize>>
+}
+
+#[derive(Debug, Deserialize)]
+struct AccessorDef {
+  #[serde(default, rename = "bufferView")]
+  buffer_view: Option<usize>,
+  #[serde(default, rename = "byteOffset")]
+  byte_offset: usize,
+  #[serde(rename = "componentType")]
+  component_type: u32,
+  #[serde(default)]
+  normalized: bool,
+  count: usize,
+  #[serde(rename = "type")]
+  ty: String,
+  #[serde(default)]
+  sparse: Option<::serde_json::Value>
+}
+
+#[derive(Debug, Deserialize)]
+struct BufferViewDef {
+  buffer: usize,
+  #[serde(default, rename = "byteOffset")]
+  byte_offset: usize,
+  #[serde(rename = "byteLength")]
+  byte_length: usize,
+  #[serde(default, rename = "byteStride")]
+  byte_stride: Option<usize>
+}
+
+#[derive(Debug, Deserialize)]
+struct BufferDef {
+  #[serde(default)]
+  uri: Option<String>,
+  #[serde(rename = "byteLength")]
+  byte_length: usize
+}
+
+#[derive(Debug, Deserialize)]
+struct MaterialDef {
+  #[serde(default)]
+  name: Option<String>,
+  #[serde(default, rename = "pbrMetallicRoughness")]
+  pbr_metallic_roughness: PbrDef,
+  #[serde(default, rename = "normalTexture")]
+  normal_texture: Option<TextureRef>,
+  #[serde(default, rename = "emissiveTexture")]
+  emissive_texture: Option<TextureRef>,
+  #[serde(default = "def_emissive", rename = "emissiveFactor")]
+  emissive_factor: [f32; 3],
+  #[serde(default = "def_alpha_mode", rename = "alphaMode")]
+  alpha_mode: String
+}
+
+#[derive(Debug, Deserialize)]
+struct PbrDef {
+  #[serde(default = "def_base_color", rename = "baseColorFactor")]
+  base_color_factor: [f32; 4],
+  #[serde(default, rename = "baseColorTexture")]
+  base_color_texture: Option<TextureRef>,
+  #[serde(default = "def_factor", rename = "metallicFactor")]
+  metallic_factor: f32,
+  #[serde(default = "def_factor", rename = "roughnessFactor")]
+  roughness_factor: f32
+}
+
+impl Default for PbrDef {
+  fn default() -> Self {
+    PbrDef {
+      base_color_factor: def_base_color(),
+      base_color_texture: None,
+      metallic_factor: def_factor(),
+      roughness_factor: def_factor()
+    }
+  }
+}
+
+#[derive(Debug, Deserialize)]
+struct TextureRef {
+  index: usize
+}
+
+#[derive(Debug, Deserialize)]
+struct TextureDef {
+  #[serde(default)]
+  source: Option<usize>
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct ImageDef {
+  #[serde(default)]
+  uri: Option<String>,
+  #[serde(default, rename = "bufferView")]
+  buffer_view: Option<usize>
+}
+
+#[derive(Debug, Deserialize)]
+struct AnimationDef {
+  #[serde(default)]
+  name: Option<String>,
+  channels: Vec<ChannelDef>,
+  samplers: Vec<AnimationSamplerDef>
+}
+
+#[derive(Debug, Deserialize)]
+struct ChannelDef {
+  sampler: usize,
+  target: TargetDef
+}
+
+#[derive(Debug, Deserialize)]
+struct TargetDef {
+  #[serde(default)]
+  node: Option<usize>,
+  path: String
+}
+
+#[derive(Debug, Deserialize)]
+struct SkinDef {
+  #[serde(default)]
+  name: Option<String>,
+  joints: Vec<usize>,
+  #[serde(default, rename = "inverseBindMatrices")]
+  inverse_bind_matrices: Option<usize>
+}
+
+#[derive(Debug, Deserialize)]
+struct AnimationSamplerDef {
+  input: usize,
+  output: usize,
+  #[serde(default = "def_interpolation")]
+  interpolation: String
+}
+
+fn def_translation() -> [f32; 3] { [0., 0., 0.] }
+fn def_rotation() -> [f32; 4] { [0., 0., 0., 1.] }
+fn def_scale() -> [f32; 3] { [1., 1., 1.] }
+fn def_mode() -> u32 { 4 }
+fn def_emissive() -> [f32; 3] { [0., 0., 0.] }
+fn def_alpha_mode() -> String { "OPAQUE".to_owned() }
+fn def_base_color() -> [f32; 4] { [1., 1., 1., 1.] }
+fn def_factor() -> f32 { 1. }
+fn def_interpolation() -> String { "LINEAR".to_owned() }
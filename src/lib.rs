@@ -28,6 +28,8 @@ pub mod report;
 pub mod resource;
 #[macro_use]
 pub mod scene;
+#[macro_use]
+pub mod shader;
 
 pub mod anim;
 pub mod app;
@@ -36,36 +38,54 @@ pub mod camera;
 pub mod compositor;
 pub mod color;
 pub mod device;
+pub mod event;
 pub mod extra;
+pub mod glsl;
+pub mod gltf;
 pub mod gui;
 pub mod id;
 pub mod linear;
+pub mod material;
+pub mod mesh;
 pub mod model;
+pub mod morph;
 pub mod object;
 pub mod projection;
 pub mod renderer;
-pub mod shader;
+pub mod skeleton;
 pub mod spline;
+pub mod tempo;
 pub mod texture;
+pub mod timeline;
 pub mod transform;
 
-pub use anim::Cont;
+pub use anim::{Boundary, Cont};
 pub use app::App;
 pub use bootstrap::{LuminanceBackend, Keyboard, Mouse, MouseMove, Scroll, WindowDim, bootstrap};
 pub use camera::{Camera, Freefly};
 pub use color::Color;
 pub use compositor::{Compositor, Screen};
 pub use device::Device;
+pub use event::{Envelope, EnvelopeShape, EventTrack};
+pub use gltf::{Animation, ChannelTarget, Gltf, Node, Primitive};
 pub use id::Id;
 pub use linear::{Matrix4};
-pub use model::{Model, ModelError, Part};
+pub use material::{Material, MaterialLibrary};
+pub use mesh::{Bounds, MeshData, VertexPosition};
+pub use model::{Model, ModelArgs, ModelData, ModelError, MorphVertex, Normals, Part, SkinnedVertex, VertexLayout,
+                generate_tangents, skinned_mesh, tangent_mesh, upload_mesh};
+pub use morph::{MorphMesh, MorphTarget};
 pub use object::Object;
 pub use projection::{Projectable, perspective};
 pub use renderer::Renderer;
-pub use resource::{Load, LoadError, Reload};
-pub use shader::{Program, ShaderError, new_program};
+pub use resource::{Extension, Load, LoadError, Reload};
+pub use shader::{Builtins, Defines, Program, ProgramArgs, ShaderError, UniformError, UniformInterface,
+                 UniformType, new_program};
 pub use scene::Scene;
+pub use skeleton::{Clip, Joint, JointTransform, Skeleton};
 pub use spline::{Interpolate, Interpolation, Key, Sampler, Spline, SplineIterator, Time};
-pub use texture::{TextureImage, load_rgba_texture, save_rgba_texture};
+pub use tempo::{TempoChange, TempoMap};
+pub use texture::{TextureImage, load_rgba_texture, load_rgba_texture_from_memory, save_rgba_texture};
+pub use timeline::{DemoPart, Schedule, ScheduleEntry, TimeSource, Timeline};
 pub use transform::{Axis, Orientation, Position, Translation, Transformable, X_AXIS, Y_AXIS, Z_AXIS,
                    Scale, translation_matrix};
@@ -0,0 +1,196 @@
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use resource::{Cache, Load, LoadError};
+
+/// Surface properties.
+///
+/// Texture maps are stored by name. Wavefront models resolve them through the cache, so a map name
+/// is the path of the texture relative to `data/textures`; glTF models name them after their image
+/// – see `gltf::Gltf::materials`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Material {
+  pub name: String,
+  pub ambient: [f32; 3],
+  pub diffuse: [f32; 3],
+  pub specular: [f32; 3],
+  pub emissive: [f32; 3],
+  /// Specular exponent.
+  pub shininess: f32,
+  /// Opacity, `1` being fully opaque.
+  pub opacity: f32,
+  pub diffuse_map: Option<String>,
+  pub specular_map: Option<String>,
+  pub emissive_map: Option<String>,
+  pub shininess_map: Option<String>,
+  pub opacity_map: Option<String>,
+  pub bump_map: Option<String>
+}
+
+impl Material {
+  /// White, diffuse-only and opaque material.
+  pub fn new(name: &str) -> Self {
+    Material {
+      name: name.to_owned(),
+      ambient: [0., 0., 0.],
+      diffuse: [1., 1., 1.],
+      specular: [0., 0., 0.],
+      emissive: [0., 0., 0.],
+      shininess: 0.,
+      opacity: 1.,
+      diffuse_map: None,
+      specular_map: None,
+      emissive_map: None,
+      shininess_map: None,
+      opacity_map: None,
+      bump_map: None
+    }
+  }
+
+  /// Names of all the texture maps the material uses.
+  pub fn maps(&self) -> Vec<&str> {
+    [&self.diffuse_map, &self.specular_map, &self.emissive_map, &self.shininess_map, &self.opacity_map, &self.bump_map]
+      .iter()
+      .filter_map(|map| map.as_ref().map(|map| map.as_str()))
+      .collect()
+  }
+
+  /// Is the map holding colors – as opposed to data such as bumps?
+  pub fn is_color_map(&self, map: &str) -> bool {
+    [&self.diffuse_map, &self.specular_map, &self.emissive_map].iter().any(|m| m.as_ref().map_or(false, |m| m == map))
+  }
+}
+
+/// Set of materials, as found in a Wavefront `.mtl` file.
+///
+/// If the library is retrieved from the cache, the path must point to a `.mtl` file. The supported
+/// statements are `newmtl`, `Ka`, `Kd`, `Ks`, `Ke`, `Ns`, `d`, `Tr`, `map_Kd`, `map_Ks`, `map_Ke`,
+/// `map_Ns`, `map_d` and `map_bump` – or `bump` and `norm`. Options of the map statements are
+/// ignored. Other statements are skipped.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MaterialLibrary {
+  materials: Vec<Material>
+}
+
+impl MaterialLibrary {
+  pub fn new(materials: Vec<Material>) -> Self {
+    MaterialLibrary {
+      materials: materials
+    }
+  }
+
+  /// Parse the content of a `.mtl` file.
+  pub fn parse(src: &str) -> Result<Self, LoadError> {
+    let mut materials: Vec<Material> = Vec::new();
+
+    for (line_nb, line) in src.lines().enumerate() {
+      let line_nb = line_nb + 1;
+      let line = line.split('#').next().unwrap().trim();
+      let mut words = line.split_whitespace();
+
+      let statement = match words.next() {
+        Some(statement) => statement,
+        None => continue
+      };
+
+      let args: Vec<_> = words.collect();
+
+      if statement == "newmtl" {
+        let name = args.join(" ");
+
+        if name.is_empty() {
+          return Err(LoadError::ParseFailed(format!("(line {}) material without a name", line_nb)));
+        }
+
+        materials.push(Material::new(&name));
+        continue;
+      }
+
+      let material = materials.last_mut().ok_or(LoadError::ParseFailed(format!("(line {}) {} statement before any newmtl", line_nb, statement)))?;
+
+      match statement {
+        "Ka" => material.ambient = parse_color(&args, line_nb)?,
+        "Kd" => material.diffuse = parse_color(&args, line_nb)?,
+        "Ks" => material.specular = parse_color(&args, line_nb)?,
+        "Ke" => material.emissive = parse_color(&args, line_nb)?,
+        "Ns" => material.shininess = parse_float(&args, line_nb)?,
+        "d" => material.opacity = parse_float(&args, line_nb)?,
+        "Tr" => material.opacity = 1. - parse_float(&args, line_nb)?,
+        "map_Kd" => material.diffuse_map = Some(parse_map(&args, line_nb)?),
+        "map_Ks" => material.specular_map = Some(parse_map(&args, line_nb)?),
+        "map_Ke" => material.emissive_map = Some(parse_map(&args, line_nb)?),
+        "map_Ns" => material.shininess_map = Some(parse_map(&args, line_nb)?),
+        "map_d" => material.opacity_map = Some(parse_map(&args, line_nb)?),
+        "map_bump" | "bump" | "norm" => material.bump_map = Some(parse_map(&args, line_nb)?),
+        _ => {
+          deb!("  skipping {} statement", statement);
+        }
+      }
+    }
+
+    Ok(MaterialLibrary::new(materials))
+  }
+
+  pub fn materials(&self) -> &[Material] {
+    &self.materials
+  }
+
+  /// Index of a material by its name.
+  pub fn index_of(&self, name: &str) -> Option<usize> {
+    self.materials.iter().position(|material| material.name == name)
+  }
+
+  pub fn get(&self, name: &str) -> Option<&Material> {
+    self.index_of(name).map(|i| &self.materials[i])
+  }
+}
+
+impl IntoIterator for MaterialLibrary {
+  type Item = Material;
+  type IntoIter = ::std::vec::IntoIter<Material>;
+
+  fn into_iter(self) -> Self::IntoIter {
+    self.materials.into_iter()
+  }
+}
+
+fn parse_float(args: &[&str], line_nb: usize) -> Result<f32, LoadError> {
+  args.get(0)
+    .and_then(|x| x.parse().ok())
+    .ok_or(LoadError::ParseFailed(format!("(line {}) expected a number", line_nb)))
+}
+
+// A single value is a gray level.
+fn parse_color(args: &[&str], line_nb: usize) -> Result<[f32; 3], LoadError> {
+  let parsed: Result<Vec<f32>, _> = args.iter().map(|x| x.parse()).collect();
+
+  match parsed {
+    Ok(ref c) if c.len() == 1 => Ok([c[0], c[0], c[0]]),
+    Ok(ref c) if c.len() == 3 => Ok([c[0], c[1], c[2]]),
+    _ => Err(LoadError::ParseFailed(format!("(line {}) expected a color", line_nb)))
+  }
+}
+
+// The file name comes last, after the options.
+fn parse_map(args: &[&str], line_nb: usize) -> Result<String, LoadError> {
+  args.last()
+    .map(|name| name.to_string())
+    .ok_or(LoadError::ParseFailed(format!("(line {}) expected a texture name", line_nb)))
+}
+
+impl<'a> Load<'a> for MaterialLibrary {
+  type Args = ();
+
+  fn load<P>(path: P, _: &mut Cache<'a>, _: Self::Args) -> Result<Self, LoadError> where P: AsRef<Path> {
+    let path = path.as_ref();
+
+    info!("loading material library: {:?}", path);
+
+    let mut src = String::new();
+    let mut file = File::open(path).map_err(|e| LoadError::FileNotFound(path.to_path_buf(), format!("{:?}", e)))?;
+    file.read_to_string(&mut src).map_err(|e| LoadError::ParseFailed(format!("{:?}", e)))?;
+
+    MaterialLibrary::parse(&src)
+  }
+}
@@ -0,0 +1,335 @@
+//! Binary model format.
+//!
+//! A binary model – `.smesh` – holds a `ModelData` ready to be uploaded: interleaved vertices,
+//! indices, the primitive mode and the bounds of every part, the materials, and the bounds of the
+//! whole model. Loading it doesn’t involve any parsing nor index rebuilding. Binary models are
+//! produced from Wavefront files with `spectra convert`.
+//!
+//! All values are little-endian:
+//!
+//! ```text
+//! magic       "SMSH"
+//! version     u32
+//! source      string, the path of the source file relative to the binary model; empty if none
+//! bounds      6 × f32 (min, max)
+//! materials   u32 count, then the materials
+//! parts       u32 count, then the parts
+//!
+//! material    name: string, ambient, diffuse, specular, emissive: 3 × f32, shininess, opacity: f32,
+//!             diffuse, specular, emissive, shininess, opacity and bump maps: optional string
+//! part        mode: u8, material: u32 (0xFFFFFFFF if none), bounds: 6 × f32,
+//!             u32 vertex count, then the vertices: 8 × f32 (position, normal, texture coordinates),
+//!             u8 flag, followed if set by a u32 index count, then the indices: u32
+//! string      u32 byte length, then UTF-8 bytes
+//! optional    u8 flag, followed by the value if set
+//! ```
+
+use luminance::Mode;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+use material::Material;
+use mesh::{Bounds, MeshData};
+use model::{ModelData, Normals, Vertex};
+use resource::LoadError;
+
+/// Extension of binary models.
+pub const EXTENSION: &'static str = "smesh";
+
+const MAGIC: &'static [u8; 4] = b"SMSH";
+const VERSION: u32 = 1;
+const NO_MATERIAL: u32 = 0xFFFFFFFF;
+
+/// Write a binary model, along with the path of its source, relative to the binary model.
+pub fn write<W>(data: &ModelData, source: Option<&str>, w: &mut W) -> io::Result<()> where W: Write {
+  let mut bytes = Vec::new();
+
+  bytes.extend_from_slice(MAGIC);
+  put_u32(&mut bytes, VERSION);
+  put_str(&mut bytes, source.unwrap_or(""));
+  put_bounds(&mut bytes, &data.bounds());
+
+  put_u32(&mut bytes, data.materials.len() as u32);
+
+  for material in &data.materials {
+    put_str(&mut bytes, &material.name);
+
+    for color in &[material.ambient, material.diffuse, material.specular, material.emissive] {
+      put_f32s(&mut bytes, color);
+    }
+
+    put_f32s(&mut bytes, &[material.shininess, material.opacity]);
+
+    for map in &[&material.diffuse_map, &material.specular_map, &material.emissive_map, &material.shininess_map, &material.opacity_map, &material.bump_map] {
+      match **map {
+        Some(ref map) => {
+          bytes.push(1);
+          put_str(&mut bytes, map);
+        },
+        None => bytes.push(0)
+      }
+    }
+  }
+
+  put_u32(&mut bytes, data.parts.len() as u32);
+
+  for &(ref mesh, material) in &data.parts {
+    bytes.push(encode_mode(mesh.mode));
+    put_u32(&mut bytes, material.map_or(NO_MATERIAL, |material| material as u32));
+    put_bounds(&mut bytes, &mesh.bounds);
+    put_u32(&mut bytes, mesh.vertices.len() as u32);
+
+    for &(p, n, uv) in &mesh.vertices {
+      put_f32s(&mut bytes, &p);
+      put_f32s(&mut bytes, &n);
+      put_f32s(&mut bytes, &uv);
+    }
+
+    match mesh.indices {
+      Some(ref indices) => {
+        bytes.push(1);
+        put_u32(&mut bytes, indices.len() as u32);
+
+        for &index in indices {
+          put_u32(&mut bytes, index);
+        }
+      },
+      None => bytes.push(0)
+    }
+  }
+
+  w.write_all(&bytes)
+}
+
+/// Read a binary model. Return it along with the path of its source, if any.
+pub fn read(bytes: &[u8]) -> Result<(ModelData, Option<String>), LoadError> {
+  let mut r = Reader::new(bytes);
+
+  if r.bytes(4)? != MAGIC {
+    return Err(LoadError::ParseFailed("not a binary model".to_owned()));
+  }
+
+  let version = r.u32()?;
+
+  if version != VERSION {
+    return Err(LoadError::ParseFailed(format!("unsupported binary model version {}; convert the model again", version)));
+  }
+
+  let source = r.string()?;
+  let _ = r.bounds()?;
+
+  let material_count = r.u32()?;
+  let mut materials = Vec::with_capacity(material_count as usize);
+
+  for _ in 0..material_count {
+    let mut material = Material::new(&r.string()?);
+
+    material.ambient = r.vec3()?;
+    material.diffuse = r.vec3()?;
+    material.specular = r.vec3()?;
+    material.emissive = r.vec3()?;
+    material.shininess = r.f32()?;
+    material.opacity = r.f32()?;
+    material.diffuse_map = r.optional_string()?;
+    material.specular_map = r.optional_string()?;
+    material.emissive_map = r.optional_string()?;
+    material.shininess_map = r.optional_string()?;
+    material.opacity_map = r.optional_string()?;
+    material.bump_map = r.optional_string()?;
+
+    materials.push(material);
+  }
+
+  let part_count = r.u32()?;
+  let mut parts = Vec::with_capacity(part_count as usize);
+
+  for _ in 0..part_count {
+    let mode = decode_mode(r.u8()?)?;
+    let material = match r.u32()? {
+      NO_MATERIAL => None,
+      material if (material as usize) < materials.len() => Some(material as usize),
+      material => return Err(LoadError::ParseFailed(format!("unknown material {}", material)))
+    };
+    let bounds = r.bounds()?;
+
+    let vertex_count = r.u32()? as usize;
+    // don’t trust the count for the allocation
+    let mut vertices: Vec<Vertex> = Vec::with_capacity(vertex_count.min(r.remaining() / 32));
+
+    for _ in 0..vertex_count {
+      vertices.push((r.vec3()?, r.vec3()?, [r.f32()?, r.f32()?]));
+    }
+
+    let indices = if r.u8()? != 0 {
+      let index_count = r.u32()? as usize;
+      let mut indices = Vec::with_capacity(index_count.min(r.remaining() / 4));
+
+      for _ in 0..index_count {
+        let index = r.u32()?;
+
+        if index as usize >= vertex_count {
+          return Err(LoadError::ParseFailed(format!("vertex index {} out of bounds", index)));
+        }
+
+        indices.push(index);
+      }
+
+      Some(indices)
+    } else {
+      None
+    };
+
+    parts.push((MeshData {
+      vertices: vertices,
+      indices: indices,
+      mode: mode,
+      bounds: bounds
+    }, material));
+  }
+
+  let data = ModelData {
+    parts: parts,
+    materials: materials
+  };
+
+  Ok((data, if source.is_empty() { None } else { Some(source) }))
+}
+
+/// Read a binary model from a file – see `read`.
+pub fn read_file<P>(path: P) -> Result<(ModelData, Option<String>), LoadError> where P: AsRef<Path> {
+  let path = path.as_ref();
+  let mut bytes = Vec::new();
+  let mut file = File::open(path).map_err(|e| LoadError::FileNotFound(path.to_path_buf(), format!("{:?}", e)))?;
+  file.read_to_end(&mut bytes).map_err(|e| LoadError::ParseFailed(format!("{:?}", e)))?;
+
+  read(&bytes)
+}
+
+/// Convert a Wavefront `.obj` file into a binary model. Missing normals are generated according to
+/// `normals`.
+pub fn convert_obj_file<P, Q>(input: P, output: Q, normals: Normals) -> Result<(), LoadError> where P: AsRef<Path>, Q: AsRef<Path> {
+  let (input, output) = (input.as_ref(), output.as_ref());
+  let (data, _) = ModelData::from_obj(input, normals)?;
+
+  // the source is relative to the binary model when they share a directory
+  let source = if input.parent() == output.parent() {
+    input.file_name().and_then(|name| name.to_str()).map(|name| name.to_owned())
+  } else {
+    input.canonicalize().ok().and_then(|path| path.to_str().map(|path| path.to_owned()))
+  };
+
+  let mut file = File::create(output).map_err(|e| LoadError::FileNotFound(output.to_path_buf(), format!("{:?}", e)))?;
+  write(&data, source.as_ref().map(|source| source.as_str()), &mut file).map_err(|e| LoadError::ConversionFailed(format!("{:?}", e)))
+}
+
+fn encode_mode(mode: Mode) -> u8 {
+  match mode {
+    Mode::Point => 0,
+    Mode::Line => 1,
+    Mode::LineStrip => 2,
+    Mode::Triangle => 3,
+    Mode::TriangleFan => 4,
+    Mode::TriangleStrip => 5
+  }
+}
+
+fn decode_mode(mode: u8) -> Result<Mode, LoadError> {
+  match mode {
+    0 => Ok(Mode::Point),
+    1 => Ok(Mode::Line),
+    2 => Ok(Mode::LineStrip),
+    3 => Ok(Mode::Triangle),
+    4 => Ok(Mode::TriangleFan),
+    5 => Ok(Mode::TriangleStrip),
+    _ => Err(LoadError::ParseFailed(format!("unknown primitive mode {}", mode)))
+  }
+}
+
+fn put_u32(bytes: &mut Vec<u8>, x: u32) {
+  bytes.extend_from_slice(&[x as u8, (x >> 8) as u8, (x >> 16) as u8, (x >> 24) as u8]);
+}
+
+fn put_f32s(bytes: &mut Vec<u8>, xs: &[f32]) {
+  for &x in xs {
+    put_u32(bytes, x.to_bits());
+  }
+}
+
+fn put_str(bytes: &mut Vec<u8>, s: &str) {
+  put_u32(bytes, s.len() as u32);
+  bytes.extend_from_slice(s.as_bytes());
+}
+
+fn put_bounds(bytes: &mut Vec<u8>, bounds: &Bounds) {
+  put_f32s(bytes, &bounds.min);
+  put_f32s(bytes, &bounds.max);
+}
+
+// Cursor over the bytes of a binary model.
+struct Reader<'a> {
+  bytes: &'a [u8],
+  offset: usize
+}
+
+impl<'a> Reader<'a> {
+  fn new(bytes: &'a [u8]) -> Self {
+    Reader {
+      bytes: bytes,
+      offset: 0
+    }
+  }
+
+  fn remaining(&self) -> usize {
+    self.bytes.len() - self.offset
+  }
+
+  fn bytes(&mut self, len: usize) -> Result<&'a [u8], LoadError> {
+    if len > self.remaining() {
+      return Err(LoadError::ParseFailed("truncated binary model".to_owned()));
+    }
+
+    let bytes = &self.bytes[self.offset..self.offset + len];
+    self.offset += len;
+
+    Ok(bytes)
+  }
+
+  fn u8(&mut self) -> Result<u8, LoadError> {
+    self.bytes(1).map(|bytes| bytes[0])
+  }
+
+  fn u32(&mut self) -> Result<u32, LoadError> {
+    self.bytes(4).map(|b| b[0] as u32 | (b[1] as u32) << 8 | (b[2] as u32) << 16 | (b[3] as u32) << 24)
+  }
+
+  fn f32(&mut self) -> Result<f32, LoadError> {
+    self.u32().map(f32::from_bits)
+  }
+
+  fn vec3(&mut self) -> Result<[f32; 3], LoadError> {
+    Ok([self.f32()?, self.f32()?, self.f32()?])
+  }
+
+  fn bounds(&mut self) -> Result<Bounds, LoadError> {
+    Ok(Bounds {
+      min: self.vec3()?,
+      max: self.vec3()?
+    })
+  }
+
+  fn string(&mut self) -> Result<String, LoadError> {
+    let len = self.u32()? as usize;
+    let bytes = self.bytes(len)?;
+
+    String::from_utf8(bytes.to_vec()).map_err(|e| LoadError::ParseFailed(format!("{:?}", e)))
+  }
+
+  fn optional_string(&mut self) -> Result<Option<String>, LoadError> {
+    if self.u8()? != 0 {
+      self.string().map(Some)
+    } else {
+      Ok(None)
+    }
+  }
+}
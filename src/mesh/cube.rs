@@ -0,0 +1,88 @@
+use luminance::Mode;
+
+use mesh::MeshData;
+use model::Vertex;
+
+// Texture coordinates of the four corners of every face.
+const FACE_UVS: [[f32; 2]; 4] = [[1., 0.], [1., 1.], [0., 0.], [0., 1.]];
+
+// A unit cube, with normals and texture coordinates. Every face has its own vertices.
+//
+//     ×-----×
+//    /|    /|
+//   ×-+---× |
+//   | ×---+-×
+//   |/    |/
+//   ×-----×
+pub fn new_cube() -> MeshData<Vertex> {
+  let corners = [
+    // front face
+    ([ 1., -1.,  1.], [ 0.,  0.,  1.]), // 0
+    ([ 1.,  1.,  1.], [ 0.,  0.,  1.]),
+    ([-1., -1.,  1.], [ 0.,  0.,  1.]),
+    ([-1.,  1.,  1.], [ 0.,  0.,  1.]),
+    // back face
+    ([ 1., -1., -1.], [ 0.,  0., -1.]), // 4
+    ([ 1.,  1., -1.], [ 0.,  0., -1.]),
+    ([-1., -1., -1.], [ 0.,  0., -1.]),
+    ([-1.,  1., -1.], [ 0.,  0., -1.]),
+    // left face
+    ([-1., -1.,  1.], [-1.,  0.,  0.]), // 8
+    ([-1.,  1.,  1.], [-1.,  0.,  0.]),
+    ([-1., -1., -1.], [-1.,  0.,  0.]),
+    ([-1.,  1., -1.], [-1.,  0.,  0.]),
+    // right face
+    ([ 1., -1., -1.], [ 1.,  0.,  0.]), // 12
+    ([ 1.,  1., -1.], [ 1.,  0.,  0.]),
+    ([ 1., -1.,  1.], [ 1.,  0.,  0.]),
+    ([ 1.,  1.,  1.], [ 1.,  0.,  0.]),
+    // top face
+    ([ 1.,  1.,  1.], [ 0.,  1., 0.]), // 16
+    ([ 1.,  1., -1.], [ 0.,  1., 0.]),
+    ([-1.,  1.,  1.], [ 0.,  1., 0.]),
+    ([-1.,  1., -1.], [ 0.,  1., 0.]),
+    // bottom face
+    ([ 1., -1., -1.], [ 0., -1., 0.]), // 20
+    ([ 1., -1.,  1.], [ 0., -1., 0.]),
+    ([-1., -1., -1.], [ 0., -1., 0.]),
+    ([-1., -1.,  1.], [ 0., -1., 0.]),
+  ];
+
+  let vertices = corners.iter().enumerate().map(|(i, &(p, n))| (p, n, FACE_UVS[i % 4])).collect();
+
+  let indices = vec![
+    0, 1, 2, 2, 1, 3, // front face
+    4, 5, 6, 6, 5, 7, // back face
+    8, 9, 10, 10, 9, 11, // left face
+    12, 13, 14, 14, 13, 15, // right face
+    16, 17, 18, 18, 17, 19, // top face
+    20, 21, 22, 22, 21, 23, // bottom face
+  ];
+
+  MeshData::new(Mode::Triangle, vertices, Some(indices))
+}
+
+// A unit cube made of its eight corners only.
+pub fn new_cube_corners() -> MeshData<[f32; 3]> {
+  let vertices = [
+    [ 1., -1.,  1.],
+    [ 1.,  1.,  1.],
+    [-1., -1.,  1.],
+    [-1.,  1.,  1.],
+    [ 1., -1., -1.],
+    [ 1.,  1., -1.],
+    [-1., -1., -1.],
+    [-1.,  1., -1.],
+  ];
+
+  let indices = [
+    0, 1, 2, 2, 1, 3, // front face
+    1, 5, 3, 3, 5, 7, // top face
+    2, 3, 6, 6, 3, 7, // right face
+    4, 5, 0, 0, 5, 1, // left face
+    4, 0, 6, 6, 0, 2, // bottom face
+    4, 5, 6, 6, 5, 7, // back face
+  ];
+
+  MeshData::new(Mode::Triangle, vertices.to_vec(), Some(indices.to_vec()))
+}
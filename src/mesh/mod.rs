@@ -0,0 +1,178 @@
+//! CPU-side meshes.
+//!
+//! Loaders and generators produce `MeshData`, which can be inspected, transformed and processed
+//! without any GL context. Uploading a mesh to the GPU is a separate step – see `MeshData::upload`.
+
+pub mod binary;
+pub mod cube;
+pub mod optimize;
+pub mod shapes;
+pub mod vector;
+
+use luminance::Mode;
+use luminance::vertex::Vertex as VertexFormat;
+use luminance_gl::gl33::Tess;
+
+/// Vertex types which have a position.
+pub trait VertexPosition {
+  fn position(&self) -> [f32; 3];
+}
+
+impl VertexPosition for [f32; 2] {
+  fn position(&self) -> [f32; 3] {
+    [self[0], self[1], 0.]
+  }
+}
+
+impl VertexPosition for [f32; 3] {
+  fn position(&self) -> [f32; 3] {
+    *self
+  }
+}
+
+impl<A> VertexPosition for ([f32; 3], A) {
+  fn position(&self) -> [f32; 3] {
+    self.0
+  }
+}
+
+impl<A, B> VertexPosition for ([f32; 3], A, B) {
+  fn position(&self) -> [f32; 3] {
+    self.0
+  }
+}
+
+impl<A, B, C> VertexPosition for ([f32; 3], A, B, C) {
+  fn position(&self) -> [f32; 3] {
+    self.0
+  }
+}
+
+impl<A, B, C, D> VertexPosition for ([f32; 3], A, B, C, D) {
+  fn position(&self) -> [f32; 3] {
+    self.0
+  }
+}
+
+impl<A, B, C, D, E> VertexPosition for ([f32; 3], A, B, C, D, E) {
+  fn position(&self) -> [f32; 3] {
+    self.0
+  }
+}
+
+impl<A, B, C, D, E, F> VertexPosition for ([f32; 3], A, B, C, D, E, F) {
+  fn position(&self) -> [f32; 3] {
+    self.0
+  }
+}
+
+impl<A, B, C, D, E, F, G, H, I> VertexPosition for ([f32; 3], A, B, C, D, E, F, G, H, I) {
+  fn position(&self) -> [f32; 3] {
+    self.0
+  }
+}
+
+/// Axis-aligned bounding box.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Bounds {
+  pub min: [f32; 3],
+  pub max: [f32; 3]
+}
+
+impl Bounds {
+  /// Bounds containing nothing.
+  pub fn empty() -> Self {
+    Bounds {
+      min: [::std::f32::INFINITY; 3],
+      max: [::std::f32::NEG_INFINITY; 3]
+    }
+  }
+
+  /// Smallest bounds containing a set of points.
+  pub fn from_points<I>(points: I) -> Self where I: IntoIterator<Item = [f32; 3]> {
+    let mut bounds = Bounds::empty();
+
+    for point in points {
+      bounds.insert(point);
+    }
+
+    bounds
+  }
+
+  /// Grow the bounds so that they contain a point.
+  pub fn insert(&mut self, point: [f32; 3]) {
+    for i in 0..3 {
+      self.min[i] = self.min[i].min(point[i]);
+      self.max[i] = self.max[i].max(point[i]);
+    }
+  }
+
+  pub fn is_empty(&self) -> bool {
+    (0..3).any(|i| self.min[i] > self.max[i])
+  }
+
+  pub fn center(&self) -> [f32; 3] {
+    [(self.min[0] + self.max[0]) * 0.5, (self.min[1] + self.max[1]) * 0.5, (self.min[2] + self.max[2]) * 0.5]
+  }
+
+  pub fn size(&self) -> [f32; 3] {
+    [self.max[0] - self.min[0], self.max[1] - self.min[1], self.max[2] - self.min[2]]
+  }
+}
+
+/// Vertices, optional indices and the primitive mode to connect them, along with the bounds of the
+/// vertices.
+#[derive(Clone, Debug)]
+pub struct MeshData<V> {
+  pub vertices: Vec<V>,
+  /// Without indices, vertices are connected in order.
+  pub indices: Option<Vec<u32>>,
+  pub mode: Mode,
+  /// Bounds of the vertices. Update them with `update_bounds` after changing positions.
+  pub bounds: Bounds
+}
+
+impl<V> MeshData<V> where V: VertexPosition {
+  pub fn new(mode: Mode, vertices: Vec<V>, indices: Option<Vec<u32>>) -> Self {
+    let bounds = Bounds::from_points(vertices.iter().map(VertexPosition::position));
+
+    MeshData {
+      vertices: vertices,
+      indices: indices,
+      mode: mode,
+      bounds: bounds
+    }
+  }
+
+  pub fn update_bounds(&mut self) {
+    self.bounds = Bounds::from_points(self.vertices.iter().map(VertexPosition::position));
+  }
+
+  /// Convert the vertices, keeping the indices and the mode.
+  pub fn map_vertices<W, F>(self, f: F) -> MeshData<W> where W: VertexPosition, F: FnMut(V) -> W {
+    let vertices = self.vertices.into_iter().map(f).collect();
+    MeshData::new(self.mode, vertices, self.indices)
+  }
+}
+
+impl<V> MeshData<V> {
+  /// Indices of the vertices, in drawing order. Generated if the mesh has none.
+  pub fn indices(&self) -> Vec<u32> {
+    match self.indices {
+      Some(ref indices) => indices.clone(),
+      None => (0..self.vertices.len() as u32).collect()
+    }
+  }
+
+  /// Number of indices – or vertices, without indices – drawn.
+  pub fn index_count(&self) -> usize {
+    self.indices.as_ref().map_or(self.vertices.len(), |indices| indices.len())
+  }
+}
+
+impl<V> MeshData<V> where V: VertexFormat {
+  /// Upload the mesh to the GPU.
+  pub fn upload(&self) -> Tess {
+    Tess::new(self.mode, &self.vertices, self.indices.as_ref().map(|indices| indices.as_slice()))
+  }
+}
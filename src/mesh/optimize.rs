@@ -0,0 +1,331 @@
+//! Mesh optimization.
+//!
+//! GPUs keep the last transformed vertices in a small cache; triangles reusing vertices recently
+//! transformed are cheaper to draw. This module welds duplicate vertices, reorders triangles so that
+//! they hit that cache more – Tom Forsyth’s linear-speed vertex cache optimization – and reorders
+//! vertices in the order they’re fetched.
+//!
+//! The efficiency of an index buffer is measured with its ACMR – average cache miss ratio – the
+//! number of vertices transformed per triangle. It ranges from `0.5` – the best – to `3`.
+
+use luminance::Mode;
+use std::collections::HashMap;
+use std::fmt;
+
+use mesh::MeshData;
+use model::Vertex;
+
+/// Size of the simulated vertex cache.
+pub const CACHE_SIZE: usize = 32;
+
+// Scoring parameters of the Forsyth algorithm.
+const CACHE_DECAY_POWER: f32 = 1.5;
+const LAST_TRIANGLE_SCORE: f32 = 0.75;
+const VALENCE_BOOST_SCALE: f32 = 2.;
+const VALENCE_BOOST_POWER: f32 = 0.5;
+
+/// Statistics of an optimization.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OptimizationStats {
+  pub vertices_before: usize,
+  pub vertices_after: usize,
+  /// ACMR before the optimization; `None` if the mesh isn’t made of triangles.
+  pub acmr_before: Option<f32>,
+  pub acmr_after: Option<f32>
+}
+
+impl fmt::Display for OptimizationStats {
+  fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+    write!(f, "{} -> {} vertices", self.vertices_before, self.vertices_after)?;
+
+    match (self.acmr_before, self.acmr_after) {
+      (Some(before), Some(after)) => write!(f, ", ACMR {:.3} -> {:.3}", before, after),
+      _ => Ok(())
+    }
+  }
+}
+
+/// Weld, reorder for the vertex cache and reorder for vertex fetching a mesh. Vertices which all
+/// attributes are within `tolerance` of each other are merged. Only triangle lists have their
+/// triangles reordered.
+pub fn optimize(mesh: &MeshData<Vertex>, tolerance: f32) -> (MeshData<Vertex>, OptimizationStats) {
+  let is_triangles = is_triangle_list(mesh.mode);
+  let acmr_before = if is_triangles { Some(acmr(&mesh.indices(), CACHE_SIZE)) } else { None };
+
+  let welded = weld(mesh, tolerance);
+  let indices = if is_triangles {
+    optimize_vertex_cache(&welded.indices(), welded.vertices.len())
+  } else {
+    welded.indices()
+  };
+  let (vertices, indices) = optimize_vertex_fetch(&welded.vertices, &indices);
+
+  let stats = OptimizationStats {
+    vertices_before: mesh.vertices.len(),
+    vertices_after: vertices.len(),
+    acmr_before: acmr_before,
+    acmr_after: if is_triangles { Some(acmr(&indices, CACHE_SIZE)) } else { None }
+  };
+
+  (MeshData::new(mesh.mode, vertices, Some(indices)), stats)
+}
+
+fn is_triangle_list(mode: Mode) -> bool {
+  match mode {
+    Mode::Triangle => true,
+    _ => false
+  }
+}
+
+/// Average cache miss ratio of a triangle list, simulating a FIFO cache of `cache_size` vertices.
+pub fn acmr(indices: &[u32], cache_size: usize) -> f32 {
+  let triangles = indices.len() / 3;
+
+  if triangles == 0 {
+    return 0.;
+  }
+
+  let mut cache: Vec<u32> = Vec::with_capacity(cache_size);
+  let mut next = 0; // slot replaced on the next miss
+  let mut misses = 0;
+
+  for &index in &indices[..triangles * 3] {
+    if !cache.contains(&index) {
+      misses += 1;
+
+      if cache.len() < cache_size {
+        cache.push(index);
+      } else {
+        cache[next] = index;
+        next = (next + 1) % cache_size;
+      }
+    }
+  }
+
+  misses as f32 / triangles as f32
+}
+
+/// Merge the vertices which all attributes are within `tolerance` of each other. Lines and
+/// triangles that collapse are removed.
+pub fn weld(mesh: &MeshData<Vertex>, tolerance: f32) -> MeshData<Vertex> {
+  let mut vertices: Vec<Vertex> = Vec::new();
+  let mut remap = Vec::with_capacity(mesh.vertices.len());
+
+  if tolerance > 0. {
+    // vertices by position cell; similar vertices are in the same cell or a neighbor one
+    let mut cells: HashMap<[i64; 3], Vec<u32>> = HashMap::new();
+
+    for vertex in &mesh.vertices {
+      let cell = cell_of(vertex.0, tolerance);
+      let mut found = None;
+
+      'search: for dx in -1..2 {
+        for dy in -1..2 {
+          for dz in -1..2 {
+            if let Some(candidates) = cells.get(&[cell[0] + dx, cell[1] + dy, cell[2] + dz]) {
+              if let Some(&candidate) = candidates.iter().find(|&&c| is_close(&vertices[c as usize], vertex, tolerance)) {
+                found = Some(candidate);
+                break 'search;
+              }
+            }
+          }
+        }
+      }
+
+      let index = match found {
+        Some(index) => index,
+        None => {
+          vertices.push(*vertex);
+          let index = vertices.len() as u32 - 1;
+          cells.entry(cell).or_insert_with(Vec::new).push(index);
+          index
+        }
+      };
+
+      remap.push(index);
+    }
+  } else {
+    let mut seen: HashMap<[u32; 8], u32> = HashMap::new();
+
+    for vertex in &mesh.vertices {
+      let index = *seen.entry(vertex_bits(vertex)).or_insert_with(|| {
+        vertices.push(*vertex);
+        vertices.len() as u32 - 1
+      });
+
+      remap.push(index);
+    }
+  }
+
+  // strips, fans and points keep all their indices
+  let arity = match mesh.mode {
+    Mode::Line => 2,
+    Mode::Triangle => 3,
+    _ => 1
+  };
+
+  let indices = mesh.indices();
+  let mut welded = Vec::with_capacity(indices.len());
+
+  for primitive in indices.chunks(arity) {
+    let primitive: Vec<u32> = primitive.iter().map(|&i| remap[i as usize]).collect();
+    let collapsed = arity > 1 && (0..primitive.len()).any(|i| primitive[i] == primitive[(i + 1) % primitive.len()]);
+
+    if !collapsed {
+      welded.extend(primitive);
+    }
+  }
+
+  MeshData::new(mesh.mode, vertices, Some(welded))
+}
+
+fn cell_of(p: [f32; 3], size: f32) -> [i64; 3] {
+  [(p[0] / size).floor() as i64, (p[1] / size).floor() as i64, (p[2] / size).floor() as i64]
+}
+
+fn is_close(a: &Vertex, b: &Vertex, tolerance: f32) -> bool {
+  (0..3).all(|i| (a.0[i] - b.0[i]).abs() <= tolerance && (a.1[i] - b.1[i]).abs() <= tolerance) &&
+    (0..2).all(|i| (a.2[i] - b.2[i]).abs() <= tolerance)
+}
+
+fn vertex_bits(v: &Vertex) -> [u32; 8] {
+  let bits = |x: f32| -> u32 {
+    // 0 and -0 are the same vertex
+    if x == 0. { 0 } else { x.to_bits() }
+  };
+
+  [bits(v.0[0]), bits(v.0[1]), bits(v.0[2]), bits(v.1[0]), bits(v.1[1]), bits(v.1[2]), bits(v.2[0]), bits(v.2[1])]
+}
+
+/// Reorder the triangles of a triangle list to make the most of the vertex cache.
+pub fn optimize_vertex_cache(indices: &[u32], vertex_count: usize) -> Vec<u32> {
+  let triangle_count = indices.len() / 3;
+
+  if triangle_count == 0 {
+    return Vec::new();
+  }
+
+  // triangles using each vertex, not emitted yet
+  let mut vertex_triangles: Vec<Vec<usize>> = vec![Vec::new(); vertex_count];
+
+  for t in 0..triangle_count {
+    for k in 0..3 {
+      vertex_triangles[indices[t * 3 + k] as usize].push(t);
+    }
+  }
+
+  let mut vertex_scores: Vec<f32> = (0..vertex_count).map(|v| vertex_score(None, vertex_triangles[v].len())).collect();
+  let mut triangle_scores: Vec<f32> = (0..triangle_count).map(|t| {
+    (0..3).map(|k| vertex_scores[indices[t * 3 + k] as usize]).sum()
+  }).collect();
+  let mut emitted = vec![false; triangle_count];
+  let mut cache: Vec<u32> = Vec::with_capacity(CACHE_SIZE + 3);
+  let mut output = Vec::with_capacity(triangle_count * 3);
+  let mut cursor = 0; // next triangle to look at when the cache gives no candidate
+
+  let mut best = (0..triangle_count).fold(0, |best, t| if triangle_scores[t] > triangle_scores[best] { t } else { best });
+
+  for _ in 0..triangle_count {
+    let triangle = [indices[best * 3], indices[best * 3 + 1], indices[best * 3 + 2]];
+
+    emitted[best] = true;
+    output.extend_from_slice(&triangle);
+
+    for &v in &triangle {
+      vertex_triangles[v as usize].retain(|&t| t != best);
+    }
+
+    // the vertices of the triangle move to the front of the cache
+    let mut new_cache: Vec<u32> = Vec::with_capacity(CACHE_SIZE + 3);
+
+    for &v in triangle.iter().chain(&cache) {
+      if !new_cache.contains(&v) {
+        new_cache.push(v);
+      }
+    }
+
+    let evicted: Vec<u32> = if new_cache.len() > CACHE_SIZE { new_cache.split_off(CACHE_SIZE) } else { Vec::new() };
+
+    for &v in &evicted {
+      vertex_scores[v as usize] = vertex_score(None, vertex_triangles[v as usize].len());
+    }
+
+    for (position, &v) in new_cache.iter().enumerate() {
+      vertex_scores[v as usize] = vertex_score(Some(position), vertex_triangles[v as usize].len());
+    }
+
+    cache = new_cache;
+
+    // rescore the triangles touched by the cache and pick the best one
+    let mut candidate: Option<usize> = None;
+
+    for &v in cache.iter().chain(&evicted) {
+      for &t in &vertex_triangles[v as usize] {
+        if emitted[t] {
+          continue;
+        }
+
+        triangle_scores[t] = (0..3).map(|k| vertex_scores[indices[t * 3 + k] as usize]).sum();
+
+        if candidate.map_or(true, |c| triangle_scores[t] > triangle_scores[c]) {
+          candidate = Some(t);
+        }
+      }
+    }
+
+    best = match candidate {
+      Some(t) => t,
+      None => {
+        while cursor < triangle_count && emitted[cursor] {
+          cursor += 1;
+        }
+
+        if cursor == triangle_count {
+          break;
+        }
+
+        cursor
+      }
+    };
+  }
+
+  output
+}
+
+// Score of a vertex given its position in the cache and its number of remaining triangles.
+fn vertex_score(cache_position: Option<usize>, remaining: usize) -> f32 {
+  if remaining == 0 {
+    return -1.;
+  }
+
+  let cache_score = match cache_position {
+    None => 0.,
+    Some(position) if position < 3 => LAST_TRIANGLE_SCORE,
+    Some(position) => {
+      let x = 1. - (position - 3) as f32 / (CACHE_SIZE - 3) as f32;
+      x.powf(CACHE_DECAY_POWER)
+    }
+  };
+
+  cache_score + VALENCE_BOOST_SCALE * (remaining as f32).powf(-VALENCE_BOOST_POWER)
+}
+
+/// Reorder vertices in the order the indices fetch them. Unused vertices are dropped.
+pub fn optimize_vertex_fetch<V>(vertices: &[V], indices: &[u32]) -> (Vec<V>, Vec<u32>) where V: Clone {
+  let mut remap: Vec<Option<u32>> = vec![None; vertices.len()];
+  let mut new_vertices = Vec::with_capacity(vertices.len());
+
+  let new_indices = indices.iter().map(|&i| {
+    match remap[i as usize] {
+      Some(j) => j,
+      None => {
+        let j = new_vertices.len() as u32;
+        new_vertices.push(vertices[i as usize].clone());
+        remap[i as usize] = Some(j);
+        j
+      }
+    }
+  }).collect();
+
+  (new_vertices, new_indices)
+}
@@ -0,0 +1,334 @@
+//! Parametric shapes.
+//!
+//! Every shape has normals and texture coordinates, and is centered on the origin. Shapes of
+//! revolution turn around the Y axis, their texture coordinates going around it along U. Counts
+//! below their minimum are raised to it.
+//!
+//! Shapes are also available as built-in models – see `builtin_mesh`.
+
+use luminance::Mode;
+use std::collections::HashMap;
+use std::f32::consts::PI;
+
+use mesh::MeshData;
+use mesh::cube::new_cube;
+use mesh::vector::{add, normalize, scale};
+use model::Vertex;
+use resource::LoadError;
+
+/// Highest count of a built-in model, so that a name can’t ask for billions of triangles.
+pub const MAX_BUILTIN_COUNT: u32 = 1024;
+
+/// Highest number of subdivisions of a built-in icosphere.
+pub const MAX_BUILTIN_SUBDIVISIONS: u32 = 8;
+
+/// A UV sphere made of `sectors` slices around the Y axis and `stacks` slices along it.
+pub fn new_uv_sphere(radius: f32, sectors: u32, stacks: u32) -> MeshData<Vertex> {
+  let (sectors, stacks) = (sectors.max(3), stacks.max(2));
+  let mut vertices = Vec::with_capacity(((sectors + 1) * (stacks + 1)) as usize);
+
+  for i in 0..stacks + 1 {
+    let phi = PI * i as f32 / stacks as f32;
+
+    for j in 0..sectors + 1 {
+      let n = around(phi, j, sectors);
+      vertices.push((scale(n, radius), n, [j as f32 / sectors as f32, 1. - i as f32 / stacks as f32]));
+    }
+  }
+
+  // the triangles touching the poles would be degenerate
+  let indices = band_indices(stacks + 1, sectors, true, true);
+
+  MeshData::new(Mode::Triangle, vertices, Some(indices))
+}
+
+/// A sphere made of an icosahedron which triangles are split in four `subdivisions` times.
+///
+/// Texture coordinates are spherical and wrap around without any seam vertices, so they are
+/// stretched along one meridian.
+pub fn new_icosphere(radius: f32, subdivisions: u32) -> MeshData<Vertex> {
+  let t = (1. + (5. as f32).sqrt()) * 0.5;
+  let mut points: Vec<[f32; 3]> = [
+    [-1., t, 0.], [1., t, 0.], [-1., -t, 0.], [1., -t, 0.],
+    [0., -1., t], [0., 1., t], [0., -1., -t], [0., 1., -t],
+    [t, 0., -1.], [t, 0., 1.], [-t, 0., -1.], [-t, 0., 1.]
+  ].iter().map(|&p| normalize(p)).collect();
+
+  let mut indices: Vec<u32> = vec![
+    0, 11, 5, 0, 5, 1, 0, 1, 7, 0, 7, 10, 0, 10, 11,
+    1, 5, 9, 5, 11, 4, 11, 10, 2, 10, 7, 6, 7, 1, 8,
+    3, 9, 4, 3, 4, 2, 3, 2, 6, 3, 6, 8, 3, 8, 9,
+    4, 9, 5, 2, 4, 11, 6, 2, 10, 8, 6, 7, 9, 8, 1
+  ];
+
+  for _ in 0..subdivisions {
+    // middle of each edge, shared by the two triangles of the edge
+    let mut middles: HashMap<(u32, u32), u32> = HashMap::new();
+    let mut subdivided = Vec::with_capacity(indices.len() * 4);
+
+    for triangle in indices.chunks(3) {
+      let mut middle = |a: u32, b: u32| -> u32 {
+        let key = (a.min(b), a.max(b));
+
+        *middles.entry(key).or_insert_with(|| {
+          let (pa, pb) = (points[a as usize], points[b as usize]);
+          points.push(normalize([pa[0] + pb[0], pa[1] + pb[1], pa[2] + pb[2]]));
+          points.len() as u32 - 1
+        })
+      };
+
+      let (a, b, c) = (triangle[0], triangle[1], triangle[2]);
+      let (ab, bc, ca) = (middle(a, b), middle(b, c), middle(c, a));
+
+      subdivided.extend_from_slice(&[a, ab, ca, b, bc, ab, c, ca, bc, ab, bc, ca]);
+    }
+
+    indices = subdivided;
+  }
+
+  let vertices = points.into_iter().map(|n| {
+    let uv = [0.5 + n[0].atan2(n[2]) / (2. * PI), 0.5 + n[1].max(-1.).min(1.).asin() / PI];
+    (scale(n, radius), n, uv)
+  }).collect();
+
+  MeshData::new(Mode::Triangle, vertices, Some(indices))
+}
+
+/// A torus which tube of radius `minor_radius` turns around the Y axis at `major_radius`, made of
+/// `rings` slices around the Y axis and `sides` slices around the tube.
+pub fn new_torus(minor_radius: f32, major_radius: f32, rings: u32, sides: u32) -> MeshData<Vertex> {
+  let (rings, sides) = (rings.max(3), sides.max(3));
+  let mut vertices = Vec::with_capacity(((rings + 1) * (sides + 1)) as usize);
+
+  for i in 0..rings + 1 {
+    let theta = 2. * PI * i as f32 / rings as f32;
+    let center = [major_radius * theta.sin(), 0., major_radius * theta.cos()];
+
+    for j in 0..sides + 1 {
+      let phi = 2. * PI * j as f32 / sides as f32;
+      let n = [phi.cos() * theta.sin(), phi.sin(), phi.cos() * theta.cos()];
+
+      vertices.push((add(center, scale(n, minor_radius)), n, [i as f32 / rings as f32, j as f32 / sides as f32]));
+    }
+  }
+
+  MeshData::new(Mode::Triangle, vertices, Some(band_indices(rings + 1, sides, false, false)))
+}
+
+/// A closed cylinder of height `height` along the Y axis, made of `segments` slices around it.
+pub fn new_cylinder(radius: f32, height: f32, segments: u32) -> MeshData<Vertex> {
+  let segments = segments.max(3);
+  let mut vertices = Vec::new();
+
+  for (i, &y) in [height * 0.5, -height * 0.5].iter().enumerate() {
+    for j in 0..segments + 1 {
+      let n = around(PI * 0.5, j, segments);
+      vertices.push(([radius * n[0], y, radius * n[2]], n, [j as f32 / segments as f32, 1. - i as f32]));
+    }
+  }
+
+  let mut indices = band_indices(2, segments, false, false);
+  add_cap(&mut vertices, &mut indices, radius, height * 0.5, segments, true);
+  add_cap(&mut vertices, &mut indices, radius, -height * 0.5, segments, false);
+
+  MeshData::new(Mode::Triangle, vertices, Some(indices))
+}
+
+/// A closed cone of height `height` along the Y axis, pointing up, made of `segments` slices around
+/// it.
+pub fn new_cone(radius: f32, height: f32, segments: u32) -> MeshData<Vertex> {
+  let segments = segments.max(3);
+  let slant = (radius * radius + height * height).sqrt();
+  let normal = |theta: f32| [height * theta.sin() / slant, radius / slant, height * theta.cos() / slant];
+  let mut vertices = Vec::new();
+
+  // an apex per slice, so that each slice gets its own normal there
+  for j in 0..segments {
+    let u = (j as f32 + 0.5) / segments as f32;
+    vertices.push(([0., height * 0.5, 0.], normal(2. * PI * u), [u, 1.]));
+  }
+
+  for j in 0..segments + 1 {
+    let theta = 2. * PI * j as f32 / segments as f32;
+    vertices.push(([radius * theta.sin(), -height * 0.5, radius * theta.cos()], normal(theta), [j as f32 / segments as f32, 0.]));
+  }
+
+  let mut indices: Vec<u32> = (0..segments).flat_map(|j| vec![j, segments + j, segments + j + 1]).collect();
+  add_cap(&mut vertices, &mut indices, radius, -height * 0.5, segments, false);
+
+  MeshData::new(Mode::Triangle, vertices, Some(indices))
+}
+
+/// A capsule: a cylinder of height `height` along the Y axis capped by hemispheres. It’s made of
+/// `segments` slices around the Y axis and `rings` slices along each hemisphere.
+pub fn new_capsule(radius: f32, height: f32, segments: u32, rings: u32) -> MeshData<Vertex> {
+  let (segments, rings) = (segments.max(3), rings.max(1));
+  let length = PI * radius + height;
+  let mut vertices = Vec::with_capacity((2 * (rings + 1) * (segments + 1)) as usize);
+
+  // the equator is duplicated, the cylinder joining both hemispheres
+  for half in 0..2 {
+    let y = if half == 0 { height * 0.5 } else { -height * 0.5 };
+
+    for i in 0..rings + 1 {
+      let phi = PI * 0.5 * (half as f32 + i as f32 / rings as f32);
+      let v = 1. - (radius * phi + half as f32 * height) / length;
+
+      for j in 0..segments + 1 {
+        let n = around(phi, j, segments);
+        vertices.push(([radius * n[0], y + radius * n[1], radius * n[2]], n, [j as f32 / segments as f32, v]));
+      }
+    }
+  }
+
+  MeshData::new(Mode::Triangle, vertices, Some(band_indices(2 * (rings + 1), segments, true, true)))
+}
+
+/// A grid of `width` along X by `depth` along Z facing up, made of `columns` by `rows` quads.
+pub fn new_grid(width: f32, depth: f32, columns: u32, rows: u32) -> MeshData<Vertex> {
+  let (columns, rows) = (columns.max(1), rows.max(1));
+  let mut vertices = Vec::with_capacity(((columns + 1) * (rows + 1)) as usize);
+
+  for i in 0..rows + 1 {
+    let v = i as f32 / rows as f32;
+
+    for j in 0..columns + 1 {
+      let u = j as f32 / columns as f32;
+      vertices.push(([(u - 0.5) * width, 0., (v - 0.5) * depth], [0., 1., 0.], [u, v]));
+    }
+  }
+
+  MeshData::new(Mode::Triangle, vertices, Some(band_indices(rows + 1, columns, false, false)))
+}
+
+/// Mesh of a built-in model – the name following `builtin:`, such as `torus?r=1&R=3`.
+///
+/// Available shapes and their parameters, with their default values:
+///
+/// - `cube`: the unit cube – see `new_cube`
+/// - `sphere?r=1&sectors=32&stacks=16`
+/// - `icosphere?r=1&subdivisions=2`
+/// - `torus?r=0.25&R=1&rings=32&sides=16`
+/// - `cylinder?r=1&h=2&segments=32`
+/// - `cone?r=1&h=2&segments=32`
+/// - `capsule?r=0.5&h=1&segments=32&rings=8`
+/// - `grid?w=2&d=2&columns=1&rows=1`
+///
+/// Counts above `MAX_BUILTIN_COUNT` are lowered to it, and subdivisions above
+/// `MAX_BUILTIN_SUBDIVISIONS`.
+pub fn builtin_mesh(name: &str) -> Result<MeshData<Vertex>, LoadError> {
+  let (shape, query) = match name.find('?') {
+    Some(i) => (&name[..i], &name[i + 1..]),
+    None => (name, "")
+  };
+
+  let mut params = Params::parse(name, query)?;
+
+  let mesh = match shape {
+    "cube" => new_cube(),
+    "sphere" => new_uv_sphere(params.float("r", 1.), params.count("sectors", 32)?, params.count("stacks", 16)?),
+    "icosphere" => new_icosphere(params.float("r", 1.), params.count("subdivisions", 2)?.min(MAX_BUILTIN_SUBDIVISIONS)),
+    "torus" => new_torus(params.float("r", 0.25), params.float("R", 1.), params.count("rings", 32)?, params.count("sides", 16)?),
+    "cylinder" => new_cylinder(params.float("r", 1.), params.float("h", 2.), params.count("segments", 32)?),
+    "cone" => new_cone(params.float("r", 1.), params.float("h", 2.), params.count("segments", 32)?),
+    "capsule" => new_capsule(params.float("r", 0.5), params.float("h", 1.), params.count("segments", 32)?, params.count("rings", 8)?),
+    "grid" => new_grid(params.float("w", 2.), params.float("d", 2.), params.count("columns", 1)?, params.count("rows", 1)?),
+    _ => return Err(LoadError::ConversionFailed(format!("unknown built-in model {}", shape)))
+  };
+
+  // typos would go unnoticed otherwise
+  match params.values.keys().next() {
+    Some(key) => Err(LoadError::ParseFailed(format!("unknown parameter {} of built-in model {}", key, name))),
+    None => Ok(mesh)
+  }
+}
+
+// Parameters of a built-in model. Parameters are removed as they are read.
+struct Params {
+  values: HashMap<String, f32>
+}
+
+impl Params {
+  fn parse(name: &str, query: &str) -> Result<Self, LoadError> {
+    let mut values = HashMap::new();
+
+    for param in query.split('&').filter(|param| !param.is_empty()) {
+      let mut parts = param.splitn(2, '=');
+      let key = parts.next().unwrap_or("");
+      let value = parts.next().and_then(|value| value.parse().ok())
+        .ok_or(LoadError::ParseFailed(format!("invalid parameter {} of built-in model {}", param, name)))?;
+
+      values.insert(key.to_owned(), value);
+    }
+
+    Ok(Params {
+      values: values
+    })
+  }
+
+  fn float(&mut self, key: &str, default: f32) -> f32 {
+    self.values.remove(key).unwrap_or(default)
+  }
+
+  fn count(&mut self, key: &str, default: u32) -> Result<u32, LoadError> {
+    match self.values.remove(key) {
+      Some(value) if value >= 0. && value.fract() == 0. => Ok(value.min(MAX_BUILTIN_COUNT as f32) as u32),
+      Some(value) => Err(LoadError::ParseFailed(format!("{} must be a natural number; got {}", key, value))),
+      None => Ok(default)
+    }
+  }
+}
+
+// Unit vector at polar angle `phi` from the Y axis and at slice `j` of `slices` around it.
+fn around(phi: f32, j: u32, slices: u32) -> [f32; 3] {
+  let theta = 2. * PI * j as f32 / slices as f32;
+  [phi.sin() * theta.sin(), phi.cos(), phi.sin() * theta.cos()]
+}
+
+// Triangles joining consecutive rows of `slices + 1` vertices. Skipping the first or last band
+// drops the triangles that would be degenerate at a pole.
+fn band_indices(rows: u32, slices: u32, skip_first: bool, skip_last: bool) -> Vec<u32> {
+  let stride = slices + 1;
+  let mut indices = Vec::with_capacity((rows.saturating_sub(1) * slices * 6) as usize);
+
+  for i in 0..rows.saturating_sub(1) {
+    for j in 0..slices {
+      let a = i * stride + j;
+      let (b, c, d) = (a + 1, a + stride, a + stride + 1);
+
+      if !(skip_first && i == 0) {
+        indices.extend_from_slice(&[a, c, b]);
+      }
+
+      if !(skip_last && i == rows - 2) {
+        indices.extend_from_slice(&[b, c, d]);
+      }
+    }
+  }
+
+  indices
+}
+
+// Add a disc at height `y` facing up or down.
+fn add_cap(vertices: &mut Vec<Vertex>, indices: &mut Vec<u32>, radius: f32, y: f32, segments: u32, up: bool) {
+  let center = vertices.len() as u32;
+  let n = if up { [0., 1., 0.] } else { [0., -1., 0.] };
+
+  vertices.push(([0., y, 0.], n, [0.5, 0.5]));
+
+  for j in 0..segments + 1 {
+    let theta = 2. * PI * j as f32 / segments as f32;
+    let (sin, cos) = (theta.sin(), theta.cos());
+    vertices.push(([radius * sin, y, radius * cos], n, [0.5 + 0.5 * sin, 0.5 + 0.5 * cos]));
+  }
+
+  for j in 0..segments {
+    let (a, b) = (center + 1 + j, center + 2 + j);
+
+    if up {
+      indices.extend_from_slice(&[center, a, b]);
+    } else {
+      indices.extend_from_slice(&[center, b, a]);
+    }
+  }
+}
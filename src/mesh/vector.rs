@@ -0,0 +1,36 @@
+//! Arithmetic on raw vertex attributes.
+//!
+//! Meshes store their attributes as plain arrays; these helpers spare converting them back and
+//! forth to `linear` types when generating or processing meshes.
+
+pub fn add(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+  [a[0] + b[0], a[1] + b[1], a[2] + b[2]]
+}
+
+pub fn sub(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+  [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+pub fn scale(a: [f32; 3], k: f32) -> [f32; 3] {
+  [a[0] * k, a[1] * k, a[2] * k]
+}
+
+pub fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+  a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+pub fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+  [a[1] * b[2] - a[2] * b[1], a[2] * b[0] - a[0] * b[2], a[0] * b[1] - a[1] * b[0]]
+}
+
+/// Normalize a vector. Null vectors – such as the normal of a degenerate triangle – stay null
+/// instead of becoming NaN.
+pub fn normalize(a: [f32; 3]) -> [f32; 3] {
+  let len = dot(a, a).sqrt();
+
+  if len > 0. {
+    scale(a, 1. / len)
+  } else {
+    [0., 0., 0.]
+  }
+}
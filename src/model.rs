@@ -1,30 +1,108 @@
-use luminance::tess;
+use luminance::{Sampler, tess};
 use luminance_gl::gl33::Tess;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fs::File;
 use std::io::Read;
 use std::iter::IntoIterator;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
 use std::vec;
 use wavefront_obj::obj;
 
-use resource::{Cache, Load, LoadError};
+use gltf::{self, Animation, Node};
+use id::Id;
+use material::{Material, MaterialLibrary};
+use mesh::{Bounds, MeshData};
+use mesh::{binary, optimize};
+use mesh::shapes::builtin_mesh;
+use mesh::vector::{add, cross, dot, normalize, scale, sub};
+use morph::MorphMesh;
+use resource::{Cache, Get, Load, LoadError, Reload};
+use skeleton::Skeleton;
+use texture::TextureImage;
 
 pub type Vertex = (VertexPos, VertexNor, VertexTexCoord);
 pub type VertexPos = [f32; 3];
 pub type VertexNor = [f32; 3];
 pub type VertexTexCoord = [f32; 2];
-
+/// Vertex of the extended layout, with a tangent.
+pub type TangentVertex = (VertexPos, VertexNor, VertexTexCoord, VertexTangent);
+/// Tangent along with the sign of the bitangent: `bitangent = w * cross(normal, tangent)`.
+pub type VertexTangent = [f32; 4];
+/// Vertex of the skinned layout: a `TangentVertex` with the joints moving it and their weights.
+pub type SkinnedVertex = (VertexPos, VertexNor, VertexTexCoord, VertexTangent, VertexJoints, VertexWeights);
+/// Indices of the joints moving a vertex in its skeleton.
+pub type VertexJoints = [u32; 4];
+/// Weights of the joints moving a vertex. Null weights leave the vertex in place.
+pub type VertexWeights = [f32; 4];
+/// Vertex of the morph layout: a `SkinnedVertex` with the position and normal displacements of two
+/// morph targets.
+pub type MorphVertex = (VertexPos, VertexNor, VertexTexCoord, VertexTangent, VertexJoints, VertexWeights, VertexPos,
+                        VertexNor, VertexPos, VertexNor);
+
+/// A model.
+///
+/// If the model is retrieved from the cache, the path must point to a Wavefront `.obj` file, to a
+/// binary model – `.smesh`, see `mesh::binary` – or to a glTF 2.0 file – `.gltf` or `.glb`.
+///
+/// The material library – `mtllib` – of an `.obj` file is read from the directory of the file and
+/// the texture maps of the materials are retrieved from the cache – see `Material`. Editing the
+/// `.obj`, the `.mtl` or any of the textures reloads the model. Vertices don’t need normals nor
+/// texture coordinates. Missing normals are generated according to `ModelArgs::normals`; missing
+/// texture coordinates are set to zero. Geometries mixing points, lines and triangles are split into
+/// a part per kind of primitive.
+///
+/// glTF materials, buffers and images are read from the file or next to it, and editing any of them
+/// reloads the model as well – see the `gltf` module. Such a model also holds the node hierarchy,
+/// the animations and the skins of its file.
+///
+/// Names starting with `builtin:` – such as `builtin:torus?r=1&R=3` – refer to generated shapes
+/// instead of files; see `mesh::shapes::builtin_mesh` for the list. They have a single part, no
+/// material, and never reload.
 pub struct Model {
-  pub parts: Vec<Part>
+  pub parts: Vec<Part>,
+  pub materials: Vec<Material>,
+  /// Nodes of a glTF model.
+  pub nodes: Vec<Node>,
+  /// Indices of the root nodes of a glTF model.
+  pub roots: Vec<usize>,
+  /// Animations of the nodes of a glTF model.
+  pub animations: Vec<Animation>,
+  /// Skeletons of a glTF model – see `Node::skin`.
+  pub skins: Vec<Skeleton>,
+  // texture maps of the materials, by name
+  textures: HashMap<String, Rc<TextureImage>>,
+  args: ModelArgs
 }
 
 impl Model {
   pub fn from_parts(parts: Vec<Part>) -> Self {
+    Model::new(parts, Vec::new(), HashMap::new())
+  }
+
+  /// Create a model out of parts, their materials and the texture maps of the materials by name.
+  pub fn new(parts: Vec<Part>, materials: Vec<Material>, textures: HashMap<String, Rc<TextureImage>>) -> Self {
     Model {
-      parts: parts
+      parts: parts,
+      materials: materials,
+      nodes: Vec::new(),
+      roots: Vec::new(),
+      animations: Vec::new(),
+      skins: Vec::new(),
+      textures: textures,
+      args: ModelArgs::default()
     }
   }
+
+  /// Material of a part, if any.
+  pub fn material(&self, part: &Part) -> Option<&Material> {
+    part.material.and_then(|i| self.materials.get(i))
+  }
+
+  /// Texture map of a material by its name – see the `*_map` fields of `Material`.
+  pub fn texture(&self, map: &str) -> Option<&TextureImage> {
+    self.textures.get(map).map(|texture| &**texture)
+  }
 }
 
 impl IntoIterator for Model {
@@ -38,25 +116,46 @@ impl IntoIterator for Model {
 
 pub struct Part {
   pub tess: Tess,
-  // TODO: add material index
+  /// Index of the material of the part in its model.
+  pub material: Option<usize>,
+  /// Mesh and morph targets of the part, if it has any – see the `morph` module.
+  pub morph: Option<MorphMesh>
 }
 
 impl Part {
   pub fn new(tess: Tess) -> Self {
+    Part::with_material(tess, None)
+  }
+
+  pub fn with_material(tess: Tess, material: Option<usize>) -> Self {
     Part {
       tess: tess,
+      material: material,
+      morph: None
+    }
+  }
+
+  /// Blend the morph targets of the part on the CPU with the given weights and upload the result in
+  /// place of its tessellation. Parts without morph targets are left untouched.
+  pub fn blend(&mut self, weights: &[f32], layout: VertexLayout) {
+    if let Some(ref morph) = self.morph {
+      self.tess = morph.upload_blended(weights, layout);
     }
   }
 }
 
-impl<'a> Load<'a> for Model {
-  type Args = ();
+/// CPU-side model: meshes along with the index of their material, and the materials.
+#[derive(Clone, Debug)]
+pub struct ModelData {
+  pub parts: Vec<(MeshData<Vertex>, Option<usize>)>,
+  pub materials: Vec<Material>
+}
 
-  fn load<P>(path: P, _: &mut Cache<'a>, _: Self::Args) -> Result<Self, LoadError> where P: AsRef<Path> {
+impl ModelData {
+  /// Read a Wavefront `.obj` file and its material library, if any. Return the model along with the
+  /// path of the library.
+  pub fn from_obj<P>(path: P, normals: Normals) -> Result<(Self, Option<PathBuf>), LoadError> where P: AsRef<Path> {
     let path = path.as_ref();
-
-    info!("loading model: {:?}", path);
-
     let mut input = String::new();
 
     // load the data directly into memory; no buffering nor streaming
@@ -68,105 +167,676 @@ impl<'a> Load<'a> for Model {
     // parse the obj file and convert it
     let obj_set = obj::parse(input).map_err(|e| LoadError::ParseFailed(format!("{:?}", e)))?;
 
-    convert_obj(obj_set).map_err(|e| LoadError::ConversionFailed(format!("{:?}", e)))
+    // read the materials
+    let (library, mtl_path) = match obj_set.material_library {
+      Some(ref mtllib) => {
+        let mtl_path = path.parent().unwrap_or(Path::new("")).join(mtllib);
+        let mut src = String::new();
+        let mut file = File::open(&mtl_path).map_err(|e| LoadError::FileNotFound(mtl_path.clone(), format!("{:?}", e)))?;
+        file.read_to_string(&mut src).map_err(|e| LoadError::ParseFailed(format!("{:?}", e)))?;
+
+        (MaterialLibrary::parse(&src)?, Some(mtl_path))
+      },
+      None => (MaterialLibrary::default(), None)
+    };
+
+    let parts = convert_obj(&obj_set, &library, normals).map_err(|e| LoadError::ConversionFailed(format!("{:?}", e)))?;
+
+    let data = ModelData {
+      parts: parts,
+      materials: library.into_iter().collect()
+    };
+
+    Ok((data, mtl_path))
+  }
+
+  /// Bounds of all the parts.
+  pub fn bounds(&self) -> Bounds {
+    let mut bounds = Bounds::empty();
+
+    for &(ref mesh, _) in &self.parts {
+      if !mesh.bounds.is_empty() {
+        bounds.insert(mesh.bounds.min);
+        bounds.insert(mesh.bounds.max);
+      }
+    }
+
+    bounds
+  }
+}
+
+/// How normals are generated for vertices that don’t have any.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Normals {
+  /// Each face gets its own normal, giving a faceted look.
+  Flat,
+  /// Normals are averaged over all the faces sharing a vertex.
+  Smooth,
+  /// Normals are averaged over the faces sharing a vertex that make an angle lower than or equal to
+  /// the given one – in degrees – with the face being shaded. Sharper edges stay sharp.
+  Angle(f32)
+}
+
+impl Default for Normals {
+  fn default() -> Self {
+    Normals::Smooth
+  }
+}
+
+/// Layout of the vertices of a `Model`.
+///
+/// Attributes are bound in order, each layout extending the previous one: the position at location
+/// `0`, the normal at `1`, the texture coordinates at `2`, the tangent at `3`, the joints and
+/// weights at `4` and `5` and the displacements of the morph targets at `6` to `9` – position then
+/// normal of each target. Every attribute keeps its location whatever the layout, so shaders can
+/// combine normal mapping, skinning and morphing.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum VertexLayout {
+  /// `Vertex`.
+  Basic,
+  /// `TangentVertex`, for normal mapping. Tangents are generated with `generate_tangents`.
+  Tangent,
+  /// `SkinnedVertex`, for skeletal animation – see the `skeleton` module. Only glTF skins provide
+  /// joints and weights; other meshes get null weights.
+  Skinned,
+  /// `MorphVertex`, for morphing on the GPU – see the `morph` module. Only glTF morph targets are
+  /// uploaded; other meshes get null displacements.
+  Morph
+}
+
+impl Default for VertexLayout {
+  fn default() -> Self {
+    VertexLayout::Basic
+  }
+}
+
+/// Arguments used to load a `Model`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ModelArgs {
+  /// How missing normals are generated.
+  pub normals: Normals,
+  /// Layout of the vertices.
+  pub layout: VertexLayout,
+  /// Mesh of a glTF file to load. If `None`, all the meshes of the scene of the file are loaded,
+  /// with their node transforms applied.
+  pub mesh: Option<usize>,
+  /// Load the source `.obj` file of a binary model instead of the binary model when the source is
+  /// newer. Editing the source then reloads the model.
+  pub source_fallback: bool,
+  /// Optimize the meshes once loaded, welding the vertices which attributes are within the given
+  /// tolerance – see `mesh::optimize`.
+  pub optimize: Option<f32>
+}
+
+impl ModelArgs {
+  pub fn new(normals: Normals) -> Self {
+    ModelArgs {
+      normals: normals,
+      layout: VertexLayout::Basic,
+      mesh: None,
+      source_fallback: false,
+      optimize: None
+    }
+  }
+
+  /// Use the given vertex layout.
+  pub fn layout(self, layout: VertexLayout) -> Self {
+    ModelArgs {
+      layout: layout,
+      ..self
+    }
+  }
+
+  /// Fall back to the source of a binary model when it’s newer.
+  pub fn source_fallback(self) -> Self {
+    ModelArgs {
+      source_fallback: true,
+      ..self
+    }
+  }
+
+  /// Optimize the meshes, welding vertices within `tolerance` of each other.
+  pub fn optimize(self, tolerance: f32) -> Self {
+    ModelArgs {
+      optimize: Some(tolerance),
+      ..self
+    }
+  }
+
+  /// Load a single mesh of a glTF file.
+  pub fn mesh(self, mesh: usize) -> Self {
+    ModelArgs {
+      mesh: Some(mesh),
+      ..self
+    }
+  }
+}
+
+impl<'a> Load<'a> for Model {
+  type Args = ModelArgs;
+
+  fn cache_key(name: &str, args: &Self::Args) -> String {
+    let mut options = Vec::new();
+
+    match args.normals {
+      Normals::Smooth => {},
+      Normals::Flat => options.push("normals=flat".to_owned()),
+      Normals::Angle(angle) => options.push(format!("normals={}", angle))
+    }
+
+    match args.layout {
+      VertexLayout::Basic => {},
+      VertexLayout::Tangent => options.push("tangents".to_owned()),
+      VertexLayout::Skinned => options.push("skinned".to_owned()),
+      VertexLayout::Morph => options.push("morph".to_owned())
+    }
+
+    if let Some(mesh) = args.mesh {
+      options.push(format!("mesh={}", mesh));
+    }
+
+    if args.source_fallback {
+      options.push("source".to_owned());
+    }
+
+    if let Some(tolerance) = args.optimize {
+      options.push(format!("optimize={}", tolerance));
+    }
+
+    if options.is_empty() {
+      name.to_owned()
+    } else {
+      format!("{}?{}", name, options.join("&"))
+    }
+  }
+
+  fn load<P>(path: P, cache: &mut Cache<'a>, args: Self::Args) -> Result<Self, LoadError> where P: AsRef<Path> {
+    let path = path.as_ref();
+
+    info!("loading model: {:?}", path);
+
+    let mut model = match path.extension().and_then(|ext| ext.to_str()) {
+      Some("gltf") | Some("glb") => gltf::load_model(path, cache, &args)?,
+      Some(binary::EXTENSION) => load_binary(path, cache, &args)?,
+      _ => load_obj(path, cache, &args)?
+    };
+
+    model.args = args;
+
+    Ok(model)
+  }
+
+  fn builtin(name: &str, cache: &mut Cache<'a>, args: Self::Args) -> Result<Self, LoadError> {
+    info!("generating model: {}", name);
+
+    let data = ModelData {
+      parts: vec![(builtin_mesh(name)?, None)],
+      materials: Vec::new()
+    };
+
+    let mut model = upload_model(data, cache, &args);
+    model.args = args;
+
+    Ok(model)
+  }
+}
+
+impl<'a> Reload<'a> for Model {
+  fn reload_args(&self) -> Self::Args {
+    self.args
+  }
+}
+
+// Load a Wavefront .obj file along with its materials.
+fn load_obj<'a>(path: &Path, cache: &mut Cache<'a>, args: &ModelArgs) -> Result<Model, LoadError> {
+  let (data, mtl_path) = ModelData::from_obj(path, args.normals)?;
+
+  if let Some(mtl_path) = mtl_path {
+    cache.add_dependency(mtl_path);
+  }
+
+  Ok(upload_model(data, cache, args))
+}
+
+// Load a binary model. If asked to and its source is newer, the source is loaded instead.
+fn load_binary<'a>(path: &Path, cache: &mut Cache<'a>, args: &ModelArgs) -> Result<Model, LoadError> {
+  let (data, source) = binary::read_file(path)?;
+
+  if args.source_fallback {
+    if let Some(source) = source {
+      let source = path.parent().unwrap_or(Path::new("")).join(source);
+      cache.add_dependency(&source);
+
+      if is_newer(&source, path) {
+        info!("{:?} is newer than {:?}; loading it instead", source, path);
+        return load_obj(&source, cache, args);
+      }
+    }
+  }
+
+  Ok(upload_model(data, cache, args))
+}
+
+// Is a file more recent than another one? False if any of them cannot be inspected.
+fn is_newer(a: &Path, b: &Path) -> bool {
+  match (a.metadata().and_then(|m| m.modified()), b.metadata().and_then(|m| m.modified())) {
+    (Ok(a), Ok(b)) => a > b,
+    _ => false
   }
 }
 
-// Turn a wavefront obj object into a `Model`
-fn convert_obj(obj_set: obj::ObjSet) -> Result<Model, ModelError> {
-  let mut parts = Vec::new();
+// Optimize if asked to and upload the meshes of a model, and get the texture maps of its materials.
+fn upload_model<'a>(data: ModelData, cache: &mut Cache<'a>, args: &ModelArgs) -> Model {
+  let textures = load_textures(&data.materials, cache);
+  let parts = data.parts.iter().map(|&(ref mesh, material)| {
+    let tess = match args.optimize {
+      Some(tolerance) => {
+        let (mesh, stats) = optimize::optimize(mesh, tolerance);
+        info!("  optimized part: {}", stats);
+        upload_mesh(&mesh, args.layout)
+      },
+      None => upload_mesh(mesh, args.layout)
+    };
+
+    Part::with_material(tess, material)
+  }).collect();
+
+  Model::new(parts, data.materials, textures)
+}
+
+// Get the texture maps of the materials from the cache. Missing textures are skipped.
+fn load_textures<'a>(materials: &[Material], cache: &mut Cache<'a>) -> HashMap<String, Rc<TextureImage>> {
+  let mut textures = HashMap::new();
+
+  for material in materials {
+    for map in material.maps() {
+      if textures.contains_key(map) {
+        continue;
+      }
+
+      // color maps are stored gamma-encoded
+      let linear = !material.is_color_map(map);
+
+      let id: Option<Id<TextureImage>> = cache.get_id(map, (Sampler::default(), linear));
+
+      match id {
+        Some(id) => {
+          cache.add_resource_dependency::<TextureImage>(map);
+
+          if let Some(texture) = cache.get_by_id(&id) {
+            textures.insert(map.to_owned(), texture);
+          }
+        },
+        None => {
+          warn!("missing texture {} for material {}", map, material.name);
+        }
+      }
+    }
+  }
+
+  textures
+}
+
+/// Convert a parsed Wavefront file into meshes along with the index of their material in the
+/// library. Each geometry gives a mesh per kind of primitive it holds – points, lines and
+/// triangles.
+pub fn convert_obj(obj_set: &obj::ObjSet, library: &MaterialLibrary, normals: Normals) -> Result<Vec<(MeshData<Vertex>, Option<usize>)>, ModelError> {
+  let mut meshes = Vec::new();
 
   info!("{} objects to convert…", obj_set.objects.len());
   for obj in &obj_set.objects {
     info!("  converting {} geometries in object {}", obj.geometry.len(), obj.name);
 
+    let generator = NormalGenerator::new(obj, normals);
+    let mut first_shape = 0;
+
     // convert all the geometries
     for geometry in &obj.geometry {
       info!("    {} vertices, {} normals, {} tex vertices", obj.vertices.len(), obj.normals.len(), obj.tex_vertices.len());
-      let (vertices, indices, mode) = convert_geometry(geometry, &obj.vertices, &obj.normals, &obj.tex_vertices)?;
-      let part = Part::new(Tess::new(mode, &vertices, Some(&indices))); // FIXME: material
-      parts.push(part);
+      let geometry_meshes = convert_geometry(geometry, first_shape, &obj.vertices, &obj.normals, &obj.tex_vertices, &generator)?;
+      first_shape += geometry.shapes.len();
+
+      let material = geometry.material_name.as_ref().and_then(|name| {
+        let index = library.index_of(name);
+
+        if index.is_none() {
+          warn!("    unknown material {}", name);
+        }
+
+        index
+      });
+
+      for mesh in geometry_meshes {
+        meshes.push((mesh, material));
+      }
+    }
+  }
+
+  Ok(meshes)
+}
+
+/// Upload a mesh with the given vertex layout.
+pub fn upload_mesh(mesh: &MeshData<Vertex>, layout: VertexLayout) -> Tess {
+  match layout {
+    VertexLayout::Basic => mesh.upload(),
+    VertexLayout::Tangent => tangent_mesh(mesh).upload(),
+    VertexLayout::Skinned => skinned_mesh(mesh, None, None).0.upload(),
+    VertexLayout::Morph => {
+      let (skinned, _) = skinned_mesh(mesh, None, None);
+      let vertices: Vec<MorphVertex> = skinned.vertices.into_iter().map(|(p, n, uv, t, j, w)| {
+        (p, n, uv, t, j, w, [0.; 3], [0.; 3], [0.; 3], [0.; 3])
+      }).collect();
+      MeshData::new(skinned.mode, vertices, skinned.indices).upload()
+    }
+  }
+}
+
+/// Extend the vertices of a mesh with tangents – see `generate_tangents`. Points and lines have no
+/// tangent space; they get null tangents.
+pub fn tangent_mesh(mesh: &MeshData<Vertex>) -> MeshData<TangentVertex> {
+  with_tangents(mesh, None).0
+}
+
+/// Extend the vertices of a mesh with tangents and with the joints and weights moving them. Missing
+/// tangents are generated – see `tangent_mesh` – and a mesh without skin gets null weights.
+///
+/// Return the new mesh along with the index of the source vertex of each new vertex, as generating
+/// tangents splits vertices.
+pub fn skinned_mesh(mesh: &MeshData<Vertex>, tangents: Option<Vec<VertexTangent>>, skin: Option<&[(VertexJoints, VertexWeights)]>) -> (MeshData<SkinnedVertex>, Vec<u32>) {
+  let (mesh, sources) = with_tangents(mesh, tangents);
+  let vertices = mesh.vertices.iter().zip(&sources).map(|(&(p, n, uv, t), &i)| {
+    let (joints, weights) = skin.map_or(([0; 4], [0.; 4]), |skin| skin[i as usize]);
+    (p, n, uv, t, joints, weights)
+  }).collect();
+
+  (MeshData::new(mesh.mode, vertices, mesh.indices), sources)
+}
+
+// Extend the vertices of a mesh with the given tangents – one per vertex – or with generated ones.
+// The index of the source vertex of each new vertex is returned along with the new mesh.
+fn with_tangents(mesh: &MeshData<Vertex>, tangents: Option<Vec<VertexTangent>>) -> (MeshData<TangentVertex>, Vec<u32>) {
+  match (tangents, mesh.mode) {
+    (Some(tangents), _) => {
+      let vertices = mesh.vertices.iter().zip(tangents).map(|(&(p, n, uv), t)| (p, n, uv, t)).collect();
+      (MeshData::new(mesh.mode, vertices, mesh.indices.clone()), (0..mesh.vertices.len() as u32).collect())
+    },
+    (None, tess::Mode::Triangle) => {
+      let (vertices, indices, sources) = tangent_vertices(&mesh.vertices, &mesh.indices());
+      (MeshData::new(mesh.mode, vertices, Some(indices)), sources)
+    },
+    (None, _) => {
+      let vertices = mesh.vertices.iter().map(|&(p, n, uv)| (p, n, uv, [0., 0., 0., 1.])).collect();
+      (MeshData::new(mesh.mode, vertices, mesh.indices.clone()), (0..mesh.vertices.len() as u32).collect())
     }
   }
+}
 
-  Ok(Model::from_parts(parts))
+// Normal of a vertex: either one from the file or one generated by averaging the normals of a set
+// of faces, identified by their shape index in the object.
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+enum NormalKey {
+  Index(usize),
+  Faces(Vec<usize>)
 }
 
-// Convert wavefront_obj’s Geometry into a pair of vertices and indices.
+type VertexKey = (usize, NormalKey, Option<usize>);
+
+// Convert wavefront_obj’s Geometry into meshes, one per kind of primitive – points, lines and
+// triangles – in order of first appearance.
 //
 // This function will regenerate the indices on the fly based on which are used in the shapes in the
 // geometry. It’s used to create independent tessellation.
-fn convert_geometry(geo: &obj::Geometry, positions: &[obj::Vertex], normals: &[obj::Normal], tvertices: &[obj::TVertex]) -> Result<(Vec<Vertex>, Vec<u32>, tess::Mode), ModelError> {
+fn convert_geometry(geo: &obj::Geometry, first_shape: usize, positions: &[obj::Vertex], normals: &[obj::Normal], tvertices: &[obj::TVertex], generator: &NormalGenerator) -> Result<Vec<MeshData<Vertex>>, ModelError> {
   if geo.shapes.is_empty() {
     return Err(ModelError::NoShape);
   }
 
-  let mut vertices = Vec::new(); // FIXME: better allocation scheme?
-  let mut indices = Vec::new();
-  let mut index_map = BTreeMap::new();
+  // one builder per primitive kind, along with the mode of that kind
+  let mut builders: Vec<(u8, tess::Mode, GeometryBuilder)> = Vec::new();
 
   info!("    converting geometry");
 
-  let mode = guess_mode(geo.shapes[0].primitive);
+  for (i, prim) in geo.shapes.iter().map(|s| s.primitive).enumerate() {
+    let kind = primitive_kind(prim);
+    let builder = match builders.iter().position(|&(k, _, _)| k == kind) {
+      Some(b) => b,
+      None => {
+        builders.push((kind, guess_mode(prim), GeometryBuilder::new()));
+        builders.len() - 1
+      }
+    };
 
-  for prim in geo.shapes.iter().map(|s| s.primitive) {
-    let keys = create_keys_from_primitive(prim)?;
+    let builder = &mut builders[builder].2;
+    let keys = create_keys_from_primitive(prim, first_shape + i, generator);
 
     for key in keys {
-      match index_map.get(&key).map(|&i| i) {
+      match builder.index_map.get(&key).map(|&i| i) {
         Some(index) => {
           // that triplet already exists; just append the index in the indices buffer
-          indices.push(index);
+          builder.indices.push(index);
         },
         None => {
           // this is a new, not yet discovered triplet; create the corresponding vertex and add it
           // to the vertices buffer, and map the triplet to the index in the indices buffer
-          let vertex = interleave_vertex(&positions[key.0], &normals[key.1], key.2.map(|ki| &tvertices[ki]));
-          let index = vertices.len() as u32;
-
-          vertices.push(vertex);
-          indices.push(index);
-          index_map.insert(key, index);
+          let normal = match key.1 {
+            NormalKey::Index(ni) => convert_nor(&normals[ni]),
+            NormalKey::Faces(ref faces) => generator.average(faces)
+          };
+          let vertex = interleave_vertex(&positions[key.0], normal, key.2.map(|ki| &tvertices[ki]));
+          let index = builder.vertices.len() as u32;
+
+          builder.vertices.push(vertex);
+          builder.indices.push(index);
+          builder.index_map.insert(key, index);
         }
       }
     }
   }
 
-  Ok((vertices, indices, mode))
+  if builders.len() > 1 {
+    info!("    geometry mixing {} kinds of primitives split", builders.len());
+  }
+
+  Ok(builders.into_iter().map(|(_, mode, builder)| MeshData::new(mode, builder.vertices, Some(builder.indices))).collect())
 }
 
-// Create triplet keys from wavefront_obj primitives. If any primitive doesn’t have all the triplet
-// information (position, normal, tex), a ModelError::UnsupportedVertex error is returned instead.
-fn create_keys_from_primitive(prim: obj::Primitive) -> Result<Vec<(usize, usize, Option<usize>)>, ModelError> {
-  match prim {
-    obj::Primitive::Point(i) => {
-      let a = vtnindex_to_key(i)?;
-      Ok(vec![a])
-    },
-    obj::Primitive::Line(i, j) => {
-      let a = vtnindex_to_key(i)?;
-      let b = vtnindex_to_key(j)?;
-      Ok(vec![a, b])
-    },
-    obj::Primitive::Triangle(i, j, k) => {
-      let a = vtnindex_to_key(i)?;
-      let b = vtnindex_to_key(j)?;
-      let c = vtnindex_to_key(k)?;
-      Ok(vec![a, b, c])
+// Vertices and indices of a mesh being built.
+struct GeometryBuilder {
+  vertices: Vec<Vertex>, // FIXME: better allocation scheme?
+  indices: Vec<u32>,
+  index_map: BTreeMap<VertexKey, u32>
+}
+
+impl GeometryBuilder {
+  fn new() -> Self {
+    GeometryBuilder {
+      vertices: Vec::new(),
+      indices: Vec::new(),
+      index_map: BTreeMap::new()
+    }
+  }
+}
+
+// Create triplet keys from wavefront_obj primitives. Vertices without normals get generated ones.
+fn create_keys_from_primitive(prim: obj::Primitive, shape: usize, generator: &NormalGenerator) -> Vec<VertexKey> {
+  let corners = match prim {
+    obj::Primitive::Point(i) => vec![i],
+    obj::Primitive::Line(i, j) => vec![i, j],
+    obj::Primitive::Triangle(i, j, k) => vec![i, j, k]
+  };
+
+  corners.into_iter().map(|(pi, ti, ni)| {
+    let normal = match ni {
+      Some(ni) => NormalKey::Index(ni),
+      None => NormalKey::Faces(generator.faces(shape, pi))
+    };
+
+    (pi, normal, ti)
+  }).collect()
+}
+
+// Generate normals for the vertices of an object.
+struct NormalGenerator {
+  mode: Normals,
+  // normal of each shape, weighted by its area; points and lines have none
+  shape_normals: Vec<Option<[f32; 3]>>,
+  // shapes sharing a position
+  incident: HashMap<usize, Vec<usize>>
+}
+
+impl NormalGenerator {
+  fn new(obj: &obj::Object, mode: Normals) -> Self {
+    let mut shape_normals = Vec::new();
+    let mut incident = HashMap::new();
+
+    for shape in obj.geometry.iter().flat_map(|geometry| &geometry.shapes) {
+      let normal = match shape.primitive {
+        obj::Primitive::Triangle((a, _, _), (b, _, _), (c, _, _)) => {
+          for &p in &[a, b, c] {
+            incident.entry(p).or_insert_with(Vec::new).push(shape_normals.len());
+          }
+
+          let (a, b, c) = (convert_vertex(&obj.vertices[a]), convert_vertex(&obj.vertices[b]), convert_vertex(&obj.vertices[c]));
+          Some(cross(sub(b, a), sub(c, a)))
+        },
+        _ => None
+      };
+
+      shape_normals.push(normal);
+    }
+
+    NormalGenerator {
+      mode: mode,
+      shape_normals: shape_normals,
+      incident: incident
+    }
+  }
+
+  // Faces to average to get the normal of a position in a given shape.
+  fn faces(&self, shape: usize, position: usize) -> Vec<usize> {
+    let normal = match self.shape_normals[shape] {
+      Some(normal) => normal,
+      None => return Vec::new()
+    };
+
+    let incident = self.incident.get(&position).map_or(&[][..], |faces| faces.as_slice());
+
+    match self.mode {
+      Normals::Flat => vec![shape],
+      Normals::Smooth => incident.to_vec(),
+      Normals::Angle(angle) => {
+        let cos_threshold = angle.to_radians().cos();
+        let normal = normalize(normal);
+
+        incident.iter().cloned().filter(|&face| {
+          face == shape || self.shape_normals[face].map_or(false, |n| dot(normal, normalize(n)) >= cos_threshold)
+        }).collect()
+      }
     }
   }
+
+  // Average the normals of faces. Without any face, the normal is null.
+  fn average(&self, faces: &[usize]) -> VertexNor {
+    let sum = faces.iter().filter_map(|&face| self.shape_normals[face]).fold([0., 0., 0.], add);
+    normalize(sum)
+  }
+}
+
+/// Generate the tangents of an indexed triangle list.
+///
+/// The algorithm follows MikkTSpace: the tangent of each triangle corner is projected on the plane
+/// of the vertex normal and weighted by the angle of the corner, and vertices shared by triangles
+/// which texture space has opposite orientations – mirrored UVs – are split. Triangles with
+/// degenerate texture coordinates don’t contribute; vertices that only belong to such triangles
+/// get an arbitrary tangent orthogonal to their normal.
+///
+/// As in MikkTSpace, the sign of the bitangent – `w` – is the sign of the area of the triangle in
+/// texture space, whatever its winding relative to the vertex normals.
+///
+/// Return the new vertices and indices.
+pub fn generate_tangents(vertices: &[Vertex], indices: &[u32]) -> (Vec<TangentVertex>, Vec<u32>) {
+  let (vertices, indices, _) = tangent_vertices(vertices, indices);
+  (vertices, indices)
 }
 
-// Convert from a wavefront_obj VTNIndex into our triplet, raising error if not possible.
-fn vtnindex_to_key(i: obj::VTNIndex) -> Result<(usize, usize, Option<usize>), ModelError> {
-  match i {
-    (pi, ti, Some(ni)) => Ok((pi, ni, ti)),
-    _ => Err(ModelError::UnsupportedVertex)
+// Generate the tangents of an indexed triangle list – see `generate_tangents`. The index of the
+// source vertex of each new vertex is returned along with the new vertices and indices.
+fn tangent_vertices(vertices: &[Vertex], indices: &[u32]) -> (Vec<TangentVertex>, Vec<u32>, Vec<u32>) {
+  // accumulated tangents, by original vertex and orientation
+  let mut groups: BTreeMap<(u32, bool), usize> = BTreeMap::new();
+  let mut sums: Vec<(u32, bool, [f32; 3])> = Vec::new();
+  let mut new_indices = Vec::with_capacity(indices.len());
+
+  for triangle in indices.chunks(3).filter(|triangle| triangle.len() == 3) {
+    let corners = [vertices[triangle[0] as usize], vertices[triangle[1] as usize], vertices[triangle[2] as usize]];
+    let p = [corners[0].0, corners[1].0, corners[2].0];
+    let n = [corners[0].1, corners[1].1, corners[2].1];
+    let uv = [corners[0].2, corners[1].2, corners[2].2];
+
+    let e1 = sub(p[1], p[0]);
+    let e2 = sub(p[2], p[0]);
+    let (du1, dv1) = (uv[1][0] - uv[0][0], uv[1][1] - uv[0][1]);
+    let (du2, dv2) = (uv[2][0] - uv[0][0], uv[2][1] - uv[0][1]);
+    let r = du1 * dv2 - du2 * dv1;
+    // the winding doesn’t matter, as in MikkTSpace
+    let orientation = r >= 0.;
+
+    let tangent = if r.abs() > 1e-12 {
+      scale(sub(scale(e1, dv2), scale(e2, dv1)), 1. / r)
+    } else {
+      [0., 0., 0.]
+    };
+
+    for k in 0..3 {
+      let index = triangle[k];
+      let group = match groups.get(&(index, orientation)).cloned() {
+        Some(group) => group,
+        None => {
+          sums.push((index, orientation, [0., 0., 0.]));
+          groups.insert((index, orientation), sums.len() - 1);
+          sums.len() - 1
+        }
+      };
+
+      // angle of the corner
+      let a = normalize(sub(p[(k + 1) % 3], p[k]));
+      let b = normalize(sub(p[(k + 2) % 3], p[k]));
+      let angle = dot(a, b).max(-1.).min(1.).acos();
+
+      let projected = normalize(project(tangent, n[k]));
+      sums[group].2 = add(sums[group].2, scale(projected, angle));
+      new_indices.push(group as u32);
+    }
   }
+
+  let sources = sums.iter().map(|&(index, _, _)| index).collect();
+  let new_vertices = sums.into_iter().map(|(index, orientation, sum)| {
+    let (p, n, uv) = vertices[index as usize];
+    let mut t = normalize(project(sum, n));
+
+    if dot(t, t) == 0. {
+      t = any_orthogonal(n);
+    }
+
+    (p, n, uv, [t[0], t[1], t[2], if orientation { 1. } else { -1. }])
+  }).collect();
+
+  (new_vertices, new_indices, sources)
+}
+
+// Component of a vector orthogonal to a normal.
+fn project(a: [f32; 3], n: [f32; 3]) -> [f32; 3] {
+  sub(a, scale(n, dot(n, a)))
 }
 
-fn interleave_vertex(p: &obj::Vertex, n: &obj::Normal, t: Option<&obj::TVertex>) -> Vertex {
-  (convert_vertex(p), convert_nor(n), t.map_or([0., 0.], convert_tvertex))
+// A unit vector orthogonal to a normal.
+fn any_orthogonal(n: [f32; 3]) -> [f32; 3] {
+  let axis = if n[0].abs() < 0.9 { [1., 0., 0.] } else { [0., 1., 0.] };
+  normalize(project(axis, n))
+}
+
+fn interleave_vertex(p: &obj::Vertex, n: VertexNor, t: Option<&obj::TVertex>) -> Vertex {
+  (convert_vertex(p), n, t.map_or([0., 0.], convert_tvertex))
 }
 
 fn convert_vertex(v: &obj::Vertex) -> VertexPos {
@@ -181,6 +851,15 @@ fn convert_tvertex(t: &obj::TVertex) -> VertexTexCoord {
   [t.u as f32, t.v as f32]
 }
 
+// Kind of a primitive, as its number of vertices.
+fn primitive_kind(prim: obj::Primitive) -> u8 {
+  match prim {
+    obj::Primitive::Point(_) => 1,
+    obj::Primitive::Line(_, _) => 2,
+    obj::Primitive::Triangle(_, _, _) => 3
+  }
+}
+
 fn guess_mode(prim: obj::Primitive) -> tess::Mode {
   match prim {
     obj::Primitive::Point(_) => tess::Mode::Point,
@@ -191,6 +870,5 @@ fn guess_mode(prim: obj::Primitive) -> tess::Mode {
 
 #[derive(Debug)]
 pub enum ModelError {
-  UnsupportedVertex,
   NoShape
 }
@@ -0,0 +1,142 @@
+//! Morph targets – also known as blend shapes.
+//!
+//! A morph target displaces the vertices of a base mesh sharing its topology. A weighted sum of
+//! targets is added to the base mesh, either on the CPU – see `MorphMesh::blend` – or on the GPU
+//! by the morphing path of the default 3D shader, for models loaded with `VertexLayout::Morph`.
+//!
+//! Weights are plain `f32`s, typically animated with `Spline`s – glTF animations of the weights of a
+//! node – or with `Cont`s – see `weights_at`.
+
+use luminance_gl::gl33::Tess;
+
+use anim::Cont;
+use mesh::MeshData;
+use mesh::vector::{add, normalize, scale, sub};
+use model::{MorphVertex, Vertex, VertexJoints, VertexLayout, VertexTangent, VertexWeights, skinned_mesh, upload_mesh};
+use spline::Time;
+
+/// Number of targets the morphing path of the default 3D shader handles. Blend on the CPU to use
+/// more targets.
+pub const MAX_GPU_TARGETS: usize = 2;
+
+/// Name of the `vec2` uniform holding the weights of the targets in morphing shaders.
+pub const WEIGHTS_UNIFORM: &'static str = "spectra_morph_weights";
+
+/// Preprocessor definition enabling the morphing path of the default 3D shader.
+pub const MORPH_DEFINE: &'static str = "SPECTRA_MORPH";
+
+/// Displacements of the vertices of a mesh.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MorphTarget {
+  pub name: Option<String>,
+  /// Displacement of the position of each vertex.
+  pub positions: Vec<[f32; 3]>,
+  /// Displacement of the normal of each vertex, if any.
+  pub normals: Option<Vec<[f32; 3]>>
+}
+
+impl MorphTarget {
+  /// Target morphing a mesh into another one with the same vertices – typically, the same model
+  /// exported in another shape. Return `None` if the meshes don’t have as many vertices.
+  pub fn difference(name: Option<String>, base: &MeshData<Vertex>, shape: &MeshData<Vertex>) -> Option<Self> {
+    if base.vertices.len() != shape.vertices.len() {
+      return None;
+    }
+
+    let pairs = base.vertices.iter().zip(&shape.vertices);
+
+    Some(MorphTarget {
+      name: name,
+      positions: pairs.clone().map(|(a, b)| sub(b.0, a.0)).collect(),
+      normals: Some(pairs.map(|(a, b)| sub(b.1, a.1)).collect())
+    })
+  }
+}
+
+/// A mesh along with its morph targets.
+#[derive(Clone, Debug)]
+pub struct MorphMesh {
+  pub base: MeshData<Vertex>,
+  pub targets: Vec<MorphTarget>,
+  /// Default weights of the targets.
+  pub weights: Vec<f32>
+}
+
+impl MorphMesh {
+  /// Return `None` if a target doesn’t displace as many vertices as the mesh has.
+  pub fn new(base: MeshData<Vertex>, targets: Vec<MorphTarget>, weights: Vec<f32>) -> Option<Self> {
+    let count = base.vertices.len();
+    let matches = targets.iter().all(|target| {
+      target.positions.len() == count && target.normals.as_ref().map_or(true, |normals| normals.len() == count)
+    });
+
+    if !matches {
+      return None;
+    }
+
+    Some(MorphMesh {
+      base: base,
+      targets: targets,
+      weights: weights
+    })
+  }
+
+  /// Blend the targets into the base mesh. Missing weights are null; normals are normalized again.
+  pub fn blend(&self, weights: &[f32]) -> MeshData<Vertex> {
+    let mut vertices = self.base.vertices.clone();
+
+    for (target, &weight) in self.targets.iter().zip(weights) {
+      if weight == 0. {
+        continue;
+      }
+
+      for (vertex, &d) in vertices.iter_mut().zip(&target.positions) {
+        vertex.0 = add(vertex.0, scale(d, weight));
+      }
+
+      if let Some(ref normals) = target.normals {
+        for (vertex, &d) in vertices.iter_mut().zip(normals) {
+          vertex.1 = add(vertex.1, scale(d, weight));
+        }
+      }
+    }
+
+    for vertex in &mut vertices {
+      vertex.1 = normalize(vertex.1);
+    }
+
+    MeshData::new(self.base.mode, vertices, self.base.indices.clone())
+  }
+
+  /// Blend the targets on the CPU and upload the result with the given layout.
+  pub fn upload_blended(&self, weights: &[f32], layout: VertexLayout) -> Tess {
+    upload_mesh(&self.blend(weights), layout)
+  }
+
+  /// Mesh carrying the displacements of the first `MAX_GPU_TARGETS` targets, to be blended by the
+  /// morphing shader. Missing targets don’t displace anything. The tangents and skin of the base
+  /// vertices are those of `skinned_mesh`, so that morphing combines with normal mapping and
+  /// skinning.
+  pub fn gpu_mesh(&self, tangents: Option<Vec<VertexTangent>>, skin: Option<&[(VertexJoints, VertexWeights)]>) -> MeshData<MorphVertex> {
+    let target = |t: usize, i: usize| -> ([f32; 3], [f32; 3]) {
+      match self.targets.get(t) {
+        Some(target) => (target.positions[i], target.normals.as_ref().map_or([0., 0., 0.], |normals| normals[i])),
+        None => ([0., 0., 0.], [0., 0., 0.])
+      }
+    };
+
+    let (skinned, sources) = skinned_mesh(&self.base, tangents, skin);
+    let vertices = skinned.vertices.into_iter().zip(sources).map(|((p, n, uv, t, j, w), i)| {
+      let (p0, n0) = target(0, i as usize);
+      let (p1, n1) = target(1, i as usize);
+      (p, n, uv, t, j, w, p0, n0, p1, n1)
+    }).collect();
+
+    MeshData::new(skinned.mode, vertices, skinned.indices)
+  }
+}
+
+/// Sample weights animated with continuous values at a given time.
+pub fn weights_at(weights: &mut [Cont<f32>], t: Time) -> Vec<f32> {
+  weights.iter_mut().map(|weight| weight.at(t)).collect()
+}
@@ -4,7 +4,7 @@ use std::fs::File;
 
 use id::Id;
 use linear::{Matrix4, Quaternion, ToHomogeneous, Unit};
-use model::Model;
+use model::{Model, ModelArgs};
 use resource::{Cache, Get, Load, LoadError};
 use transform::{Orientation, Position, Scale, Transformable, translation_matrix};
 
@@ -33,6 +33,8 @@ impl<'a> Transformable for Object<'a> {
   }
 }
 
+/// Manifest of an object. `model` is the name of a model in the cache, which can be a built-in one
+/// – such as `builtin:torus?r=1&R=3`.
 #[derive(Debug, Deserialize, Serialize)]
 pub struct ObjectManifest {
   model: String,
@@ -59,7 +61,7 @@ impl<'a> Load<'a> for Object<'a> {
     };
 
     // get the model id
-    let model_id = cache.get_id(&manifest.model, ()).ok_or(LoadError::ConversionFailed(format!("unable to find model {} for object at {:?}", manifest.model, path)))?;
+    let model_id = cache.get_id(&manifest.model, ModelArgs::default()).ok_or(LoadError::ConversionFailed(format!("unable to find model {} for object at {:?}", manifest.model, path)))?;
 
     Ok(Object {
       model: model_id,
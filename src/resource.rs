@@ -1,7 +1,9 @@
 // FIXME: add the support of transient objects
 
 use notify::{self, RecommendedWatcher, Watcher};
+use std::any::{Any, TypeId};
 use std::collections::HashMap;
+use std::mem;
 use std::path::{Path, PathBuf};
 use std::rc::Rc;
 use std::sync::{Arc, Mutex};
@@ -9,12 +11,16 @@ use std::sync::mpsc::{Receiver, Sender, channel};
 use std::thread;
 use time::precise_time_s;
 
+use event::EventTrack;
 use id::Id;
+use material::MaterialLibrary;
 use model::Model;
 use object::Object;
 use shader::Program;
 use spline::Spline;
+use tempo::TempoMap;
 use texture::TextureImage;
+use timeline::Schedule;
 
 /// Class of types that can be loaded.
 pub trait Load<'a>: Sized {
@@ -23,6 +29,41 @@ pub trait Load<'a>: Sized {
 
   // TODO: see whether we can use something with From/Into instead, so that we can use lambdas.
   fn load<P>(path: P, cache: &mut Cache<'a>, args: Self::Args) -> Result<Self> where P: AsRef<Path>;
+
+  /// Key identifying a resource in the cache.
+  ///
+  /// By default, a resource is identified by its name only. If the same file can be loaded with
+  /// different arguments to give different resources, include the arguments in the key so that
+  /// each variant gets its own cache entry.
+  fn cache_key(name: &str, _: &Self::Args) -> String {
+    name.to_owned()
+  }
+
+  /// Resource to use in place of one that fails to load the first time – or which file is missing.
+  ///
+  /// The fallback is cached and watched as if it was the actual resource, so that fixing the file
+  /// replaces it. By default, there’s no fallback and getting the resource fails.
+  fn fallback(_: &Self::Args) -> Option<Self> {
+    None
+  }
+
+  /// Resource generated rather than read from a file – requested with a name starting with
+  /// `BUILTIN_PREFIX`, which is stripped off before being passed here.
+  ///
+  /// Built-in resources have no file to watch and are never reloaded. By default, there are none.
+  fn builtin(name: &str, _: &mut Cache<'a>, _: Self::Args) -> Result<Self> {
+    Err(LoadError::ConversionFailed(format!("no built-in resource {}", name)))
+  }
+}
+
+/// Class of resources stored in the cache besides the built-in ones – such as the resources of
+/// `extra`.
+///
+/// Implementing it is enough to get such resources from the cache; they’re read from
+/// `data/<directory>/<name>` and reloaded like any other resource.
+pub trait Extension: 'static {
+  /// Directory of the resources in `data`.
+  fn directory() -> &'static str;
 }
 
 /// Class of types that can be reloaded.
@@ -50,35 +91,84 @@ pub type Result<T> = ::std::result::Result<T, LoadError>;
 
 type Timestamp = f64;
 
+/// Prefix of the names of built-in resources – see `Load::builtin`.
+pub const BUILTIN_PREFIX: &'static str = "builtin:";
+
 /// Time to await after a resource update to establish that it should be reloaded.
 const UPDATE_AWAIT_TIME: Timestamp = 0.1; // 100ms
 
-struct CacheBlock<'a, T> where T: 'a {
-  data: Vec<(Rc<T>, PathBuf, (Receiver<Timestamp>, f64))>,
-  ids: HashMap<String, Id<'a, T>>,
+type Senders = Arc<Mutex<HashMap<PathBuf, Vec<Sender<Timestamp>>>>>;
+
+struct CacheEntry<T> {
+  resource: Rc<T>,
+  path: PathBuf,
+  // receive update notifications for the resource’s path and its dependencies
+  receiver: Receiver<Timestamp>,
+  sender: Sender<Timestamp>,
+  // time at which the resource was loaded
+  loaded_at: Timestamp,
+  // dependencies the sender is registered for
+  dependencies: Vec<PathBuf>,
+  // error of the last (re)load, if it failed; the resource is then the fallback or the last good one
+  error: Option<LoadError>
 }
 
-impl<'a, T> CacheBlock<'a, T> {
+struct CacheBlock<T> {
+  data: Vec<CacheEntry<T>>,
+  // index of each resource in data, by cache key
+  ids: HashMap<String, u32>,
+}
+
+impl<T> CacheBlock<T> {
   pub fn new() -> Self {
     CacheBlock {
       data: Vec::new(),
       ids: HashMap::new(),
     }
   }
+
+  fn errors(&self) -> Vec<(&Path, &LoadError)> {
+    self.data.iter().filter_map(|entry| entry.error.as_ref().map(|e| (entry.path.as_path(), e))).collect()
+  }
+}
+
+// Block of extension resources, whatever their type.
+trait ExtensionBlock {
+  fn errors(&self) -> Vec<(&Path, &LoadError)>;
+  fn as_any(&self) -> &Any;
+  fn as_any_mut(&mut self) -> &mut Any;
+}
+
+impl<T> ExtensionBlock for CacheBlock<T> where T: 'static {
+  fn errors(&self) -> Vec<(&Path, &LoadError)> {
+    CacheBlock::errors(self)
+  }
+
+  fn as_any(&self) -> &Any {
+    self
+  }
+
+  fn as_any_mut(&mut self) -> &mut Any {
+    self
+  }
 }
 
 macro_rules! cache_struct {
   ($l:tt, $($n:ident : $t:ty),*) => {
     pub struct Cache<$l> {
-      senders: Arc<Mutex<HashMap<PathBuf, Sender<Timestamp>>>>,
+      senders: Senders,
+      // dependencies of the resource being loaded
+      dependencies: Vec<PathBuf>,
+      // blocks of the extension resources, by type
+      extensions: HashMap<TypeId, Box<ExtensionBlock>>,
       $(
-        $n: CacheBlock<$l, $t>
+        $n: CacheBlock<$t>
       ),*
     }
 
     impl<$l> Cache<$l> {
       pub fn new<P>(root:P) -> Self where P: AsRef<Path> {
-        let senders: Arc<Mutex<HashMap<PathBuf, Sender<Timestamp>>>> = Arc::new(Mutex::new(HashMap::new()));
+        let senders: Senders = Arc::new(Mutex::new(HashMap::new()));
 
         // start watcher thread
         {
@@ -93,8 +183,12 @@ macro_rules! cache_struct {
             for event in wrx.iter() {
               match event {
                 notify::Event { path: Some(path), op: Ok(notify::op::WRITE) } => {
-                  if let Some(sx) = senders.lock().unwrap().get(&path) {
-                    sx.send(precise_time_s()).unwrap();
+                  if let Some(sxs) = senders.lock().unwrap().get(&path) {
+                    let now = precise_time_s();
+
+                    for sx in sxs {
+                      let _ = sx.send(now);
+                    }
                   }
                 },
                 _ => {}
@@ -105,88 +199,187 @@ macro_rules! cache_struct {
 
         Cache {
           senders: senders,
+          dependencies: Vec::new(),
+          extensions: HashMap::new(),
           $(
             $n: CacheBlock::new()
           ),*
         }
       }
+
+      /// Errors of all the resources that are currently broken, along with their paths.
+      ///
+      /// A broken resource is either a fallback or the last version that loaded fine.
+      pub fn errors(&self) -> Vec<(&Path, &LoadError)> {
+        let mut errors = Vec::new();
+
+        $(
+          errors.extend(self.$n.errors());
+        )*
+
+        for block in self.extensions.values() {
+          errors.extend(block.errors());
+        }
+
+        errors
+      }
+
+      /// Declare that the resource being loaded depends on a file.
+      ///
+      /// This is meant to be called from `Load::load`. Any change to that file will reload the
+      /// resource, as if its own file had changed.
+      pub fn add_dependency<P>(&mut self, path: P) where P: AsRef<Path> {
+        self.dependencies.push(path.as_ref().to_owned());
+      }
+
+      /// Declare that the resource being loaded depends on another resource of the cache – such as
+      /// the tempo map of a spline. Built-in resources never change, so depending on one does
+      /// nothing.
+      pub fn add_resource_dependency<T>(&mut self, name: &str) where Self: Get<$l, T>, T: $l + Reload<$l> {
+        if let Some(path) = <Self as Get<$l, T>>::path(self, name) {
+          self.add_dependency(path);
+        }
+      }
+
+      // Block of an extension type, created on first use.
+      fn extension<T>(&mut self) -> &mut CacheBlock<T> where T: Extension {
+        let block = self.extensions.entry(TypeId::of::<T>()).or_insert_with(|| Box::new(CacheBlock::<T>::new()) as Box<ExtensionBlock>);
+        block.as_any_mut().downcast_mut().unwrap()
+      }
     }
   }
 }
 
+// Load a resource and collect its dependencies. Dependencies of nested loads are kept apart.
+fn load_with_dependencies<'a, T, P>(path: P, cache: &mut Cache<'a>, args: T::Args) -> (Result<T>, Vec<PathBuf>)
+    where T: Load<'a>,
+          P: AsRef<Path> {
+  let parent_dependencies = mem::replace(&mut cache.dependencies, Vec::new());
+  let loaded = T::load(path, cache, args);
+  let dependencies = mem::replace(&mut cache.dependencies, parent_dependencies);
+
+  (loaded, dependencies)
+}
+
+// Register a sender for the given paths.
+fn register_sender(senders: &Senders, sender: &Sender<Timestamp>, paths: &[PathBuf]) {
+  let mut senders = senders.lock().unwrap();
+
+  for path in paths {
+    senders.entry(path.clone()).or_insert_with(Vec::new).push(sender.clone());
+  }
+}
+
 pub trait Get<'a, T> where T: 'a + Reload<'a> {
   fn get_id(&mut self, name: &str, args: T::Args) -> Option<Id<'a, T>>;
   fn get_by_id(&mut self, id: &Id<'a, T>) -> Option<Rc<T>>;
+  /// Error of the last (re)load of a resource, if it failed.
+  fn get_error(&self, id: &Id<'a, T>) -> Option<&LoadError>;
+  /// Path of the file a resource is read from; built-in resources have none.
+  fn path(&self, name: &str) -> Option<PathBuf>;
   fn get(&mut self, name: &str, args: T::Args) -> Option<Rc<T>> {
     self.get_id(name, args).and_then(move |i| self.get_by_id(&i))
   }
 }
 
+// Path of a resource given the directory of its type in data.
+fn resource_path(dir: &str, name: &str) -> Option<PathBuf> {
+  if name.starts_with(BUILTIN_PREFIX) {
+    None
+  } else {
+    Some(PathBuf::from(format!("data/{}/{}", dir, name)))
+  }
+}
+
+// $dir is the directory of the resources in data and $block the expression of their cache block.
 macro_rules! impl_get_id {
-  ($n:ident : $t:ty, $this:ident, $name:ident, $args: ident) => {{
-    let path_str = format!("data/{}/{}", stringify!($n), $name);
+  ($dir:expr, $t:ty, $this:ident, $name:ident, $args:ident, $($block:tt)+) => {{
+    let resource_path = resource_path($dir, $name);
+    let builtin = resource_path.is_none();
+    let path_str = resource_path.map_or($name.to_owned(), |path| path.display().to_string());
     let path = Path::new(&path_str);
+    let key = <$t as Load>::cache_key($name, &$args);
 
-    match $this.$n.ids.get($name).cloned() {
-      id@Some(..) => {
-        deb!("cache hit for {}", path_str);
-        id
+    match $($block)+.ids.get(&key).cloned() {
+      Some(id) => {
+        deb!("cache hit for {}", key);
+        Some(id.into())
       },
       None => {
-        deb!("cache miss for {}", path_str);
-
-        // specific loading
-        if path.exists() {
-          match <$t as Load>::load(&path, $this, $args) {
-            Ok(resource) => {
-              let path_buf = path.to_owned();
-
-              // create the id if we have loaded the resource
-              let id: Id<$t> = ($this.$n.data.len() as u32).into();
-
-              // create a channel to notify any update later and register the sender for the
-              // given path
-              let (sx, rx) = channel();
-              {
-                let mut senders = $this.senders.lock().unwrap();
-                senders.insert(path_buf.clone(), sx);
-              }
+        deb!("cache miss for {}", key);
+
+        // specific loading; built-in resources don’t have any file, and a missing file gets the
+        // fallback too
+        let fallback_args = $args.clone();
+        let (loaded, dependencies) = if builtin {
+          (<$t as Load>::builtin(&$name[BUILTIN_PREFIX.len()..], $this, $args), Vec::new())
+        } else if path.exists() {
+          load_with_dependencies::<$t, _>(&path, $this, $args)
+        } else {
+          (Err(LoadError::FileNotFound(path.to_owned(), "resource cannot be found".to_owned())), Vec::new())
+        };
+
+        let loaded = match loaded {
+          Ok(resource) => Some((resource, None)),
+          Err(e) => {
+            err!("unable to load resource from {}:\n{:#?}", path_str, e);
+
+            <$t as Load>::fallback(&fallback_args).map(|fallback| {
+              warn!("using a fallback for {}", path_str);
+              (fallback, Some(e))
+            })
+          }
+        };
 
-              // add the resource to the list of loaded ones
-              $this.$n.data.push((Rc::new(resource), path_buf.clone(), (rx, precise_time_s())));
-              // cache the resource
-              $this.$n.ids.insert($name.to_owned(), id.clone());
+        loaded.map(|(resource, error)| {
+          let path_buf = path.to_owned();
 
-              Some(id)
-            },
-            Err(e) => {
-              err!("unable to load resource from {}:\n{:#?}", path_str, e);
-              None
-            }
+          // create the id if we have loaded the resource
+          let id = $($block)+.data.len() as u32;
+
+          // create a channel to notify any update later and register the sender for the
+          // given path and all the dependencies of the resource; built-in resources never change
+          let (sx, rx) = channel();
+
+          if !builtin {
+            register_sender(&$this.senders, &sx, &[path_buf.clone()]);
+            register_sender(&$this.senders, &sx, &dependencies);
           }
-        } else { // path doesn’t exist
-          err!("resource at {} cannot be found", path_str);
-          None
-        }
+
+          // add the resource to the list of loaded ones
+          $($block)+.data.push(CacheEntry {
+            resource: Rc::new(resource),
+            path: path_buf.clone(),
+            receiver: rx,
+            sender: sx,
+            loaded_at: precise_time_s(),
+            dependencies: dependencies,
+            error: error
+          });
+          // cache the resource
+          $($block)+.ids.insert(key, id);
+
+          id.into()
+        })
       }
     }
   }}
 }
 
 macro_rules! impl_get_by_id {
-  ($n:ident : $t:ty, $this:ident, $id:ident) => {{
+  ($t:ty, $this:ident, $id:ident, $($block:tt)+) => {{
     // synchronization
     let mut reload_args = None;
 
-    if let Some(data) = $this.$n.data.get($id.id as usize) {
+    if let Some(entry) = $($block)+.data.get($id.id as usize) {
       // this while loop unqueue the channel to prevent any resource reloading saturation; that can
       // occur if several changes / update are required by the channel is not consumed for a long
       // period of time
-      while let Ok(timestamp) = (data.2).0.try_recv() {
+      while let Ok(timestamp) = entry.receiver.try_recv() {
         // if the change is old enough, consider the resource has been updated; otherwise, keep on
         // waiting
-        if timestamp - (data.2).1 >= UPDATE_AWAIT_TIME {
-          reload_args = Some((data.1.to_owned(), data.0.reload_args()));
+        if timestamp - entry.loaded_at >= UPDATE_AWAIT_TIME {
+          reload_args = Some((entry.path.to_owned(), entry.resource.reload_args()));
         }
       }
     } else {
@@ -194,19 +387,38 @@ macro_rules! impl_get_by_id {
     }
 
     if let Some((path, args)) = reload_args {
-      match <$t as Load>::load(&path, $this, args) {
+      let (reloaded, dependencies) = load_with_dependencies::<$t, _>(&path, $this, args);
+      let senders = $this.senders.clone();
+      let entry = &mut $($block)+.data[$id.id as usize];
+
+      match reloaded {
         Ok(new_resource) => {
           // replace the current resource with the freshly loaded one
           deb!("reloaded resource from {:?}", path);
-          $this.$n.data[$id.id as usize].0 = Rc::new(new_resource);
+
+          entry.resource = Rc::new(new_resource);
+          entry.error = None;
         },
         Err(e) => {
+          // keep the last good version – or the fallback
           warn!("reloading resource from {:?} has failed:\n{:#?}", path, e);
+          entry.error = Some(e);
         }
       }
+
+      // watch the dependencies that weren’t there before
+      let new_dependencies: Vec<_> = dependencies.into_iter().filter(|dep| !entry.dependencies.contains(dep)).collect();
+      register_sender(&senders, &entry.sender, &new_dependencies);
+      entry.dependencies.extend(new_dependencies);
     }
 
-    $this.$n.data.get($id.id as usize).map(|r| r.0.clone())
+    $($block)+.data.get($id.id as usize).map(|entry| entry.resource.clone())
+  }}
+}
+
+macro_rules! impl_get_error {
+  ($id:ident, $($block:tt)+) => {{
+    $($block)+.data.get($id.id as usize).and_then(|entry| entry.error.as_ref())
   }}
 }
 
@@ -214,34 +426,79 @@ macro_rules! impl_get_no_lifetime {
   ($n:ident : $t:ty) => {
     impl<'a> Get<'a, $t> for Cache<'a> {
       fn get_id(&mut self, name: &str, args: <$t as Load<'a>>::Args) -> Option<Id<'a, $t>> {
-        impl_get_id!($n: $t, self, name, args)
+        impl_get_id!(stringify!($n), $t, self, name, args, self.$n)
       }
     
       fn get_by_id(&mut self, id: &Id<'a, $t>) -> Option<Rc<$t>> {
-        impl_get_by_id!($n: $t, self, id)
+        impl_get_by_id!($t, self, id, self.$n)
+      }
+
+      fn get_error(&self, id: &Id<'a, $t>) -> Option<&LoadError> {
+        impl_get_error!(id, self.$n)
+      }
+
+      fn path(&self, name: &str) -> Option<PathBuf> {
+        resource_path(stringify!($n), name)
       }
     }
   }
 }
 
 cache_struct!('a,
+              events: EventTrack,
+              materials: MaterialLibrary,
               models: Model,
               objects: Object<'a>,
+              schedules: Schedule,
               shaders: Program,
               splines: Spline<f32>,
+              tempos: TempoMap,
               textures: TextureImage);
 
+impl_get_no_lifetime!(events: EventTrack);
+impl_get_no_lifetime!(materials: MaterialLibrary);
 impl_get_no_lifetime!(models: Model);
+impl_get_no_lifetime!(schedules: Schedule);
 impl_get_no_lifetime!(shaders: Program);
 impl_get_no_lifetime!(splines: Spline<f32>);
+impl_get_no_lifetime!(tempos: TempoMap);
 impl_get_no_lifetime!(textures: TextureImage);
 
 impl<'a> Get<'a, Object<'a>> for Cache<'a> {
   fn get_id(&mut self, name: &str, args: <Object<'a> as Load<'a>>::Args) -> Option<Id<'a, Object<'a>>> {
-    impl_get_id!(objects: Object<'a>, self, name, args)
+    impl_get_id!("objects", Object<'a>, self, name, args, self.objects)
   }
   
   fn get_by_id(&mut self, id: &Id<'a, Object<'a>>) -> Option<Rc<Object<'a>>> {
-    impl_get_by_id!(objects: Object<'a>, self, id)
+    impl_get_by_id!(Object<'a>, self, id, self.objects)
+  }
+
+  fn get_error(&self, id: &Id<'a, Object<'a>>) -> Option<&LoadError> {
+    impl_get_error!(id, self.objects)
+  }
+
+  fn path(&self, name: &str) -> Option<PathBuf> {
+    resource_path("objects", name)
+  }
+}
+
+// the arguments are kept for the fallback of a resource failing to load
+impl<'a, T> Get<'a, T> for Cache<'a> where T: Extension + Reload<'a>, T::Args: Clone {
+  fn get_id(&mut self, name: &str, args: T::Args) -> Option<Id<'a, T>> {
+    impl_get_id!(T::directory(), T, self, name, args, self.extension::<T>())
+  }
+
+  fn get_by_id(&mut self, id: &Id<'a, T>) -> Option<Rc<T>> {
+    impl_get_by_id!(T, self, id, self.extension::<T>())
+  }
+
+  fn get_error(&self, id: &Id<'a, T>) -> Option<&LoadError> {
+    self.extensions.get(&TypeId::of::<T>())
+      .and_then(|block| block.as_any().downcast_ref::<CacheBlock<T>>())
+      .and_then(|block| impl_get_error!(id, block))
+  }
+
+  fn path(&self, name: &str) -> Option<PathBuf> {
+    resource_path(T::directory(), name)
   }
 }
@@ -1,8 +1,12 @@
 use std::path::Path;
 use std::rc::Rc;
+use time::precise_time_s;
 
 use id::Id;
-use resource::{Cache, Load, Get, Reload};
+use resource::{Cache, Load, LoadError, Get, Reload};
+use shader::Builtins;
+use spline::Time;
+use tempo::TempoMap;
 
 /// The scene type.
 ///
@@ -10,16 +14,72 @@ use resource::{Cache, Load, Get, Reload};
 /// visual effects.
 pub struct Scene<'a> {
   /// Cache.
-  pub cache: Cache<'a>
+  pub cache: Cache<'a>,
+  /// Values of the built-in uniforms that don’t depend on what’s being rendered.
+  pub builtins: Builtins,
+  tempo: Option<Rc<TempoMap>>,
+  // number of frames so far
+  frames: u32,
+  // time at which the scene was created, in seconds
+  created_at: f64,
+  // is a frame in progress – started with next_frame or begin_frame, and not ended yet?
+  in_frame: bool
 }
 
 impl<'a> Scene<'a> {
   pub fn new<P>(root: P) -> Self where P: AsRef<Path>{
     Scene {
       cache: Cache::new(root),
+      builtins: Builtins::default(),
+      tempo: None,
+      frames: 0,
+      created_at: precise_time_s(),
+      in_frame: false
     }
   }
 
+  /// Set the tempo map used to compute the `spectra_beat` built-in uniform.
+  pub fn set_tempo(&mut self, tempo: Option<Rc<TempoMap>>) {
+    self.tempo = tempo;
+  }
+
+  /// Move on to the next frame, at a given time. This updates the time, beat and frame built-in
+  /// uniforms.
+  ///
+  /// `Timeline::update` calls it with the time driving the timeline. Without a timeline, the
+  /// renderers and compositors in `extra` call `begin_frame` instead.
+  pub fn next_frame(&mut self, t: Time) {
+    self.builtins.time = t;
+    self.builtins.beat = self.tempo.as_ref().map_or(0., |tempo| tempo.beat_at(t));
+    self.builtins.frame = self.frames;
+    self.frames = self.frames.wrapping_add(1);
+    self.in_frame = true;
+  }
+
+  /// Move on to the next frame if none is in progress, at the time elapsed since the scene was
+  /// created.
+  ///
+  /// Renderers and compositors call it before reading the built-ins, so that they’re filled in even
+  /// without a `Timeline`. It’s idempotent within a frame: every renderer and compositor of a frame
+  /// calls it, and only the first call moves on – until `end_frame`.
+  pub fn begin_frame(&mut self) {
+    if !self.in_frame {
+      let t = (precise_time_s() - self.created_at) as Time;
+      self.next_frame(t);
+    }
+  }
+
+  /// End the frame in progress.
+  ///
+  /// Whatever outputs the frame must call it: compositors in `extra` do once they have composited
+  /// it, while renderers never do, as a compositor may follow them. Without any compositor – with
+  /// a `SimpleRenderer` alone, for instance – call it yourself once the frame is displayed, or the
+  /// frame built-ins never move on. `Timeline::update` moves on to the next frame whether or not the
+  /// previous one was ended.
+  pub fn end_frame(&mut self) {
+    self.in_frame = false;
+  }
+
   pub fn get_id<T>(&mut self, name: &str, args: <T as Load<'a>>::Args) -> Option<Id<'a, T>> where Cache<'a>: Get<'a, T>, T: 'a + Reload<'a> {
     self.cache.get_id(name, args)
   }
@@ -31,6 +91,16 @@ impl<'a> Scene<'a> {
   pub fn get<T>(&mut self, name: &str, args: <T as Load<'a>>::Args) -> Option<Rc<T>> where Cache<'a>: Get<'a, T>, T: 'a + Reload<'a> {
     self.cache.get(name, args)
   }
+
+  /// Error of the last (re)load of a resource, if it failed.
+  pub fn get_error<T>(&self, id: &Id<'a, T>) -> Option<&LoadError> where Cache<'a>: Get<'a, T>, T: 'a + Reload<'a> {
+    self.cache.get_error(id)
+  }
+
+  /// Errors of all the resources that are currently broken. See `Cache::errors`.
+  pub fn errors(&self) -> Vec<(&Path, &LoadError)> {
+    self.cache.errors()
+  }
 }
 
 #[macro_export]
@@ -1,16 +1,20 @@
 use luminance::StageError;
 use luminance::shader::stage;
 use luminance_gl::gl33::Stage;
+use serde_json::from_reader;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
 use std::ops::Deref;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
-pub use luminance::{ProgramError, Sem, Uniformable};
+pub use luminance::{M44, ProgramError, Sem, Uniformable, Unit};
 pub use luminance::shader::program::UniformWarning;
 pub use luminance_gl::gl33::{self, Uniform};
 pub use luminance_gl::gl33::token::GL33;
 
+use glsl::{GlslDiagnostic, UniformDecl, program_uniforms, validate};
 use resource::{Cache, Load, LoadError, Reload};
 
 #[derive(Debug)]
@@ -58,6 +62,66 @@ fn compile_stages(tcs_src: &str, tes_src: &str, vs_src: &str, gs_src: &str, fs_s
   Ok((tess, vs, gs, fs))
 }
 
+/// Root directory of shaders in the cache. Includes are resolved relative to it.
+pub const SHADERS_ROOT: &'static str = "data/shaders";
+
+/// Preprocessor definitions, mapping names to values. An empty value defines the name only.
+pub type Defines = BTreeMap<String, String>;
+
+/// Arguments used to load a `Program`.
+#[derive(Clone, Default)]
+pub struct ProgramArgs {
+  /// Semantic map of the uniforms. If empty, uniforms are discovered from the sources.
+  pub sem_map: Vec<Sem>,
+  /// Definitions injected in every stage, right after the `#version` directive if any.
+  pub defines: Defines,
+  /// Uniforms the program is expected to declare, along with their GLSL types.
+  pub interface: Vec<(&'static str, &'static str)>,
+  /// Whether the program draws a fullscreen quad without any vertex attribute – as with
+  /// `Tess::attributeless`. The fallback program then draws that quad too.
+  pub fullscreen: bool,
+  // layout of the discovered uniforms of a previous load, kept so that handles survive reloading
+  layout: Vec<UniformDecl>
+}
+
+impl ProgramArgs {
+  pub fn new(sem_map: Vec<Sem>) -> Self {
+    ProgramArgs {
+      sem_map: sem_map,
+      ..ProgramArgs::default()
+    }
+  }
+
+  /// Arguments for a program which uniforms are discovered from its sources.
+  pub fn discover() -> Self {
+    ProgramArgs::default()
+  }
+
+  /// Add a definition.
+  pub fn define(mut self, name: &str, value: &str) -> Self {
+    self.defines.insert(name.to_owned(), value.to_owned());
+    self
+  }
+
+  /// Expect the program to declare the uniforms of an interface. Mismatching types fail the load.
+  pub fn interface<I>(mut self) -> Self where I: UniformInterface {
+    self.interface.extend(I::uniforms());
+    self
+  }
+
+  /// Declare that the program draws a fullscreen quad without vertex attributes.
+  pub fn fullscreen(mut self) -> Self {
+    self.fullscreen = true;
+    self
+  }
+}
+
+impl From<Vec<Sem>> for ProgramArgs {
+  fn from(sem_map: Vec<Sem>) -> Self {
+    ProgramArgs::new(sem_map)
+  }
+}
+
 /// Shader program.
 ///
 /// If the program is retrieved from the cache, the path must point to a file containing all the
@@ -74,9 +138,128 @@ fn compile_stages(tcs_src: &str, tes_src: &str, vs_src: &str, gs_src: &str, fs_s
 /// use twice the same pragma in a file.
 ///
 /// At the top of the file, if you don’t put a pragma, you can use `//` to add comments, or die.
+///
+/// The same file can be loaded with different `ProgramArgs::defines` to get several variants of
+/// the program – each variant is cached on its own and they’re all reloaded when the file changes.
+///
+/// Sources are validated with `glsl::validate` – once the defines are in – before being handed to
+/// the driver. Errors fail the load with the file, stage and line they come from; likely mistakes
+/// are only logged as warnings.
+///
+/// Inside a stage, you can include other files with `#include "path"`, where `path` is relative to
+/// the shaders root (`data/shaders`). A file is included at most once per stage and cyclic
+/// includes are rejected. Included files cannot contain stage pragmas. Editing an included file
+/// reloads every program that includes it.
+///
+/// Alternatively, the path can point to a JSON manifest – with the `.json` extension – naming a
+/// file per stage, relative to the shaders root:
+///
+/// ```json
+/// {
+///   "vs": "common/fullscreen.vert",
+///   "fs": "effects/blur.frag"
+/// }
+/// ```
+///
+/// `"vs"` and `"fs"` are mandatory; `"gs"`, `"tcs"` and `"tes"` are optional. Stage files are plain
+/// GLSL – they can include other files but cannot contain stage pragmas. Editing a stage file
+/// reloads every program built from it.
+///
+/// If a program fails to load the first time, a magenta fallback program is used instead, and it’s
+/// replaced as soon as the program is fixed. If a reload fails, the last good version is kept. In
+/// both cases, the error is available with `Scene::get_error`.
+///
+/// Uniforms are discovered from the sources unless you pass an explicit semantic map: get typed
+/// handles by name with `Program::uniform`, or declare a whole set of them at once with
+/// `uniform_interface!`. Handles remain valid when the program is reloaded.
+///
+/// Programs can also declare any of the engine built-in uniforms – see `Builtins` – which are then
+/// filled in by the renderers and compositors with `Program::update_builtins`.
 pub struct Program {
   program: gl33::Program,
-  sem_map: Vec<Sem>
+  args: ProgramArgs,
+  files: Vec<PathBuf>,
+  uniforms: Vec<UniformDecl>,
+  // discovered uniforms that are bound, in semantic order, after the explicit semantic map if any
+  layout: Vec<UniformDecl>,
+  builtins: BuiltinUniforms
+}
+
+impl Program {
+  /// Definitions the program was compiled with.
+  pub fn defines(&self) -> &Defines {
+    &self.args.defines
+  }
+
+  /// Files the program was built from. The index of a file in that list is the source string
+  /// number used in `#line` directives – and then in the driver’s error messages.
+  pub fn files(&self) -> &[PathBuf] {
+    &self.files
+  }
+
+  /// Uniforms declared in the sources.
+  pub fn uniforms(&self) -> &[UniformDecl] {
+    &self.uniforms
+  }
+
+  /// Get a typed handle on a uniform by its name.
+  ///
+  /// This is only possible if the uniforms were discovered – i.e. if the program wasn’t given an
+  /// explicit semantic map – or for built-in uniforms.
+  pub fn uniform<T>(&self, name: &str) -> Result<Uniform<T>, UniformError> where T: UniformType {
+    let decl = self.uniforms.iter().find(|decl| decl.name == name).ok_or(UniformError::NotFound(name.to_owned()))?;
+
+    if !glsl_type_matches(T::glsl_type(), &decl.ty) {
+      return Err(UniformError::TypeMismatch(name.to_owned(), T::glsl_type().to_owned(), decl.ty.clone()));
+    }
+
+    let offset = self.args.sem_map.len();
+
+    self.layout.iter().position(|decl| decl.name == name)
+      .map(|index| Uniform::new((offset + index) as u32))
+      .ok_or(UniformError::NotBound(name.to_owned()))
+  }
+
+  /// Update the built-in uniforms the program declares. The program must be bound – typically, call
+  /// that in the closure of the `Pipe` holding its `ShadingCommand`.
+  pub fn update_builtins(&self, builtins: &Builtins) {
+    let handles = &self.builtins;
+
+    if let Some(ref u) = handles.time {
+      self.update(u, builtins.time);
+    }
+
+    if let Some(ref u) = handles.resolution {
+      self.update(u, builtins.resolution);
+    }
+
+    if let Some(ref u) = handles.view {
+      self.update(u, builtins.view);
+    }
+
+    if let Some(ref u) = handles.proj {
+      self.update(u, builtins.proj);
+    }
+
+    if let Some(ref u) = handles.beat {
+      self.update(u, builtins.beat);
+    }
+
+    if let Some(ref u) = handles.frame {
+      self.update(u, builtins.frame);
+    }
+  }
+
+  fn builtin<T>(&self, name: &str) -> Option<Uniform<T>> where T: UniformType {
+    match self.uniform(name) {
+      Ok(u) => Some(u),
+      Err(UniformError::NotFound(_)) => None,
+      Err(e) => {
+        warn!("built-in {}", e);
+        None
+      }
+    }
+  }
 }
 
 impl Deref for Program {
@@ -88,133 +271,747 @@ impl Deref for Program {
 }
 
 impl<'a> Load<'a> for Program {
-  type Args = Vec<Sem>;
+  type Args = ProgramArgs;
 
-  fn load<P>(path: P, _: &mut Cache<'a>, args: Self::Args) -> Result<Self, LoadError> where P: AsRef<Path> {
+  fn load<P>(path: P, cache: &mut Cache<'a>, args: Self::Args) -> Result<Self, LoadError> where P: AsRef<Path> {
     let path = path.as_ref();
 
     info!("loading shader: {:?}", path);
 
-    enum CurrentStage {
-      VS,
-      FS,
-      GS,
-      TCS,
-      TES
-    }
-
-    fn add_line_to_src(src: &mut String, line: &str, line_nb: usize) {
-      *src += &format!("#line {}\n{}\n", line_nb, line);
-    }
-
-    match File::open(path) {
-      Ok(fh) => {
-        let buffered = BufReader::new(fh);
-        let mut tcs_src = String::new();
-        let mut tes_src = String::new();
-        let mut vs_src = String::new();
-        let mut gs_src = String::new();
-        let mut fs_src = String::new();
-        let mut current_stage: Option<CurrentStage> = None;
-
-        for (line_nb, line) in buffered.lines().enumerate() {
-          let line_nb = line_nb + 1;
-          let line = line.unwrap();
-          let trimmed = line.trim();
-
-          if trimmed.starts_with("#vs") {
-            if !vs_src.is_empty() {
-              return Err(LoadError::ParseFailed(format!("(line {}) several #vs sections", line_nb)));
-            }
-
-            info!("  found a vertex shader");
-
-            current_stage = Some(CurrentStage::VS);
-            continue;
-          } else if trimmed.starts_with("#fs") {
-            if !fs_src.is_empty() {
-              return Err(LoadError::ParseFailed(format!("(line {}) several #fs sections", line_nb)));
-            }
-
-            info!("  found a fragment shader");
-
-            current_stage = Some(CurrentStage::FS);
-            continue;
-          } else if trimmed.starts_with("#gs") {
-            if !gs_src.is_empty() {
-              return Err(LoadError::ParseFailed(format!("(line {}) several #gs sections", line_nb)));
-            }
-
-            info!("  found a geometry shader");
-
-            current_stage = Some(CurrentStage::GS);
-            continue;
-          } else if trimmed.starts_with("#tcs") {
-            if !tcs_src.is_empty() {
-              return Err(LoadError::ParseFailed(format!("(line {}) several #tcs sections", line_nb)));
-            }
-
-            info!("  found a tessellation control shader");
-
-            current_stage = Some(CurrentStage::TCS);
-            continue;
-          } else if trimmed.starts_with("#tes") {
-            if !tes_src.is_empty() {
-              return Err(LoadError::ParseFailed(format!("(line {}) several #tes sections", line_nb)));
-            }
-
-            info!("  found a tessellation evaluation shader");
-
-            current_stage = Some(CurrentStage::TES);
-            continue;
-          } else if current_stage.is_none() && !trimmed.is_empty() && !trimmed.starts_with("//") && !trimmed.starts_with("\n") {
-            return Err(LoadError::ParseFailed(format!("(line {}) not in a shader stage nor a comment", line_nb)));
-          }
-
-          match current_stage {
-            Some(CurrentStage::VS) => {
-              add_line_to_src(&mut vs_src, trimmed, line_nb);
-            },
-            Some(CurrentStage::FS) => {
-              add_line_to_src(&mut fs_src, trimmed, line_nb);
-            },
-            Some(CurrentStage::GS) => {
-              add_line_to_src(&mut gs_src, trimmed, line_nb);
-            },
-            Some(CurrentStage::TCS) => {
-              add_line_to_src(&mut tcs_src, trimmed, line_nb);
-            },
-            Some(CurrentStage::TES) => {
-              add_line_to_src(&mut tes_src, trimmed, line_nb);
-            },
-            None => {}
-          }
+    let sources = preprocess(path, SHADERS_ROOT)?;
+
+    // watch the included files
+    for file in &sources.files[1..] {
+      cache.add_dependency(file);
+    }
+
+    Program::from_sources(sources, args)
+  }
+
+  fn fallback(args: &Self::Args) -> Option<Self> {
+    Program::from_sources(fallback_sources(args), args.clone())
+      .map_err(|e| {
+        err!("unable to build the fallback program:\n{:#?}", e);
+      })
+      .ok()
+  }
+
+  // Every argument changing the built program is in the key: the defines, then the other arguments,
+  // prefixed with `:` so that they can’t be mistaken for defines.
+  fn cache_key(name: &str, args: &Self::Args) -> String {
+    let mut params: Vec<_> = args.defines.iter().map(|(k, v)| if v.is_empty() { k.clone() } else { format!("{}={}", k, v) }).collect();
+
+    if args.fullscreen {
+      params.push(":fullscreen".to_owned());
+    }
+
+    if !args.interface.is_empty() {
+      let interface: Vec<_> = args.interface.iter().map(|&(name, ty)| format!("{}:{}", name, ty)).collect();
+      params.push(format!(":interface={}", interface.join(",")));
+    }
+
+    if !args.sem_map.is_empty() {
+      let sem_map: Vec<_> = args.sem_map.iter().map(|sem| format!("{:?}", sem)).collect();
+      params.push(format!(":sem_map={}", sem_map.join(",")));
+    }
+
+    if !args.layout.is_empty() {
+      let layout: Vec<_> = args.layout.iter().map(|decl| format!("{}:{}", decl.name, decl.ty)).collect();
+      params.push(format!(":layout={}", layout.join(",")));
+    }
+
+    if params.is_empty() {
+      name.to_owned()
+    } else {
+      format!("{}?{}", name, params.join("&"))
+    }
+  }
+}
+
+impl Program {
+  /// Build a program out of preprocessed sources.
+  pub fn from_sources(mut sources: StageSources, args: ProgramArgs) -> Result<Self, LoadError> {
+    sources.define(&args.defines);
+
+    // reject what the driver would, with readable locations; heuristics are only warnings
+    let (errors, warnings): (Vec<_>, Vec<_>) = validate(&sources).into_iter().partition(GlslDiagnostic::is_error);
+
+    for warning in warnings {
+      warn!("{}", warning);
+    }
+
+    if !errors.is_empty() {
+      let errors: Vec<_> = errors.iter().map(|e| e.to_string()).collect();
+      return Err(LoadError::ParseFailed(errors.join("\n")));
+    }
+
+    let uniforms = program_uniforms(&sources).map_err(LoadError::ConversionFailed)?;
+    check_interface(&args.interface, &uniforms)?;
+
+    // with an explicit semantic map, only the built-ins are discovered
+    let layout = if args.sem_map.is_empty() {
+      uniform_layout(&args.layout, &uniforms)
+    } else {
+      uniforms.iter().filter(|decl| is_builtin(&decl.name) && uniform_sem(decl).is_some()).cloned().collect()
+    };
+
+    let mut sem_map = args.sem_map.clone();
+    sem_map.extend(layout_sem_map(&layout));
+
+    let (program, warnings) = new_program(&sources.tcs, &sources.tes, &sources.vs, &sources.gs, &sources.fs, &sem_map)
+      .map_err(|e| LoadError::ConversionFailed(format!("{:#?}\n{}", e, sources.file_table())))?;
+
+    // check for semantic errors
+    for warning in warnings {
+      warn!("uniform warning: {:?}", warning);
+    }
+
+    let mut program = Program {
+      program: program,
+      args: args,
+      files: sources.files,
+      uniforms: uniforms,
+      layout: layout,
+      builtins: BuiltinUniforms::default()
+    };
+
+    program.builtins = BuiltinUniforms {
+      time: program.builtin(BUILTIN_TIME),
+      resolution: program.builtin(BUILTIN_RESOLUTION),
+      view: program.builtin(BUILTIN_VIEW),
+      proj: program.builtin(BUILTIN_PROJ),
+      beat: program.builtin(BUILTIN_BEAT),
+      frame: program.builtin(BUILTIN_FRAME)
+    };
+
+    Ok(program)
+  }
+}
+
+impl<'a> Reload<'a> for Program {
+  fn reload_args(&self) -> Self::Args {
+    ProgramArgs {
+      layout: self.layout.clone(),
+      ..self.args.clone()
+    }
+  }
+}
+
+const FALLBACK_VS: &'static str = "\
+layout (location = 0) in vec3 co;
+
+uniform mat4 spectra_proj;
+uniform mat4 spectra_view;
+
+void main() {
+  gl_Position = spectra_proj * spectra_view * vec4(co, 1.);
+}
+";
+
+// vertices of a triangle strip covering the screen, with no vertex attribute
+const FALLBACK_FULLSCREEN_VS: &'static str = "\
+vec2[4] CO = vec2[](
+  vec2( 1., -1.),
+  vec2( 1.,  1.),
+  vec2(-1., -1.),
+  vec2(-1.,  1.)
+);
+
+void main() {
+  gl_Position = vec4(CO[gl_VertexID], 0., 1.);
+}
+";
+
+const FALLBACK_FS: &'static str = "\
+out vec4 frag;
+
+void main() {
+  frag = vec4(1., 0., 1., 1.);
+}
+";
+
+/// Sources of the magenta program used in place of programs that fail to load. It declares the
+/// uniforms of the expected interface, so that getting their handles still works.
+pub fn fallback_sources(args: &ProgramArgs) -> StageSources {
+  let mut declarations = String::new();
+
+  for &(name, ty) in &args.interface {
+    if !is_builtin(name) {
+      let ty = if ty == "sampler" { "sampler2D" } else { ty };
+      declarations += &format!("uniform {} {};\n", ty, name);
+    }
+  }
+
+  let vs = if args.fullscreen { FALLBACK_FULLSCREEN_VS } else { FALLBACK_VS };
+
+  StageSources {
+    vs: format!("{}{}", declarations, vs),
+    fs: FALLBACK_FS.to_owned(),
+    files: vec![PathBuf::from("<fallback>")],
+    ..StageSources::default()
+  }
+}
+
+/// Name of the built-in uniform holding the current time in seconds (`float`).
+pub const BUILTIN_TIME: &'static str = "spectra_time";
+/// Name of the built-in uniform holding the resolution of the render target in pixels (`vec2`).
+pub const BUILTIN_RESOLUTION: &'static str = "spectra_resolution";
+/// Name of the built-in uniform holding the view matrix of the camera (`mat4`).
+pub const BUILTIN_VIEW: &'static str = "spectra_view";
+/// Name of the built-in uniform holding the projection matrix of the camera (`mat4`).
+pub const BUILTIN_PROJ: &'static str = "spectra_proj";
+/// Name of the built-in uniform holding the current (fractional) beat (`float`).
+pub const BUILTIN_BEAT: &'static str = "spectra_beat";
+/// Name of the built-in uniform holding the index of the current frame (`uint`).
+pub const BUILTIN_FRAME: &'static str = "spectra_frame";
+
+fn is_builtin(name: &str) -> bool {
+  [BUILTIN_TIME, BUILTIN_RESOLUTION, BUILTIN_VIEW, BUILTIN_PROJ, BUILTIN_BEAT, BUILTIN_FRAME].contains(&name)
+}
+
+/// Values of the engine built-in uniforms.
+///
+/// The scene holds the values that don’t depend on what’s being rendered – see `Scene::builtins`.
+/// Renderers and compositors complete them with the resolution of their target and the camera
+/// they use, if any.
+#[derive(Clone, Copy, Debug)]
+pub struct Builtins {
+  pub time: f32,
+  pub resolution: [f32; 2],
+  pub view: M44,
+  pub proj: M44,
+  pub beat: f32,
+  pub frame: u32
+}
+
+impl Default for Builtins {
+  fn default() -> Self {
+    let identity = [
+      [1., 0., 0., 0.],
+      [0., 1., 0., 0.],
+      [0., 0., 1., 0.],
+      [0., 0., 0., 1.]
+    ];
+
+    Builtins {
+      time: 0.,
+      resolution: [0., 0.],
+      view: identity,
+      proj: identity,
+      beat: 0.,
+      frame: 0
+    }
+  }
+}
+
+// Handles on the built-in uniforms a program declares.
+#[derive(Default)]
+struct BuiltinUniforms {
+  time: Option<Uniform<f32>>,
+  resolution: Option<Uniform<[f32; 2]>>,
+  view: Option<Uniform<M44>>,
+  proj: Option<Uniform<M44>>,
+  beat: Option<Uniform<f32>>,
+  frame: Option<Uniform<u32>>
+}
+
+/// Class of types that can be sent to a uniform.
+pub trait UniformType: Uniformable {
+  /// GLSL type of the uniform. Samplers are all represented by `"sampler"`, arrays by their element
+  /// type followed by `[]`.
+  fn glsl_type() -> &'static str;
+}
+
+macro_rules! impl_uniform_type {
+  ($($t:ty => $glsl:expr),* ; $($array:ty => $array_glsl:expr),*) => {
+    $(
+      impl UniformType for $t {
+        fn glsl_type() -> &'static str {
+          $glsl
         }
+      }
+    )*
 
-        let (program, warnings) = new_program(&tcs_src, &tes_src, &vs_src, &gs_src, &fs_src, &args)
-          .map_err(|e| LoadError::ConversionFailed(format!("{:#?}", e)))?;
+    $(
+      impl<'a> UniformType for &'a [$array] {
+        fn glsl_type() -> &'static str {
+          $array_glsl
+        }
+      }
+    )*
+
+    // Semantic of a discovered uniform, if its type is supported.
+    fn uniform_sem(decl: &UniformDecl) -> Option<Sem> {
+      $(
+        if glsl_type_matches($glsl, &decl.ty) {
+          return Some(Uniform::<$t>::sem(&decl.name));
+        }
+      )*
 
-        // check for semantic errors
-        for warning in warnings {
-          warn!("uniform warning: {:?}", warning);
+      $(
+        if glsl_type_matches($array_glsl, &decl.ty) {
+          return Some(Uniform::<&[$array]>::sem(&decl.name));
         }
+      )*
+
+      None
+    }
+  }
+}
 
-        Ok(
-          Program {
-            program: program,
-            sem_map: args
-          }
-        )
+impl_uniform_type!(f32 => "float",
+                   [f32; 2] => "vec2",
+                   [f32; 3] => "vec3",
+                   [f32; 4] => "vec4",
+                   i32 => "int",
+                   [i32; 2] => "ivec2",
+                   [i32; 3] => "ivec3",
+                   [i32; 4] => "ivec4",
+                   u32 => "uint",
+                   [u32; 2] => "uvec2",
+                   [u32; 3] => "uvec3",
+                   [u32; 4] => "uvec4",
+                   M44 => "mat4",
+                   Unit => "sampler";
+                   M44 => "mat4[]");
+
+// Arrays of any size match an unsized array type – `"mat4[]"` matches `"mat4[64]"`.
+fn glsl_type_matches(expected: &str, found: &str) -> bool {
+  expected == found ||
+    (expected == "sampler" && found.contains("sampler") && !found.contains('[')) ||
+    (expected.ends_with("[]") && found.starts_with(&expected[..expected.len() - 1]) && found.ends_with(']'))
+}
+
+/// Error that can occur when getting a uniform by name.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum UniformError {
+  /// The uniform is not declared in the sources.
+  NotFound(String),
+  /// The uniform is declared with another type. The expected type comes first, then the declared
+  /// one.
+  TypeMismatch(String, String, String),
+  /// The uniform is declared but cannot be accessed by name, either because its type is not
+  /// supported or because the program uses an explicit semantic map.
+  NotBound(String)
+}
+
+impl fmt::Display for UniformError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+    match *self {
+      UniformError::NotFound(ref name) => write!(f, "uniform {} is not declared", name),
+      UniformError::TypeMismatch(ref name, ref expected, ref found) => write!(f, "uniform {} is declared as {} but used as {}", name, found, expected),
+      UniformError::NotBound(ref name) => write!(f, "uniform {} cannot be accessed by name", name)
+    }
+  }
+}
+
+/// A set of uniforms.
+///
+/// You shouldn’t implement that trait by hand: use `uniform_interface!` instead.
+pub trait UniformInterface: Sized {
+  /// Names and GLSL types of the uniforms.
+  fn uniforms() -> Vec<(&'static str, &'static str)>;
+  /// Get the uniforms of a program.
+  fn from_program(program: &Program) -> Result<Self, UniformError>;
+}
+
+/// Declare a uniform interface – a struct which fields are uniform handles named after the GLSL
+/// uniforms.
+///
+/// ```ignore
+/// uniform_interface! {
+///   pub struct CameraUniforms {
+///     proj: M44,
+///     view: M44
+///   }
+/// }
+///
+/// let id = get_id!(scene, "camera.glsl", ProgramArgs::discover().interface::<CameraUniforms>());
+/// let uniforms = CameraUniforms::from_program(&program)?;
+/// program.update(&uniforms.proj, proj);
+/// ```
+#[macro_export]
+macro_rules! uniform_interface {
+  ($(#[$attr:meta])* pub struct $name:ident { $($field:ident : $t:ty),* $(,)* }) => {
+    $(#[$attr])*
+    pub struct $name {
+      $(pub $field: $crate::shader::Uniform<$t>),*
+    }
+
+    impl $crate::shader::UniformInterface for $name {
+      fn uniforms() -> Vec<(&'static str, &'static str)> {
+        vec![$((stringify!($field), <$t as $crate::shader::UniformType>::glsl_type())),*]
+      }
+
+      fn from_program(program: &$crate::shader::Program) -> Result<Self, $crate::shader::UniformError> {
+        Ok($name {
+          $($field: program.uniform(stringify!($field))?),*
+        })
+      }
+    }
+  }
+}
+
+// Check that the uniforms of an interface are declared with the right types.
+fn check_interface(interface: &[(&'static str, &'static str)], uniforms: &[UniformDecl]) -> Result<(), LoadError> {
+  for &(name, ty) in interface {
+    match uniforms.iter().find(|decl| decl.name == name) {
+      Some(decl) if !glsl_type_matches(ty, &decl.ty) => {
+        return Err(LoadError::ConversionFailed(format!("{}", UniformError::TypeMismatch(name.to_owned(), ty.to_owned(), decl.ty.clone()))));
       },
-      Err(e) => {
-        Err(LoadError::FileNotFound(path.to_owned(), format!("{:#?}", e)))
+      Some(_) => {},
+      None => {
+        warn!("uniform {} is not declared", name);
       }
     }
   }
+
+  Ok(())
 }
 
-impl<'a> Reload<'a> for Program {
-  fn reload_args(&self) -> Self::Args {
-    self.sem_map.clone()
+// Layout of the discovered uniforms. The uniforms of the previous layout keep their index – even if
+// they’re gone, in which case they just won’t be found – and new ones are appended.
+fn uniform_layout(previous: &[UniformDecl], uniforms: &[UniformDecl]) -> Vec<UniformDecl> {
+  let mut layout: Vec<UniformDecl> = previous.iter().map(|decl| {
+    match uniforms.iter().find(|d| d.name == decl.name) {
+      Some(d) if uniform_sem(d).is_some() => d.clone(),
+      _ => decl.clone()
+    }
+  }).collect();
+
+  for decl in uniforms {
+    if layout.iter().any(|d| d.name == decl.name) {
+      continue;
+    }
+
+    if uniform_sem(decl).is_some() {
+      layout.push(decl.clone());
+    } else {
+      warn!("uniform {} has an unsupported type ({}) and won’t be accessible by name", decl.name, decl.ty);
+    }
+  }
+
+  layout
+}
+
+// Every uniform of a layout has a supported type.
+fn layout_sem_map(layout: &[UniformDecl]) -> Vec<Sem> {
+  layout.iter().filter_map(uniform_sem).collect()
+}
+
+/// Preprocessed sources of the stages of a program.
+///
+/// Stages that are not present are empty.
+#[derive(Clone, Debug, Default)]
+pub struct StageSources {
+  pub tcs: String,
+  pub tes: String,
+  pub vs: String,
+  pub gs: String,
+  pub fs: String,
+  /// Files the sources come from. The index of a file is the source string number used in the
+  /// `#line` directives of the sources. The first file is the program file.
+  pub files: Vec<PathBuf>
+}
+
+impl StageSources {
+  /// Present stages along with their sources.
+  pub fn stages(&self) -> Vec<(StageKind, &str)> {
+    let all = [
+      (StageKind::TCS, &self.tcs),
+      (StageKind::TES, &self.tes),
+      (StageKind::VS, &self.vs),
+      (StageKind::GS, &self.gs),
+      (StageKind::FS, &self.fs)
+    ];
+
+    all.iter().filter(|&&(_, src)| !src.is_empty()).map(|&(stage, src)| (stage, src.as_str())).collect()
+  }
+
+  /// Inject definitions in all the present stages, right after the `#version` directive if any.
+  pub fn define(&mut self, defines: &Defines) {
+    if defines.is_empty() {
+      return;
+    }
+
+    let mut directives = String::new();
+
+    for (name, value) in defines {
+      directives += &format!("#define {} {}\n", name, value);
+    }
+
+    for src in &mut [&mut self.tcs, &mut self.tes, &mut self.vs, &mut self.gs, &mut self.fs] {
+      if !src.is_empty() {
+        let at = match src.find("#version") {
+          Some(i) => src[i..].find('\n').map_or(src.len(), |j| i + j + 1),
+          None => 0
+        };
+
+        **src = format!("{}{}{}", &src[..at], directives, &src[at..]);
+      }
+    }
+  }
+
+  /// Human-readable table of the source string numbers.
+  pub fn file_table(&self) -> String {
+    let mut table = String::from("source files:");
+
+    for (i, file) in self.files.iter().enumerate() {
+      table += &format!("\n  {}: {}", i, file.display());
+    }
+
+    table
+  }
+}
+
+/// Kind of shader stage.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum StageKind {
+  /// Tessellation control stage.
+  TCS,
+  /// Tessellation evaluation stage.
+  TES,
+  /// Vertex stage.
+  VS,
+  /// Geometry stage.
+  GS,
+  /// Fragment stage.
+  FS
+}
+
+impl StageKind {
+  pub fn name(&self) -> &'static str {
+    match *self {
+      StageKind::TCS => "tessellation control shader",
+      StageKind::TES => "tessellation evaluation shader",
+      StageKind::VS => "vertex shader",
+      StageKind::GS => "geometry shader",
+      StageKind::FS => "fragment shader"
+    }
+  }
+}
+
+// Stage introduced by a stage pragma, if any.
+fn stage_pragma(trimmed: &str) -> Option<StageKind> {
+  if trimmed.starts_with("#vs") {
+    Some(StageKind::VS)
+  } else if trimmed.starts_with("#fs") {
+    Some(StageKind::FS)
+  } else if trimmed.starts_with("#gs") {
+    Some(StageKind::GS)
+  } else if trimmed.starts_with("#tcs") {
+    Some(StageKind::TCS)
+  } else if trimmed.starts_with("#tes") {
+    Some(StageKind::TES)
+  } else {
+    None
+  }
+}
+
+// Path in an include directive, if the line is one.
+fn include_directive(trimmed: &str) -> Option<Result<&str, LoadError>> {
+  if !trimmed.starts_with("#include") {
+    return None;
+  }
+
+  let arg = trimmed["#include".len()..].trim();
+  let quoted = arg.len() >= 2 && ((arg.starts_with('"') && arg.ends_with('"')) || (arg.starts_with('<') && arg.ends_with('>')));
+
+  if quoted {
+    Some(Ok(&arg[1..arg.len() - 1]))
+  } else {
+    Some(Err(LoadError::ParseFailed(format!("malformed include directive: {}", trimmed))))
+  }
+}
+
+fn add_line_to_src(src: &mut String, line: &str, line_nb: usize, file_nb: usize) {
+  *src += &format!("#line {} {}\n{}\n", line_nb, file_nb, line);
+}
+
+fn read_lines(path: &Path) -> Result<Vec<String>, LoadError> {
+  let fh = File::open(path).map_err(|e| LoadError::FileNotFound(path.to_owned(), format!("{:#?}", e)))?;
+
+  BufReader::new(fh).lines().collect::<Result<Vec<_>, _>>().map_err(|e| LoadError::ParseFailed(format!("{:?}: {:?}", path, e)))
+}
+
+/// Preprocess a program file – split it into stages – or a program manifest, and resolve includes
+/// relative to `root`.
+pub fn preprocess<P, R>(path: P, root: R) -> Result<StageSources, LoadError> where P: AsRef<Path>, R: AsRef<Path> {
+  let path = path.as_ref();
+
+  if path.extension().map_or(false, |ext| ext == "json") {
+    return preprocess_manifest(path, root.as_ref());
+  }
+
+  let mut pp = Preprocessor::new(root.as_ref(), path);
+  let mut sources = StageSources::default();
+  let mut current_stage: Option<StageKind> = None;
+
+  for (line_nb, line) in read_lines(path)?.into_iter().enumerate() {
+    let line_nb = line_nb + 1;
+    let trimmed = line.trim();
+
+    if let Some(stage) = stage_pragma(trimmed) {
+      if !stage_src(&mut sources, stage).is_empty() {
+        return Err(LoadError::ParseFailed(format!("(line {}) several {} sections", line_nb, trimmed)));
+      }
+
+      info!("  found a {}", stage.name());
+
+      current_stage = Some(stage);
+      continue;
+    } else if current_stage.is_none() && !trimmed.is_empty() && !trimmed.starts_with("//") && !trimmed.starts_with("\n") {
+      return Err(LoadError::ParseFailed(format!("(line {}) not in a shader stage nor a comment", line_nb)));
+    }
+
+    if let Some(stage) = current_stage {
+      match include_directive(trimmed) {
+        Some(include) => {
+          let include = include.map_err(|e| at_line(e, path, line_nb))?;
+          pp.include(stage_src(&mut sources, stage), stage, include)?;
+        },
+        None => {
+          add_line_to_src(stage_src(&mut sources, stage), trimmed, line_nb, 0);
+        }
+      }
+    }
+  }
+
+  sources.files = pp.files;
+  Ok(sources)
+}
+
+#[derive(Deserialize)]
+struct ProgramManifest {
+  #[serde(default)]
+  tcs: Option<String>,
+  #[serde(default)]
+  tes: Option<String>,
+  vs: String,
+  #[serde(default)]
+  gs: Option<String>,
+  fs: String
+}
+
+// Preprocess the stage files named by a program manifest.
+fn preprocess_manifest(path: &Path, root: &Path) -> Result<StageSources, LoadError> {
+  let file = File::open(path).map_err(|e| LoadError::FileNotFound(path.to_path_buf(), format!("{:?}", e)))?;
+  let manifest: ProgramManifest = from_reader(file).map_err(|e| LoadError::ParseFailed(format!("{:?}", e)))?;
+  let mut pp = Preprocessor::new(root, path);
+  let mut sources = StageSources::default();
+
+  let stages = [
+    (StageKind::TCS, manifest.tcs.as_ref()),
+    (StageKind::TES, manifest.tes.as_ref()),
+    (StageKind::VS, Some(&manifest.vs)),
+    (StageKind::GS, manifest.gs.as_ref()),
+    (StageKind::FS, Some(&manifest.fs))
+  ];
+
+  for &(stage, file) in &stages {
+    if let Some(file) = file {
+      info!("  found a {} in {}", stage.name(), file);
+      pp.include(stage_src(&mut sources, stage), stage, file)?;
+    }
+  }
+
+  sources.files = pp.files;
+  Ok(sources)
+}
+
+fn stage_src(sources: &mut StageSources, stage: StageKind) -> &mut String {
+  match stage {
+    StageKind::TCS => &mut sources.tcs,
+    StageKind::TES => &mut sources.tes,
+    StageKind::VS => &mut sources.vs,
+    StageKind::GS => &mut sources.gs,
+    StageKind::FS => &mut sources.fs
+  }
+}
+
+// Prefix a parse error with its location.
+fn at_line(e: LoadError, path: &Path, line_nb: usize) -> LoadError {
+  match e {
+    LoadError::ParseFailed(reason) => LoadError::ParseFailed(format!("({:?}, line {}) {}", path, line_nb, reason)),
+    e => e
+  }
+}
+
+// Include resolver.
+struct Preprocessor<'p> {
+  root: &'p Path,
+  // source string numbers
+  files: Vec<PathBuf>,
+  // files being included, to detect cycles
+  stack: Vec<PathBuf>,
+  // files already included in each stage – implicit include guards
+  included: HashMap<StageKind, HashSet<PathBuf>>
+}
+
+impl<'p> Preprocessor<'p> {
+  fn new(root: &'p Path, path: &Path) -> Self {
+    Preprocessor {
+      root: root,
+      files: vec![path.to_owned()],
+      stack: vec![path.to_owned()],
+      included: HashMap::new()
+    }
+  }
+
+  fn file_nb(&mut self, path: &Path) -> usize {
+    match self.files.iter().position(|file| file == path) {
+      Some(nb) => nb,
+      None => {
+        self.files.push(path.to_owned());
+        self.files.len() - 1
+      }
+    }
+  }
+
+  // Has the file already been included in the given stage? If not, mark it as included.
+  fn already_included(&mut self, stage: StageKind, path: &Path) -> bool {
+    !self.included.entry(stage).or_insert_with(HashSet::new).insert(path.to_owned())
+  }
+
+  fn include(&mut self, src: &mut String, stage: StageKind, include: &str) -> Result<(), LoadError> {
+    let path = self.root.join(include);
+
+    if self.stack.contains(&path) {
+      let cycle: Vec<_> = self.stack.iter().chain(Some(&path)).map(|p| format!("{}", p.display())).collect();
+      return Err(LoadError::ParseFailed(format!("include cycle: {}", cycle.join(" -> "))));
+    }
+
+    if self.already_included(stage, &path) {
+      return Ok(());
+    }
+
+    deb!("  including {:?}", path);
+
+    let file_nb = self.file_nb(&path);
+    let lines = read_lines(&path)?;
+
+    self.stack.push(path.clone());
+
+    for (line_nb, line) in lines.into_iter().enumerate() {
+      let line_nb = line_nb + 1;
+      let trimmed = line.trim();
+
+      if stage_pragma(trimmed).is_some() {
+        return Err(LoadError::ParseFailed(format!("({:?}, line {}) stage pragma in an included or stage file", path, line_nb)));
+      }
+
+      match include_directive(trimmed) {
+        Some(include) => {
+          let include = include.map_err(|e| at_line(e, &path, line_nb))?;
+          self.include(src, stage, include)?;
+        },
+        None => {
+          add_line_to_src(src, trimmed, line_nb, file_nb);
+        }
+      }
+    }
+
+    let _ = self.stack.pop();
+    Ok(())
   }
 }
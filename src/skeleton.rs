@@ -0,0 +1,248 @@
+//! Skeletons and skinning.
+//!
+//! A `Skeleton` is a hierarchy of joints. Posing it – setting the local transform of each joint,
+//! by hand or with a `Clip` – gives a palette of joint matrices, which the skinning path of the
+//! default 3D shader uses to deform the vertices of a `Model` loaded with `VertexLayout::Skinned` –
+//! or `VertexLayout::Morph`, to combine skinning and morphing. Each vertex is moved by up to four
+//! joints, according to its weights.
+//!
+//! Skeletons and clips are read from glTF files – see `Model::skins` and `Skeleton::clip`.
+
+use luminance::M44;
+
+use gltf::{Animation, Channel};
+use linear::{Quaternion, UnitQuaternion, Vector3};
+use spline::Time;
+use transform::{Orientation, Scale, Translation};
+
+/// Maximum number of joints the skinning path of the default 3D shader handles.
+pub const MAX_JOINTS: usize = 64;
+
+/// Name of the `mat4` array uniform holding the joint palette in skinning shaders.
+pub const JOINTS_UNIFORM: &'static str = "spectra_joints";
+
+/// Preprocessor definition enabling the skinning path of the default 3D shader.
+pub const SKINNING_DEFINE: &'static str = "SPECTRA_SKINNING";
+
+const IDENTITY: M44 = [
+  [1., 0., 0., 0.],
+  [0., 1., 0., 0.],
+  [0., 0., 1., 0.],
+  [0., 0., 0., 1.]
+];
+
+/// Local transform of a joint, relative to its parent.
+#[derive(Clone, Copy, Debug)]
+pub struct JointTransform {
+  pub translation: Translation,
+  pub rotation: Orientation,
+  pub scale: Scale
+}
+
+impl JointTransform {
+  pub fn new(translation: Translation, rotation: Orientation, scale: Scale) -> Self {
+    JointTransform {
+      translation: translation,
+      rotation: rotation,
+      scale: scale
+    }
+  }
+
+  pub fn identity() -> Self {
+    JointTransform::new(Vector3::new(0., 0., 0.), UnitQuaternion::new(&Quaternion::new(1., 0., 0., 0.)), Scale::default())
+  }
+
+  /// Column-major matrix scaling, then rotating, then translating.
+  pub fn matrix(&self) -> M44 {
+    let q = self.rotation.quaternion();
+    let (w, x, y, z) = (q.w, q.i, q.j, q.k);
+    let (sx, sy, sz) = (self.scale.x, self.scale.y, self.scale.z);
+    let t = &self.translation;
+
+    [
+      [(1. - 2. * (y * y + z * z)) * sx, 2. * (x * y + w * z) * sx, 2. * (x * z - w * y) * sx, 0.],
+      [2. * (x * y - w * z) * sy, (1. - 2. * (x * x + z * z)) * sy, 2. * (y * z + w * x) * sy, 0.],
+      [2. * (x * z + w * y) * sz, 2. * (y * z - w * x) * sz, (1. - 2. * (x * x + y * y)) * sz, 0.],
+      [t.x, t.y, t.z, 1.]
+    ]
+  }
+}
+
+impl Default for JointTransform {
+  fn default() -> Self {
+    JointTransform::identity()
+  }
+}
+
+/// A joint of a skeleton.
+#[derive(Clone, Debug)]
+pub struct Joint {
+  pub name: Option<String>,
+  /// Index of the parent joint, if any.
+  pub parent: Option<usize>,
+  /// Transform applied between the parent joint – or the origin of the model for root joints – and
+  /// the local transform of the joint. It holds the transforms of the glTF nodes that aren’t joints
+  /// in between, and is usually the identity.
+  pub offset: M44,
+  /// Local transform of the joint in the rest pose.
+  pub rest: JointTransform,
+  /// Matrix bringing the vertices of the mesh in the space of the joint at bind time.
+  pub inverse_bind: M44,
+  /// Index of the node of the joint in the glTF file it comes from, if any.
+  pub node: Option<usize>
+}
+
+impl Joint {
+  pub fn new(name: Option<String>, parent: Option<usize>, rest: JointTransform, inverse_bind: M44) -> Self {
+    Joint {
+      name: name,
+      parent: parent,
+      offset: IDENTITY,
+      rest: rest,
+      inverse_bind: inverse_bind,
+      node: None
+    }
+  }
+}
+
+/// A hierarchy of joints.
+#[derive(Clone, Debug)]
+pub struct Skeleton {
+  pub name: Option<String>,
+  joints: Vec<Joint>,
+  // joints sorted so that parents come before their children
+  order: Vec<usize>
+}
+
+impl Skeleton {
+  /// Build a skeleton out of joints. Return `None` if a joint refers to an unknown parent or if the
+  /// joints form a cycle.
+  pub fn new(name: Option<String>, joints: Vec<Joint>) -> Option<Self> {
+    let mut depths = Vec::with_capacity(joints.len());
+
+    for joint in &joints {
+      let mut depth = 0;
+      let mut parent = joint.parent;
+
+      while let Some(p) = parent {
+        if p >= joints.len() || depth >= joints.len() {
+          return None;
+        }
+
+        depth += 1;
+        parent = joints[p].parent;
+      }
+
+      depths.push(depth);
+    }
+
+    let mut order: Vec<usize> = (0..joints.len()).collect();
+    order.sort_by_key(|&i| depths[i]);
+
+    Some(Skeleton {
+      name: name,
+      joints: joints,
+      order: order
+    })
+  }
+
+  pub fn joints(&self) -> &[Joint] {
+    &self.joints
+  }
+
+  /// Index of a joint by its name.
+  pub fn joint_index(&self, name: &str) -> Option<usize> {
+    self.joints.iter().position(|joint| joint.name.as_ref().map_or(false, |n| n == name))
+  }
+
+  /// Local transforms of the joints in the rest pose. Use it as a starting point to pose the
+  /// skeleton.
+  pub fn rest_pose(&self) -> Vec<JointTransform> {
+    self.joints.iter().map(|joint| joint.rest).collect()
+  }
+
+  /// Transforms of the joints relative to the origin of the model, given their local transforms.
+  /// Joints missing from the pose are in their rest pose.
+  pub fn world_transforms(&self, pose: &[JointTransform]) -> Vec<M44> {
+    let mut world = vec![IDENTITY; self.joints.len()];
+
+    for &i in &self.order {
+      let joint = &self.joints[i];
+      let local = pose.get(i).unwrap_or(&joint.rest).matrix();
+      let parent = joint.parent.map_or(IDENTITY, |p| world[p]);
+
+      world[i] = mul(&mul(&parent, &joint.offset), &local);
+    }
+
+    world
+  }
+
+  /// Joint matrices to send to the skinning shader – see `JOINTS_UNIFORM`. The rest pose gives
+  /// identity matrices when the inverse bind matrices match it.
+  pub fn palette(&self, pose: &[JointTransform]) -> Vec<M44> {
+    self.world_transforms(pose).iter().zip(&self.joints).map(|(world, joint)| mul(world, &joint.inverse_bind)).collect()
+  }
+
+  /// Clip of the channels of an animation driving the joints of this skeleton. Channels driving
+  /// other nodes are dropped.
+  pub fn clip(&self, animation: &Animation) -> Clip {
+    let channels = animation.channels.iter().filter_map(|channel| {
+      self.joints.iter().position(|joint| joint.node == Some(channel.node)).map(|joint| {
+        Channel {
+          node: joint,
+          target: channel.target.clone()
+        }
+      })
+    }).collect();
+
+    Clip {
+      name: animation.name.clone(),
+      channels: channels
+    }
+  }
+}
+
+/// Animation of the joints of a skeleton. The `node` of each channel is the index of a joint.
+#[derive(Clone, Debug)]
+pub struct Clip {
+  pub name: Option<String>,
+  pub channels: Vec<Channel>
+}
+
+impl Clip {
+  /// Time of the last key of the clip, in seconds.
+  pub fn duration(&self) -> Time {
+    self.channels.iter().map(|channel| channel.target.duration()).fold(0., f32::max)
+  }
+
+  /// Set the animated local transforms of a pose at a given time. Before its first key and after
+  /// its last one, a channel holds the value of that key.
+  pub fn apply(&self, t: Time, pose: &mut [JointTransform]) {
+    for channel in &self.channels {
+      if let Some(joint) = pose.get_mut(channel.node) {
+        channel.target.apply(t, &mut joint.translation, &mut joint.rotation, &mut joint.scale, &mut []);
+      }
+    }
+  }
+
+  /// Pose of a skeleton at a given time, starting from its rest pose.
+  pub fn pose(&self, skeleton: &Skeleton, t: Time) -> Vec<JointTransform> {
+    let mut pose = skeleton.rest_pose();
+    self.apply(t, &mut pose);
+    pose
+  }
+}
+
+/// Product of column-major matrices.
+pub fn mul(a: &M44, b: &M44) -> M44 {
+  let mut m = [[0.; 4]; 4];
+
+  for c in 0..4 {
+    for r in 0..4 {
+      m[c][r] = (0..4).map(|k| a[k][r] * b[c][k]).sum();
+    }
+  }
+
+  m
+}
+
@@ -1,12 +1,14 @@
 use serde::Deserialize;
-use serde_json::from_reader;
+use serde_json::{Value, from_reader, from_value};
 use std::f32::consts;
 use std::fs::File;
 use std::ops::{Add, Div, Mul, Sub};
 use std::path::Path;
+use std::rc::Rc;
 
 use linear::{UnitQuaternion, Vector2, Vector3, Vector4};
-use resource::{Cache, Load, LoadError};
+use resource::{Cache, Get, Load, LoadError};
+use tempo::TempoMap;
 
 /// Time used as sampling type in splines.
 pub type Time = f32;
@@ -78,20 +80,54 @@ impl<T> Spline<T> {
       keys: cps
     }
   }
+
+  /// Control points, sorted by time.
+  pub fn keys(&self) -> &[Key<T>] {
+    &self.keys
+  }
 }
 
+// Spline authored in beats.
+#[derive(Deserialize)]
+struct BeatManifest<T> {
+  tempo: String,
+  keys: Vec<Key<T>>
+}
+
+/// If the spline is retrieved from the cache, the path must point to a JSON file containing either
+/// an array of keys, which times are in seconds, or an object of the form:
+///
+/// ```json
+/// {
+///   "tempo": "main.json",
+///   "keys": [ … ]
+/// }
+/// ```
+///
+/// In that case, the times of the keys are in beats and are converted into seconds with the given
+/// tempo map.
 impl<'a, T> Load<'a> for Spline<T> where T: Deserialize {
   type Args = ();
 
-  fn load<P>(path: P, _: &mut Cache<'a>, _: Self::Args) -> Result<Self, LoadError> where P: AsRef<Path> {
+  fn load<P>(path: P, cache: &mut Cache<'a>, _: Self::Args) -> Result<Self, LoadError> where P: AsRef<Path> {
     let path = path.as_ref();
 
     info!("loading spline: {:?}", path);
 
     let file = File::open(path).map_err(|e| LoadError::FileNotFound(path.to_path_buf(), format!("{:?}", e)))?;
-    let keys = from_reader(file).map_err(|e| LoadError::ParseFailed(format!("{:?}", e)))?;
+    let value: Value = from_reader(file).map_err(|e| LoadError::ParseFailed(format!("{:?}", e)))?;
+
+    if value.is_array() {
+      let keys = from_value(value).map_err(|e| LoadError::ParseFailed(format!("{:?}", e)))?;
+      Ok(Spline::new(keys))
+    } else {
+      let manifest: BeatManifest<T> = from_value(value).map_err(|e| LoadError::ParseFailed(format!("{:?}", e)))?;
+      let tempo: Rc<TempoMap> = cache.get(&manifest.tempo, ()).ok_or(LoadError::ConversionFailed(format!("unable to find tempo map {} for spline at {:?}", manifest.tempo, path)))?;
+      cache.add_resource_dependency::<TempoMap>(&manifest.tempo);
+      let keys = manifest.keys.into_iter().map(|key| Key { t: tempo.time_at_beat(key.t), ..key }).collect();
 
-    Ok(Spline::new(keys))
+      Ok(Spline::new(keys))
+    }
   }
 }
 
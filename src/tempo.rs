@@ -0,0 +1,206 @@
+use serde_json::from_reader;
+use std::cmp::Ordering;
+use std::fs::File;
+use std::path::Path;
+
+use resource::{Cache, Load, LoadError};
+use spline::Time;
+
+/// A tempo change.
+///
+/// From `beat` on – and until the next change –, the music plays at `bpm` beats per minute, with
+/// `beats_per_bar` beats in a bar.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+pub struct TempoChange {
+  /// Beat at which the change happens.
+  pub beat: f32,
+  /// Beats per minute.
+  pub bpm: f32,
+  /// Number of beats in a bar.
+  #[serde(default = "def_beats_per_bar")]
+  pub beats_per_bar: u32
+}
+
+impl TempoChange {
+  pub fn new(beat: f32, bpm: f32, beats_per_bar: u32) -> Self {
+    TempoChange {
+      beat: beat,
+      bpm: bpm,
+      beats_per_bar: beats_per_bar
+    }
+  }
+
+  // Times of a map with this change are finite only if it’s valid.
+  fn is_valid(&self) -> bool {
+    self.bpm > 0. && self.bpm.is_finite() && self.beats_per_bar > 0 && self.beat.is_finite()
+  }
+
+  // Duration of a beat, in seconds.
+  fn beat_duration(&self) -> Time {
+    60. / self.bpm
+  }
+}
+
+/// Tempo map.
+///
+/// A tempo map converts between seconds – which is what `Time`, `App::time` and
+/// `Device::playback_cursor` use – and musical time – beats and bars.
+///
+/// If the map is retrieved from the cache, the path must point to a JSON file containing an array
+/// of tempo changes:
+///
+/// ```json
+/// [
+///   { "beat": 0, "bpm": 120, "beats_per_bar": 4 },
+///   { "beat": 128, "bpm": 140 }
+/// ]
+/// ```
+///
+/// The first change always starts at beat `0`. Bars are counted from the first beat and restart
+/// at every tempo change, so a change should happen on a bar boundary if you want bars to keep
+/// making sense.
+#[derive(Clone, Debug)]
+pub struct TempoMap {
+  changes: Vec<TempoChange>,
+  // time at which each change happens
+  times: Vec<Time>,
+  // bar at which each change happens
+  bars: Vec<f32>
+}
+
+impl TempoMap {
+  /// Panics if there’s no change, or if a change has a non-positive or non-finite tempo, no beat
+  /// per bar or a non-finite beat.
+  pub fn new(mut changes: Vec<TempoChange>) -> Self {
+    assert!(!changes.is_empty(), "a tempo map needs at least one tempo change");
+
+    for change in &changes {
+      assert!(change.is_valid(), "invalid tempo change: {:?}", change);
+    }
+
+    changes.sort_by(|a, b| a.beat.partial_cmp(&b.beat).unwrap());
+    changes[0].beat = 0.;
+
+    let mut times = Vec::with_capacity(changes.len());
+    let mut bars = Vec::with_capacity(changes.len());
+    let mut t = 0.;
+    let mut bar = 0.;
+
+    for (i, change) in changes.iter().enumerate() {
+      if i > 0 {
+        let prev = &changes[i - 1];
+        let beats = change.beat - prev.beat;
+
+        t += beats * prev.beat_duration();
+        bar += beats / prev.beats_per_bar as f32;
+      }
+
+      times.push(t);
+      bars.push(bar);
+    }
+
+    TempoMap {
+      changes: changes,
+      times: times,
+      bars: bars
+    }
+  }
+
+  /// Constant tempo.
+  pub fn constant(bpm: f32, beats_per_bar: u32) -> Self {
+    TempoMap::new(vec![TempoChange::new(0., bpm, beats_per_bar)])
+  }
+
+  pub fn changes(&self) -> &[TempoChange] {
+    &self.changes
+  }
+
+  /// Tempo in use at a given time.
+  pub fn bpm_at(&self, t: Time) -> f32 {
+    self.changes[self.segment_at_time(t)].bpm
+  }
+
+  /// Convert seconds into (fractional) beats.
+  pub fn beat_at(&self, t: Time) -> f32 {
+    let i = self.segment_at_time(t);
+    let change = &self.changes[i];
+
+    change.beat + (t - self.times[i]) / change.beat_duration()
+  }
+
+  /// Convert (fractional) beats into seconds.
+  pub fn time_at_beat(&self, beat: f32) -> Time {
+    let i = segment_at(&self.changes, |c| c.beat <= beat);
+    let change = &self.changes[i];
+
+    self.times[i] + (beat - change.beat) * change.beat_duration()
+  }
+
+  /// Convert seconds into (fractional) bars.
+  pub fn bar_at(&self, t: Time) -> f32 {
+    let i = self.segment_at_time(t);
+    let change = &self.changes[i];
+
+    self.bars[i] + (self.beat_at(t) - change.beat) / change.beats_per_bar as f32
+  }
+
+  /// Convert (fractional) bars into seconds.
+  pub fn time_at_bar(&self, bar: f32) -> Time {
+    let i = segment_at(&self.bars, |&b| b <= bar);
+    let change = &self.changes[i];
+
+    self.time_at_beat(change.beat + (bar - self.bars[i]) * change.beats_per_bar as f32)
+  }
+
+  /// Bar and fractional beat within that bar at a given time.
+  pub fn bar_beat_at(&self, t: Time) -> (i32, f32) {
+    let i = self.segment_at_time(t);
+    let bar = self.bar_at(t);
+    let whole_bar = bar.floor();
+
+    (whole_bar as i32, (bar - whole_bar) * self.changes[i].beats_per_bar as f32)
+  }
+
+  fn segment_at_time(&self, t: Time) -> usize {
+    segment_at(&self.times, |&ct| ct <= t)
+  }
+}
+
+// Index of the last element that satisfies the predicate, assuming the predicate is monotonic; if
+// no element does, the first one is used, so that times before the map are extrapolated with the
+// first tempo.
+fn segment_at<T, F>(xs: &[T], before: F) -> usize where F: Fn(&T) -> bool {
+  let happened = match xs.binary_search_by(|x| if before(x) { Ordering::Less } else { Ordering::Greater }) {
+    Ok(i) | Err(i) => i
+  };
+
+  if happened == 0 { 0 } else { happened - 1 }
+}
+
+impl<'a> Load<'a> for TempoMap {
+  type Args = ();
+
+  fn load<P>(path: P, _: &mut Cache<'a>, _: Self::Args) -> Result<Self, LoadError> where P: AsRef<Path> {
+    let path = path.as_ref();
+
+    info!("loading tempo map: {:?}", path);
+
+    let file = File::open(path).map_err(|e| LoadError::FileNotFound(path.to_path_buf(), format!("{:?}", e)))?;
+    let changes: Vec<TempoChange> = from_reader(file).map_err(|e| LoadError::ParseFailed(format!("{:?}", e)))?;
+
+    if changes.is_empty() {
+      return Err(LoadError::ParseFailed(format!("no tempo change in {:?}", path)));
+    }
+
+    // TempoMap::new panics otherwise
+    for change in &changes {
+      if !change.is_valid() {
+        return Err(LoadError::ParseFailed(format!("invalid tempo change in {:?}: {:?}", path, change)));
+      }
+    }
+
+    Ok(TempoMap::new(changes))
+  }
+}
+
+fn def_beats_per_bar() -> u32 { 4 }
@@ -12,7 +12,18 @@ use resource::{Cache, Load, LoadError, Reload, Result};
 pub fn load_rgba_texture<P>(path: P, sampler: &Sampler, linear: bool) -> Result<Texture<Flat, Dim2, RGBA32F>> where P: AsRef<Path> {
   info!("loading texture image: \x1b[35m{:?}", path.as_ref());
 
-  let image = image::open(path).map_err(|e| LoadError::ConversionFailed(format!("{:?}", e)))?.to_rgba();
+  let image = image::open(path).map_err(|e| LoadError::ConversionFailed(format!("{:?}", e)))?;
+  upload_rgba_image(image, sampler, linear)
+}
+
+/// Load an RGBA texture from an encoded image in memory – a PNG file, for instance.
+pub fn load_rgba_texture_from_memory(bytes: &[u8], sampler: &Sampler, linear: bool) -> Result<Texture<Flat, Dim2, RGBA32F>> {
+  let image = image::load_from_memory(bytes).map_err(|e| LoadError::ConversionFailed(format!("{:?}", e)))?;
+  upload_rgba_image(image, sampler, linear)
+}
+
+fn upload_rgba_image(image: image::DynamicImage, sampler: &Sampler, linear: bool) -> Result<Texture<Flat, Dim2, RGBA32F>> {
+  let image = image.to_rgba();
   let dim = image.dimensions();
   let raw: Vec<f32> = image.into_raw().into_iter().map(|x| {
     let y = x as f32 / 255.;
@@ -59,6 +70,18 @@ impl Deref for TextureImage {
   }
 }
 
+impl TextureImage {
+  /// Create a texture image from an encoded image in memory. Such an image is not hot-reloaded on
+  /// its own.
+  pub fn from_memory(bytes: &[u8], sampler: Sampler, linear: bool) -> Result<Self> {
+    load_rgba_texture_from_memory(bytes, &sampler, linear).map(|tex| TextureImage {
+      texture: tex,
+      sampler: sampler,
+      linear: linear
+    })
+  }
+}
+
 impl<'a> Load<'a> for TextureImage {
   type Args = (Sampler, bool);
 
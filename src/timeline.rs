@@ -0,0 +1,260 @@
+use serde_json::from_reader;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::Path;
+use std::rc::Rc;
+
+use app::App;
+use device::Device;
+use id::Id;
+use resource::{Cache, Get, Load, LoadError};
+use scene::Scene;
+use spline::Time;
+use tempo::TempoMap;
+
+/// Class of types that can be played on a `Timeline`.
+///
+/// A part is initialized when it becomes live, rendered every frame it’s live with a time local to
+/// its entry in the schedule, and torn down when it’s not live anymore. Seeking around the timeline
+/// honors that lifecycle.
+pub trait DemoPart<'a> {
+  /// Called when the part becomes live.
+  fn init(&mut self, _: &mut Scene<'a>) {}
+  /// Render the part. `t` is the time elapsed since the beginning of the part’s entry.
+  fn render(&mut self, scene: &mut Scene<'a>, t: Time);
+  /// Called when the part is not live anymore.
+  fn teardown(&mut self, _: &mut Scene<'a>) {}
+}
+
+/// Class of types that can drive a `Timeline`.
+pub trait TimeSource {
+  fn time(&self) -> Time;
+}
+
+impl TimeSource for App {
+  fn time(&self) -> Time {
+    App::time(self)
+  }
+}
+
+impl TimeSource for Device {
+  fn time(&self) -> Time {
+    self.playback_cursor()
+  }
+}
+
+/// An entry in a `Schedule`: a part plays in `[start; end[`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ScheduleEntry {
+  /// Name of the part to play.
+  pub part: String,
+  /// Absolute time at which the part starts.
+  pub start: Time,
+  /// Absolute time at which the part stops.
+  pub end: Time,
+  /// Parts that overlap are rendered by increasing layer.
+  #[serde(default)]
+  pub layer: i32
+}
+
+/// Schedule of the parts of a `Timeline`.
+///
+/// If the schedule is retrieved from the cache, the path must point to a JSON file of the form:
+///
+/// ```json
+/// {
+///   "parts": [
+///     { "part": "intro", "start": 0, "end": 32 },
+///     { "part": "tunnel", "start": 30, "end": 64, "layer": 1 }
+///   ]
+/// }
+/// ```
+///
+/// Overlapping entries are allowed – that’s how you get transitions.
+///
+/// If you add a `"tempo"` field naming a tempo map, `start` and `end` are in beats and are
+/// converted into seconds with that tempo map.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Schedule {
+  parts: Vec<ScheduleEntry>,
+  #[serde(default)]
+  tempo: Option<String>
+}
+
+impl Schedule {
+  pub fn new(mut entries: Vec<ScheduleEntry>) -> Self {
+    sort_entries(&mut entries);
+
+    Schedule {
+      parts: entries,
+      tempo: None
+    }
+  }
+
+  /// Entries, sorted by layer and then start time.
+  pub fn entries(&self) -> &[ScheduleEntry] {
+    &self.parts
+  }
+
+  /// Time at which the last entry ends.
+  pub fn end(&self) -> Time {
+    self.parts.iter().fold(0., |end, entry| end.max(entry.end))
+  }
+
+  /// Entries that are live at a given time.
+  pub fn live_at<'b>(&'b self, t: Time) -> impl Iterator<Item = &'b ScheduleEntry> {
+    self.parts.iter().filter(move |entry| entry.start <= t && t < entry.end)
+  }
+}
+
+impl<'a> Load<'a> for Schedule {
+  type Args = ();
+
+  fn load<P>(path: P, cache: &mut Cache<'a>, _: Self::Args) -> Result<Self, LoadError> where P: AsRef<Path> {
+    let path = path.as_ref();
+
+    info!("loading schedule: {:?}", path);
+
+    let file = File::open(path).map_err(|e| LoadError::FileNotFound(path.to_path_buf(), format!("{:?}", e)))?;
+    let mut schedule: Schedule = from_reader(file).map_err(|e| LoadError::ParseFailed(format!("{:?}", e)))?;
+
+    // convert beats into seconds
+    if let Some(ref tempo_name) = schedule.tempo {
+      let tempo: Rc<TempoMap> = cache.get(tempo_name, ()).ok_or(LoadError::ConversionFailed(format!("unable to find tempo map {} for schedule at {:?}", tempo_name, path)))?;
+      cache.add_resource_dependency::<TempoMap>(tempo_name);
+
+      for entry in &mut schedule.parts {
+        entry.start = tempo.time_at_beat(entry.start);
+        entry.end = tempo.time_at_beat(entry.end);
+      }
+    }
+
+    Ok(Schedule::new(schedule.parts))
+  }
+}
+
+fn sort_entries(entries: &mut Vec<ScheduleEntry>) {
+  entries.sort_by(|a, b| {
+    match a.layer.cmp(&b.layer) {
+      Ordering::Equal => a.start.partial_cmp(&b.start).unwrap(),
+      ordering => ordering
+    }
+  });
+}
+
+struct PartSlot<'a> {
+  part: Box<DemoPart<'a> + 'a>,
+  live: bool
+}
+
+/// Timeline sequencer.
+///
+/// A timeline plays named parts according to a `Schedule`. It doesn’t own any notion of time: you
+/// have to drive it with whatever time you want – `App::time`, `Device::playback_cursor` or your
+/// own – by calling `update` or `drive` every frame.
+pub struct Timeline<'a> {
+  schedule: Rc<Schedule>,
+  schedule_id: Option<Id<'a, Schedule>>,
+  parts: HashMap<String, PartSlot<'a>>
+}
+
+impl<'a> Timeline<'a> {
+  pub fn new(schedule: Schedule) -> Self {
+    Timeline {
+      schedule: Rc::new(schedule),
+      schedule_id: None,
+      parts: HashMap::new()
+    }
+  }
+
+  /// Create a timeline which schedule lives in the cache. Any change to the schedule is picked up
+  /// at the next update.
+  pub fn from_cache(scene: &mut Scene<'a>, name: &str) -> Option<Self> {
+    scene.get_id::<Schedule>(name, ()).and_then(|id| {
+      scene.get_by_id(&id).map(|schedule| {
+        Timeline {
+          schedule: schedule,
+          schedule_id: Some(id),
+          parts: HashMap::new()
+        }
+      })
+    })
+  }
+
+  /// Register a part under a name. The name is the one used in the schedule.
+  pub fn add_part<P>(&mut self, name: &str, part: P) where P: 'a + DemoPart<'a> {
+    self.parts.insert(name.to_owned(), PartSlot {
+      part: Box::new(part),
+      live: false
+    });
+  }
+
+  pub fn schedule(&self) -> &Schedule {
+    &self.schedule
+  }
+
+  /// Update the timeline at a given absolute time.
+  ///
+  /// Parts that stop being live are torn down, parts that become live are initialized and then all
+  /// live parts are rendered, in schedule order. The scene is moved on to the next frame first – see
+  /// `Scene::next_frame`.
+  pub fn update(&mut self, scene: &mut Scene<'a>, t: Time) {
+    scene.next_frame(t);
+
+    // follow schedule reloading
+    if let Some(ref id) = self.schedule_id {
+      if let Some(schedule) = scene.get_by_id(id) {
+        if &*schedule as *const Schedule != &*self.schedule as *const Schedule {
+          deb!("timeline schedule has changed");
+          self.schedule = schedule;
+          self.check_parts();
+        }
+      }
+    }
+
+    let schedule = self.schedule.clone();
+
+    for (name, slot) in &mut self.parts {
+      if slot.live && !schedule.live_at(t).any(|entry| &entry.part == name) {
+        slot.part.teardown(scene);
+        slot.live = false;
+      }
+    }
+
+    for entry in schedule.live_at(t) {
+      if let Some(slot) = self.parts.get_mut(&entry.part) {
+        if !slot.live {
+          slot.part.init(scene);
+          slot.live = true;
+        }
+
+        slot.part.render(scene, t - entry.start);
+      }
+    }
+  }
+
+  /// Update the timeline with the current time of a time source.
+  pub fn drive<S>(&mut self, scene: &mut Scene<'a>, source: &S) where S: TimeSource {
+    self.update(scene, source.time());
+  }
+
+  /// Tear down all live parts.
+  pub fn stop(&mut self, scene: &mut Scene<'a>) {
+    for slot in self.parts.values_mut() {
+      if slot.live {
+        slot.part.teardown(scene);
+        slot.live = false;
+      }
+    }
+  }
+
+  // Warn about parts that are scheduled but not registered.
+  fn check_parts(&self) {
+    for entry in self.schedule.entries() {
+      if !self.parts.contains_key(&entry.part) {
+        warn!("part {} is scheduled but wasn’t added to the timeline", entry.part);
+      }
+    }
+  }
+}
@@ -1,9 +1,42 @@
 extern crate rand;
 extern crate spectra;
+extern crate wavefront_obj;
 
 use rand::{Rng, thread_rng};
-use spectra::linear::{UnitQuaternion, Quaternion};
+use spectra::anim::{Boundary, Cont};
+use spectra::event::{Envelope, EnvelopeShape, EventTrack};
+use spectra::scene::Scene;
+use spectra::extra::{new_cube, new_plane};
+use spectra::extra::shadertoy::{Channel, ShadertoyProgram};
+use spectra::extra::terrain::{Heightmap, TerrainArgs, fractal_noise, terrain_meshes};
+use spectra::mesh::{Bounds, MeshData};
+use spectra::mesh::binary;
+use spectra::mesh::shapes::*;
+use spectra::mesh::vector;
+use spectra::mesh::optimize::{CACHE_SIZE, acmr, optimize, optimize_vertex_cache, optimize_vertex_fetch, weld};
+use spectra::model::{Model, ModelData, Normals, Vertex, convert_obj, generate_tangents, skinned_mesh};
+use spectra::morph::{MorphMesh, MorphTarget};
+use spectra::material::{Material, MaterialLibrary};
+use spectra::luminance::Mode;
+use spectra::glsl::{Severity, UniformDecl, evaluate, program_uniforms, uniforms, validate, validate_stage};
+use spectra::gltf::{Gltf, Primitive, decode_base64, parse_glb};
+use spectra::resource::{Cache, Get, Load, LoadError};
+use spectra::shader::{Program, ProgramArgs, StageKind, StageSources, fallback_sources, preprocess};
+use spectra::skeleton::{Joint, JointTransform, Skeleton};
+use spectra::tempo::{TempoChange, TempoMap};
+use spectra::texture::TextureImage;
+use spectra::timeline::*;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::env;
+use std::fs::{File, create_dir_all};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use wavefront_obj::obj;
+use spectra::linear::{UnitQuaternion, Quaternion, Vector3};
 use spectra::spline::*;
+use spectra::transform::Scale;
 
 #[test]
 fn sampler_hold() {
@@ -60,3 +93,1345 @@ fn keys_sorted() {
     t = key.t;
   }
 }
+
+#[test]
+fn cont_from_discrete() {
+  let mut c = Cont::from_discrete(0, vec![(10., 2), (0., 1), (20., 3)]);
+
+  assert_eq!(c.at(-1.), 0);
+  assert_eq!(c.at(0.), 1);
+  assert_eq!(c.at(5.), 1);
+  assert_eq!(c.at(10.), 2);
+  assert_eq!(c.at(19.), 2);
+  assert_eq!(c.at(1000.), 3);
+}
+
+#[test]
+fn cont_time_combinators() {
+  assert_eq!(Cont::new(|t| t).offset(2.).at(5.), 3.);
+  assert_eq!(Cont::new(|t| t).scale(2.).at(5.), 10.);
+  assert_eq!(Cont::new(|t| t).clamp(1., 4.).at(5.), 4.);
+  assert_eq!(Cont::new(|t| t).clamp(1., 4.).at(-5.), 1.);
+  assert_eq!(Cont::new(|t| t).looped(4.).at(5.), 1.);
+  assert_eq!(Cont::new(|t| t).looped(4.).at(-1.), 3.);
+  assert_eq!(Cont::new(|t| t).reverse(10.).at(3.), 7.);
+}
+
+#[test]
+fn cont_switch_blend() {
+  let mut s = Cont::constant(1.).switch(10., Cont::constant(2.));
+
+  assert_eq!(s.at(9.), 1.);
+  assert_eq!(s.at(10.), 2.);
+
+  let mut b = Cont::blend(Cont::constant(0.), Cont::constant(10.), Cont::new(|t| t));
+
+  assert_eq!(b.at(0.), 0.);
+  assert_eq!(b.at(0.5), 5.);
+  assert_eq!(b.at(1.), 10.);
+}
+
+#[test]
+fn cont_map_zip_apply() {
+  let mut m = Cont::new(|t| t).map(|x| x * 3.);
+  assert_eq!(m.at(2.), 6.);
+
+  let mut z = Cont::new(|t| t).zip(Cont::constant(true));
+  assert_eq!(z.at(2.), (2., true));
+
+  let mut a = Cont::new(|_| |x: f32| x + 1.).apply(Cont::new(|t| t));
+  assert_eq!(a.at(2.), 3.);
+}
+
+#[test]
+fn cont_from_spline() {
+  let spline = Spline::new(vec![
+    Key::new(0., 0., Interpolation::Linear),
+    Key::new(10., 10., Interpolation::Linear)
+  ]);
+
+  let mut def = Cont::from_spline(&spline, Boundary::Default(-1.));
+  assert_eq!(def.at(-1.), -1.);
+  assert_eq!(def.at(5.), 5.);
+  assert_eq!(def.at(11.), -1.);
+
+  let mut hold = Cont::from_spline(&spline, Boundary::Hold(-1.));
+  assert_eq!(hold.at(-1.), 0.);
+  assert_eq!(hold.at(11.), 10.);
+}
+
+#[test]
+fn cont_bake() {
+  let spline = Cont::new(|t| t * 2.).bake(0., 1., 0.25, Interpolation::Linear);
+  let keys: Vec<_> = spline.keys().iter().map(|k| (k.t, k.value)).collect();
+
+  assert_eq!(keys, vec![(0., 0.), (0.25, 0.5), (0.5, 1.), (0.75, 1.5), (1., 2.)]);
+}
+
+struct RecordingPart(&'static str, Rc<RefCell<Vec<String>>>);
+
+impl<'a> DemoPart<'a> for RecordingPart {
+  fn init(&mut self, _: &mut Scene<'a>) {
+    self.1.borrow_mut().push(format!("init {}", self.0));
+  }
+
+  fn render(&mut self, _: &mut Scene<'a>, t: Time) {
+    self.1.borrow_mut().push(format!("render {} {}", self.0, t));
+  }
+
+  fn teardown(&mut self, _: &mut Scene<'a>) {
+    self.1.borrow_mut().push(format!("teardown {}", self.0));
+  }
+}
+
+#[test]
+fn timeline_lifecycle() {
+  let log = Rc::new(RefCell::new(Vec::new()));
+  let mut scene = Scene::new("data");
+  let mut timeline = Timeline::new(Schedule::new(vec![
+    ScheduleEntry { part: "b".to_owned(), start: 8., end: 16., layer: 0 },
+    ScheduleEntry { part: "a".to_owned(), start: 0., end: 10., layer: 0 }
+  ]));
+
+  timeline.add_part("a", RecordingPart("a", log.clone()));
+  timeline.add_part("b", RecordingPart("b", log.clone()));
+
+  timeline.update(&mut scene, 1.);
+  timeline.update(&mut scene, 9.);
+  timeline.update(&mut scene, 12.);
+  timeline.update(&mut scene, 2.); // seek backwards
+
+  assert_eq!(*log.borrow(), vec![
+    "init a", "render a 1",
+    "render a 9", "init b", "render b 1",
+    "teardown a", "render b 4",
+    "teardown b", "init a", "render a 2"
+  ]);
+}
+
+#[test]
+fn tempo_map_conversions() {
+  // 120 BPM in 4/4 for 8 bars, then 240 BPM in 3/4
+  let tempo = TempoMap::new(vec![
+    TempoChange::new(32., 240., 3),
+    TempoChange::new(0., 120., 4)
+  ]);
+
+  assert_eq!(tempo.beat_at(0.), 0.);
+  assert_eq!(tempo.beat_at(1.), 2.);
+  assert_eq!(tempo.beat_at(16.), 32.);
+  assert_eq!(tempo.beat_at(17.), 36.);
+  assert_eq!(tempo.bpm_at(15.), 120.);
+  assert_eq!(tempo.bpm_at(16.), 240.);
+
+  assert_eq!(tempo.time_at_beat(2.), 1.);
+  assert_eq!(tempo.time_at_beat(36.), 17.);
+
+  assert_eq!(tempo.bar_at(16.), 8.);
+  assert_eq!(tempo.bar_at(17.), 8. + 4. / 3.);
+  assert_eq!(tempo.time_at_bar(8.), 16.);
+  assert_eq!(tempo.time_at_bar(9.), 16.75);
+
+  let (bar, beat) = tempo.bar_beat_at(17.25);
+  assert_eq!(bar, 9);
+  assert!((beat - 2.).abs() < 1e-5, "beat: {}", beat);
+}
+
+#[test]
+fn tempo_map_validation() {
+  let root = temp_files("tempo_map_validation", &[
+    ("good.json", "[{ \"beat\": 0, \"bpm\": 120 }]"),
+    ("empty.json", "[]"),
+    ("null_bpm.json", "[{ \"beat\": 0, \"bpm\": 0 }]"),
+    ("negative_bpm.json", "[{ \"beat\": 0, \"bpm\": -60 }]"),
+    ("null_bar.json", "[{ \"beat\": 0, \"bpm\": 120, \"beats_per_bar\": 0 }]")
+  ]);
+  let mut cache = Cache::new(&root);
+
+  assert!(TempoMap::load(root.join("good.json"), &mut cache, ()).is_ok());
+
+  for name in &["empty.json", "null_bpm.json", "negative_bpm.json", "null_bar.json"] {
+    match TempoMap::load(root.join(name), &mut cache, ()) {
+      Err(LoadError::ParseFailed(_)) => {},
+      _ => panic!("{} should fail to parse", name)
+    }
+  }
+}
+
+#[test]
+#[should_panic]
+fn tempo_map_null_bpm() {
+  TempoMap::constant(0., 4);
+}
+
+#[test]
+#[should_panic]
+fn tempo_map_infinite_bpm() {
+  TempoMap::new(vec![TempoChange::new(0., 120., 4), TempoChange::new(16., ::std::f32::INFINITY, 4)]);
+}
+
+#[test]
+fn resource_paths() {
+  let cache = Cache::new("data");
+
+  assert_eq!(Get::<TempoMap>::path(&cache, "song.json"), Some(PathBuf::from("data/tempos/song.json")));
+  assert_eq!(Get::<TextureImage>::path(&cache, "wood.png"), Some(PathBuf::from("data/textures/wood.png")));
+  assert_eq!(Get::<Model>::path(&cache, "builtin:cube"), None);
+}
+
+#[test]
+fn event_track_queries() {
+  let mut events = HashMap::new();
+  events.insert("kick".to_owned(), vec![2., 0., 1.]);
+  let track = EventTrack::new(events, None);
+
+  assert_eq!(track.count("kick", -1.), 0);
+  assert_eq!(track.count("kick", 0.), 1);
+  assert_eq!(track.count("kick", 1.5), 2);
+  assert_eq!(track.count("kick", 10.), 3);
+  assert_eq!(track.count("snare", 10.), 0);
+
+  assert_eq!(track.since_last("kick", -1.), None);
+  assert_eq!(track.since_last("kick", 1.5), Some(0.5));
+  assert_eq!(track.since_last("kick", 10.), Some(8.));
+
+  let env = Envelope::new(0., 1., EnvelopeShape::Linear);
+  assert_eq!(track.envelope("kick", 1.25, &env), 0.75);
+  assert_eq!(track.envelope("kick", 10., &env), 0.);
+  assert_eq!(track.envelope("snare", 1., &env), 0.);
+}
+
+#[test]
+fn event_track_validation() {
+  let root = temp_files("event_track_validation", &[
+    ("good.json", "{ \"loop\": 4, \"events\": { \"kick\": [0, 1] } }"),
+    ("null_loop.json", "{ \"loop\": 0, \"events\": { \"kick\": [0, 1] } }"),
+    ("negative_loop.json", "{ \"loop\": -2, \"events\": { \"kick\": [0, 1] } }"),
+    ("negative_time.json", "{ \"events\": { \"kick\": [-1, 1] } }"),
+    ("past_loop.json", "{ \"loop\": 4, \"events\": { \"kick\": [0, 4] } }")
+  ]);
+  let mut cache = Cache::new(&root);
+
+  assert!(EventTrack::load(root.join("good.json"), &mut cache, ()).is_ok());
+
+  for name in &["null_loop.json", "negative_loop.json", "negative_time.json", "past_loop.json"] {
+    match EventTrack::load(root.join(name), &mut cache, ()) {
+      Err(LoadError::ParseFailed(_)) => {},
+      _ => panic!("{} should fail to parse", name)
+    }
+  }
+}
+
+#[test]
+fn event_track_looping() {
+  let mut events = HashMap::new();
+  events.insert("kick".to_owned(), vec![1., 3.]);
+  let track = EventTrack::new(events, Some(4.));
+
+  assert_eq!(track.count("kick", 0.5), 0);
+  assert_eq!(track.count("kick", 3.), 2);
+  assert_eq!(track.count("kick", 9.), 5);
+  assert_eq!(track.since_last("kick", 0.5), None);
+  assert_eq!(track.since_last("kick", 4.5), Some(1.5));
+  assert_eq!(track.since_last("kick", 5.5), Some(0.5));
+
+  // seeking backwards
+  assert_eq!(track.count("kick", 1.), 1);
+}
+
+#[test]
+fn envelope_shapes() {
+  for &shape in &[EnvelopeShape::Linear, EnvelopeShape::Exponential, EnvelopeShape::Smooth] {
+    let env = Envelope::new(0.5, 1., shape);
+
+    assert_eq!(env.value(-1.), 0.);
+    assert!(env.value(0.) < 1e-5);
+    assert!((env.value(0.5) - 1.).abs() < 1e-5);
+    assert!(env.value(1.) > 0. && env.value(1.) < 1.);
+    assert!(env.value(1.5).abs() < 1e-5);
+    assert_eq!(env.value(2.), 0.);
+  }
+
+  // invalid durations are null
+  let env = Envelope::new(-1., ::std::f32::NAN, EnvelopeShape::Linear);
+  assert_eq!((env.attack, env.decay), (0., 0.));
+  assert_eq!(env.value(0.), 0.);
+
+  let env = Envelope { attack: -1., decay: 1., shape: EnvelopeShape::Linear };
+  assert_eq!(env.value(0.), 1.);
+  assert_eq!(env.value(0.5), 0.5);
+}
+
+// Create a set of files in a fresh temporary directory and return that directory.
+fn temp_files(name: &str, files: &[(&str, &str)]) -> PathBuf {
+  let root = env::temp_dir().join("spectra-tests").join(name);
+
+  for &(path, content) in files {
+    let path = root.join(path);
+    create_dir_all(path.parent().unwrap()).unwrap();
+    File::create(path).unwrap().write_all(content.as_bytes()).unwrap();
+  }
+
+  root
+}
+
+#[test]
+fn shader_includes() {
+  let root = temp_files("shader_includes", &[
+    ("main.glsl", "#vs\n#include \"lib/a.glsl\"\nvoid main() {}\n#fs\n#include \"lib/a.glsl\"\n#include \"lib/b.glsl\"\n"),
+    ("lib/a.glsl", "float a() { return 1.; }"),
+    ("lib/b.glsl", "#include \"lib/a.glsl\"\nfloat b() { return a(); }")
+  ]);
+
+  let sources = preprocess(root.join("main.glsl"), &root).unwrap();
+
+  assert_eq!(sources.files, vec![root.join("main.glsl"), root.join("lib/a.glsl"), root.join("lib/b.glsl")]);
+  assert_eq!(sources.vs, "#line 1 1\nfloat a() { return 1.; }\n#line 3 0\nvoid main() {}\n");
+  // a is included only once in the fragment stage
+  assert_eq!(sources.fs, "#line 1 1\nfloat a() { return 1.; }\n#line 2 2\nfloat b() { return a(); }\n");
+}
+
+#[test]
+fn shader_include_cycle() {
+  let root = temp_files("shader_include_cycle", &[
+    ("main.glsl", "#fs\n#include \"a.glsl\"\n"),
+    ("a.glsl", "#include \"b.glsl\""),
+    ("b.glsl", "#include \"a.glsl\"")
+  ]);
+
+  assert!(preprocess(root.join("main.glsl"), &root).is_err());
+  assert!(preprocess(Path::new("/nonexistent/main.glsl"), &root).is_err());
+}
+
+#[test]
+fn shader_manifest() {
+  let root = temp_files("shader_manifest", &[
+    ("blur.json", "{ \"vs\": \"common/quad.vert\", \"fs\": \"blur.frag\" }"),
+    ("common/quad.vert", "#include \"common/lib.glsl\"\nvoid main() {}"),
+    ("common/lib.glsl", "float a() { return 1.; }"),
+    ("blur.frag", "void main() {}"),
+    ("broken.json", "{ \"vs\": \"blur.frag\", \"fs\": \"pragma.frag\" }"),
+    ("pragma.frag", "#fs\nvoid main() {}")
+  ]);
+
+  let sources = preprocess(root.join("blur.json"), &root).unwrap();
+
+  assert_eq!(sources.files, vec![root.join("blur.json"), root.join("common/quad.vert"), root.join("common/lib.glsl"), root.join("blur.frag")]);
+  assert_eq!(sources.vs, "#line 1 2\nfloat a() { return 1.; }\n#line 2 1\nvoid main() {}\n");
+  assert_eq!(sources.fs, "#line 1 3\nvoid main() {}\n");
+  assert_eq!(sources.gs, "");
+
+  assert!(preprocess(root.join("broken.json"), &root).is_err());
+}
+
+#[test]
+fn shader_defines() {
+  let mut sources = StageSources::default();
+  sources.vs = "#line 1 0\n#version 330 core\n#line 2 0\nvoid main() {}\n".to_owned();
+  sources.fs = "#line 1 0\nvoid main() {}\n".to_owned();
+
+  let args = ProgramArgs::new(Vec::new()).define("SHADOWS", "4").define("DEBUG", "");
+  sources.define(&args.defines);
+
+  assert_eq!(sources.vs, "#line 1 0\n#version 330 core\n#define DEBUG \n#define SHADOWS 4\n#line 2 0\nvoid main() {}\n");
+  assert_eq!(sources.fs, "#define DEBUG \n#define SHADOWS 4\n#line 1 0\nvoid main() {}\n");
+  assert_eq!(sources.gs, "");
+
+  assert_eq!(Program::cache_key("a.glsl", &ProgramArgs::new(Vec::new())), "a.glsl");
+  assert_eq!(Program::cache_key("a.glsl", &args), "a.glsl?DEBUG&SHADOWS=4");
+
+  // the other arguments change the built program too
+  let mut args = args.fullscreen();
+  args.interface.push(("t", "float"));
+  args.interface.push(("color", "vec3"));
+
+  assert_eq!(Program::cache_key("a.glsl", &args), "a.glsl?DEBUG&SHADOWS=4&:fullscreen&:interface=t:float,color:vec3");
+  assert_eq!(Program::cache_key("a.glsl", &ProgramArgs::discover().fullscreen()), "a.glsl?:fullscreen");
+}
+
+#[test]
+fn glsl_validation() {
+  let files = vec![PathBuf::from("main.glsl"), PathBuf::from("lib.glsl")];
+  let ok = "#line 1 0\nuniform float t; // comment\n#line 1 1\n/* multi\nline */ void main() {\n  float a = t;\n}\n";
+
+  assert_eq!(validate_stage(StageKind::FS, ok, &files), vec![]);
+
+  let missing_semicolon = "#line 4 1\nvoid main() {\n  float a = 1.\n  float b = 2.;\n}\n";
+  let diagnostics = validate_stage(StageKind::VS, missing_semicolon, &files);
+
+  assert_eq!(diagnostics.len(), 1);
+  assert_eq!(diagnostics[0].severity, Severity::Warning);
+  assert_eq!(diagnostics[0].file, PathBuf::from("lib.glsl"));
+  assert_eq!(diagnostics[0].stage, StageKind::VS);
+  assert_eq!(diagnostics[0].line, 5);
+  assert_eq!(diagnostics[0].snippet, "float a = 1.");
+
+  let unbalanced = "#line 1 0\nvoid main() {\n  if (true) {\n}\n";
+  let diagnostics = validate_stage(StageKind::FS, unbalanced, &files);
+
+  assert_eq!(diagnostics.len(), 1);
+  assert_eq!(diagnostics[0].severity, Severity::Error);
+  assert_eq!(diagnostics[0].line, 1);
+
+  let no_main = "#line 1 0\nvoid foo() {}\n";
+  let diagnostics = validate_stage(StageKind::FS, no_main, &files);
+
+  assert_eq!(diagnostics.len(), 1);
+  assert!(diagnostics[0].is_error());
+
+  // only the branches taken are checked
+  let conditional = "#line 1 0\n#if N > 1 && defined(A)\nvoid main() {}\n#elif N == 1\nvoid main() {\n#else\n#error no variant\n#endif\n";
+
+  assert_eq!(validate_stage(StageKind::FS, &format!("#define N 2\n#define A\n{}", conditional), &files), vec![]);
+
+  let diagnostics = validate_stage(StageKind::FS, &format!("#define N 1\n{}", conditional), &files);
+
+  assert_eq!(diagnostics.len(), 1);
+  assert_eq!(diagnostics[0].message, "unclosed `{`");
+  assert_eq!(diagnostics[0].line, 4);
+
+  let diagnostics = validate_stage(StageKind::FS, &format!("#define N 0\n{}", conditional), &files);
+
+  assert_eq!(diagnostics.len(), 1);
+  assert_eq!(diagnostics[0].message, "#error no variant");
+  assert_eq!(diagnostics[0].line, 6);
+
+  // N is undefined
+  let diagnostics = validate_stage(StageKind::FS, conditional, &files);
+
+  assert!(diagnostics.iter().any(|diagnostic| diagnostic.is_error() && diagnostic.line == 1));
+
+  let unterminated = "#line 1 0\n#ifdef A\nvoid main() {}\n";
+  assert!(validate_stage(StageKind::FS, unterminated, &files)[0].is_error());
+}
+
+#[test]
+fn preprocessor_expressions() {
+  let mut macros = HashMap::new();
+  macros.insert("N", Some("4"));
+  macros.insert("M", Some("(N + 1) * 2"));
+  macros.insert("EMPTY", Some(""));
+  macros.insert("F", None);
+
+  assert_eq!(evaluate("N == 4 && M == 10", &macros), Ok(1));
+  assert_eq!(evaluate("defined N && !defined(X)", &macros), Ok(1));
+  assert_eq!(evaluate("0x10 + 010 - 1u", &macros), Ok(23));
+  assert_eq!(evaluate("-N << 1 | 1", &macros), Ok(-7));
+  assert_eq!(evaluate("defined(X) && X > 1", &macros), Ok(0));
+  assert_eq!(evaluate("GL_ARB_shading_language_420pack", &macros), Ok(0));
+  assert!(evaluate("X > 1", &macros).is_err());
+  assert!(evaluate("EMPTY", &macros).is_err());
+  assert!(evaluate("F(1)", &macros).is_err());
+  assert!(evaluate("N / 0", &macros).is_err());
+  assert!(evaluate("(N", &macros).is_err());
+}
+
+#[test]
+fn uniform_discovery() {
+  let src = "#version 330 core\nlayout (std140) uniform Lights {\n  vec4 pos[8];\n};\nuniform highp mat4 proj, view;\nuniform float weights[4];\nuniform sampler2D tex;\nvoid main() {\n  float uniform_like = 1.;\n}\n";
+
+  assert_eq!(uniforms(src), vec![
+    UniformDecl::new("proj", "mat4"),
+    UniformDecl::new("view", "mat4"),
+    UniformDecl::new("weights", "float[4]"),
+    UniformDecl::new("tex", "sampler2D")
+  ]);
+
+  let mut sources = StageSources::default();
+  sources.vs = "uniform mat4 proj;\nuniform float t;\n".to_owned();
+  sources.fs = "uniform float t;\nuniform vec3 color;\n".to_owned();
+
+  assert_eq!(program_uniforms(&sources), Ok(vec![
+    UniformDecl::new("proj", "mat4"),
+    UniformDecl::new("t", "float"),
+    UniformDecl::new("color", "vec3")
+  ]));
+
+  sources.fs = "uniform int t;\n".to_owned();
+  assert!(program_uniforms(&sources).is_err());
+
+  // only the uniforms of the variant
+  let variant = "#ifdef SHADOWS\nuniform sampler2D shadows;\n#endif\nuniform float t;\n";
+
+  assert_eq!(uniforms(variant), vec![UniformDecl::new("t", "float")]);
+  assert_eq!(uniforms(&format!("#define SHADOWS\n{}", variant)), vec![
+    UniformDecl::new("shadows", "sampler2D"),
+    UniformDecl::new("t", "float")
+  ]);
+}
+
+#[test]
+fn scene_builtins() {
+  let mut scene = Scene::new("data");
+
+  scene.next_frame(1.);
+  assert_eq!(scene.builtins.time, 1.);
+  assert_eq!(scene.builtins.beat, 0.);
+  assert_eq!(scene.builtins.frame, 0);
+
+  scene.set_tempo(Some(Rc::new(TempoMap::constant(120., 4))));
+  scene.next_frame(1.5);
+  assert_eq!(scene.builtins.beat, 3.);
+  assert_eq!(scene.builtins.frame, 1);
+}
+
+#[test]
+fn scene_frames_without_timeline() {
+  let mut scene = Scene::new("data");
+
+  // renderers and compositors start the frame only once
+  scene.begin_frame();
+  scene.begin_frame();
+  assert_eq!(scene.builtins.frame, 0);
+  assert!(scene.builtins.time >= 0.);
+
+  scene.end_frame();
+  scene.begin_frame();
+  assert_eq!(scene.builtins.frame, 1);
+  scene.end_frame();
+
+  // an explicit time isn’t overridden
+  scene.next_frame(5.);
+  scene.begin_frame();
+  assert_eq!(scene.builtins.time, 5.);
+  assert_eq!(scene.builtins.frame, 2);
+}
+
+#[test]
+fn mtl_parsing() {
+  let src = "# exported\nnewmtl wood\nKa 0.1 0.1 0.1\nKd 0.8 0.5 0.2\nKs 1\nNs 32 # shiny\nd 0.5\nmap_Kd -s 2 2 1 wood.png\nbump wood_n.png\nillum 2\n\nnewmtl glow\nKe 1 0 0\nTr 0.25\n";
+  let library = MaterialLibrary::parse(src).unwrap();
+
+  let mut wood = Material::new("wood");
+  wood.ambient = [0.1, 0.1, 0.1];
+  wood.diffuse = [0.8, 0.5, 0.2];
+  wood.specular = [1., 1., 1.];
+  wood.shininess = 32.;
+  wood.opacity = 0.5;
+  wood.diffuse_map = Some("wood.png".to_owned());
+  wood.bump_map = Some("wood_n.png".to_owned());
+
+  let mut glow = Material::new("glow");
+  glow.emissive = [1., 0., 0.];
+  glow.opacity = 0.75;
+
+  assert_eq!(library.materials(), &[wood.clone(), glow][..]);
+  assert_eq!(library.index_of("glow"), Some(1));
+  assert_eq!(library.get("wood").unwrap().maps(), vec!["wood.png", "wood_n.png"]);
+  assert!(wood.is_color_map("wood.png"));
+  assert!(!wood.is_color_map("wood_n.png"));
+
+  assert!(MaterialLibrary::parse("Kd 1 1 1\n").is_err());
+  assert!(MaterialLibrary::parse("newmtl a\nKd 1 1\n").is_err());
+}
+
+fn assert_tangent(actual: [f32; 4], expected: [f32; 4]) {
+  for i in 0..4 {
+    assert!((actual[i] - expected[i]).abs() < 1e-5, "{:?} != {:?}", actual, expected);
+  }
+}
+
+#[test]
+fn tangent_generation() {
+  let n = [0., 0., 1.];
+  let back = [0., 0., -1.];
+  let tilted = [0., 0.6, 0.8];
+
+  // reference tangents, computed with MikkTSpace – through bevy_mikktspace 0.15.3
+  let fixtures: Vec<([Vertex; 3], [f32; 4])> = vec![
+    // texture space aligned with the object space
+    ([([0., 0., 0.], n, [0., 0.]), ([1., 0., 0.], n, [1., 0.]), ([0., 1., 0.], n, [0., 1.])], [1., 0., 0., 1.]),
+    // mirrored u
+    ([([0., 0., 0.], n, [0., 0.]), ([1., 0., 0.], n, [-1., 0.]), ([0., 1., 0.], n, [0., 1.])], [-1., 0., 0., -1.]),
+    // rotated texture space
+    ([([0., 0., 0.], n, [0., 0.]), ([0., 1., 0.], n, [1., 0.]), ([-1., 0., 0.], n, [0., 1.])], [0., 1., 0., 1.]),
+    // sheared texture space
+    ([([0., 0., 0.], n, [0., 0.]), ([2., 0., 0.], n, [1., 0.]), ([1., 2., 0.], n, [0., 1.])], [1., 0., 0., 1.]),
+    // wound against its normals: the sign only depends on the texture space
+    ([([0., 0., 0.], n, [0., 0.]), ([0., 1., 0.], n, [0., 1.]), ([1., 0., 0.], n, [1., 0.])], [1., 0., 0., -1.]),
+    // normals facing away from the winding
+    ([([0., 0., 0.], back, [0., 0.]), ([1., 0., 0.], back, [1., 0.]), ([0., 1., 0.], back, [0., 1.])], [1., 0., 0., 1.]),
+    // normals tilted off the triangle plane
+    ([([0., 0., 0.], tilted, [0., 0.]), ([1., 0., 0.], tilted, [1., 0.]), ([0., 1., 0.], tilted, [0., 1.])], [1., 0., 0., 1.])
+  ];
+
+  for (triangle, expected) in fixtures {
+    let (vertices, indices) = generate_tangents(&triangle, &[0, 1, 2]);
+
+    assert_eq!(indices, vec![0, 1, 2]);
+
+    for v in &vertices {
+      assert_tangent(v.3, expected);
+    }
+  }
+
+  // a vertex shared by triangles with opposite orientations is split; MikkTSpace gives +X, 1 to the
+  // corners of the first triangle and -X, -1 to the ones of the second
+  let vertices = [
+    ([0., 0., 0.], n, [0., 0.]),
+    ([1., 0., 0.], n, [1., 0.]),
+    ([0., 1., 0.], n, [0., 1.]),
+    ([-1., 0., 0.], n, [1., 0.])
+  ];
+  let (tangent_vertices, indices) = generate_tangents(&vertices, &[0, 1, 2, 0, 2, 3]);
+
+  assert_eq!(tangent_vertices.len(), 6);
+  assert_eq!(indices, vec![0, 1, 2, 3, 4, 5]);
+  assert_tangent(tangent_vertices[0].3, [1., 0., 0., 1.]);
+  assert_tangent(tangent_vertices[3].3, [-1., 0., 0., -1.]);
+
+  // triangles with the same orientation share their vertices; MikkTSpace gives +X, 1 everywhere
+  let quad = [
+    ([0., 0., 0.], n, [0., 0.]),
+    ([1., 0., 0.], n, [1., 0.]),
+    ([0., 1., 0.], n, [0., 1.]),
+    ([1., 1., 0.], n, [1., 1.])
+  ];
+  let (tangent_vertices, indices) = generate_tangents(&quad, &[0, 1, 2, 1, 3, 2]);
+
+  assert_eq!(tangent_vertices.len(), 4);
+  assert_eq!(indices, vec![0, 1, 2, 1, 3, 2]);
+
+  for v in &tangent_vertices {
+    assert_tangent(v.3, [1., 0., 0., 1.]);
+  }
+}
+
+const GLTF_TRIANGLE: &'static str = r#"{
+  "asset": { "version": "2.0" },
+  "scene": 0,
+  "scenes": [{ "nodes": [0] }],
+  "nodes": [
+    { "name": "root", "children": [1], "translation": [1, 2, 3] },
+    { "name": "triangle", "mesh": 0, "matrix": [2, 0, 0, 0, 0, 2, 0, 0, 0, 0, 2, 0, 4, 5, 6, 1] }
+  ],
+  "meshes": [{ "primitives": [{ "attributes": { "POSITION": 0 }, "indices": 1 }] }],
+  "buffers": [{
+    "byteLength": 76,
+    "uri": "data:application/octet-stream;base64,AAAAAAAAAAAAAAAAAACAPwAAAAAAAAAAAAAAAAAAgD8AAAAAAAABAAIAAAAAAAAAAACAPwAAAAAAAAAAAAAAAAAAAEAAAAAAAAAAAA=="
+  }],
+  "bufferViews": [
+    { "buffer": 0, "byteOffset": 0, "byteLength": 36 },
+    { "buffer": 0, "byteOffset": 36, "byteLength": 6 },
+    { "buffer": 0, "byteOffset": 44, "byteLength": 32 }
+  ],
+  "accessors": [
+    { "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3" },
+    { "bufferView": 1, "componentType": 5123, "count": 3, "type": "SCALAR" },
+    { "bufferView": 2, "componentType": 5126, "count": 2, "type": "SCALAR" },
+    { "bufferView": 2, "byteOffset": 8, "componentType": 5126, "count": 2, "type": "VEC3" }
+  ],
+  "materials": [{ "name": "red", "pbrMetallicRoughness": { "baseColorFactor": [1, 0, 0, 1] } }],
+  "animations": [{
+    "channels": [{ "sampler": 0, "target": { "node": 0, "path": "translation" } }],
+    "samplers": [{ "input": 2, "output": 3 }]
+  }]
+}"#;
+
+#[test]
+fn base64_decoding() {
+  assert_eq!(decode_base64("c3BlY3RyYQ==").unwrap(), b"spectra".to_vec());
+  assert_eq!(decode_base64("c3BlY3RyYQ").unwrap(), b"spectra".to_vec());
+  assert_eq!(decode_base64("c3Bl\nY3Ry").unwrap(), b"spectr".to_vec());
+  assert!(decode_base64("c3B*").is_err());
+}
+
+#[test]
+fn glb_parsing() {
+  fn u32_le(x: u32) -> Vec<u8> {
+    vec![x as u8, (x >> 8) as u8, (x >> 16) as u8, (x >> 24) as u8]
+  }
+
+  let json = b"{}  ";
+  let bin = [1u8, 2, 3, 4];
+  let mut glb = b"glTF".to_vec();
+  glb.extend(u32_le(2));
+  glb.extend(u32_le(12 + 8 + 4 + 8 + 4));
+  glb.extend(u32_le(4));
+  glb.extend(b"JSON");
+  glb.extend(json.iter().cloned());
+  glb.extend(u32_le(4));
+  glb.extend(b"BIN\0");
+  glb.extend(bin.iter().cloned());
+
+  let (parsed_json, parsed_bin) = parse_glb(&glb).unwrap();
+  assert_eq!(parsed_json, &json[..]);
+  assert_eq!(parsed_bin, Some(&bin[..]));
+
+  assert!(parse_glb(b"glTF").is_err());
+  glb.truncate(22);
+  assert!(parse_glb(&glb).is_err());
+}
+
+#[test]
+fn gltf_document() {
+  let gltf = Gltf::from_slice(GLTF_TRIANGLE.as_bytes(), Path::new("")).unwrap();
+
+  assert_eq!(gltf.mesh_count(), 1);
+  assert_eq!(gltf.read_indices(1).unwrap(), vec![0, 1, 2]);
+  assert_eq!(gltf.read_floats(2).unwrap(), (vec![0., 1.], 1));
+
+  // no normals: flat ones are generated
+  let Primitive { mesh, tangents, skin, .. } = gltf.primitive(0, 0).unwrap();
+  assert_eq!(mesh.vertices, vec![([0., 0., 0.], [0., 0., 1.], [0., 0.]), ([1., 0., 0.], [0., 0., 1.], [0., 0.]), ([0., 1., 0.], [0., 0., 1.], [0., 0.])]);
+  assert!(tangents.is_none());
+  assert!(skin.is_none());
+  assert_eq!(mesh.indices, Some(vec![0, 1, 2]));
+  assert_eq!(mesh.bounds, Bounds { min: [0., 0., 0.], max: [1., 1., 0.] });
+
+  let materials = gltf.materials();
+  assert_eq!(materials.len(), 1);
+  assert_eq!(materials[0].name, "red");
+  assert_eq!(materials[0].diffuse, [1., 0., 0.]);
+
+  let (mut nodes, roots) = gltf.nodes().unwrap();
+  assert_eq!(roots, vec![0]);
+  assert_eq!(nodes[0].children, vec![1]);
+  assert_eq!(nodes[1].mesh, Some(0));
+  assert_eq!(nodes[1].scale, Scale::new(2., 2., 2.));
+  assert_eq!((nodes[1].translation.x, nodes[1].translation.y, nodes[1].translation.z), (4., 5., 6.));
+
+  let animations = gltf.animations().unwrap();
+  assert_eq!(animations.len(), 1);
+  assert_eq!(animations[0].duration(), 1.);
+
+  animations[0].apply(0.5, &mut nodes);
+  assert_eq!((nodes[0].translation.x, nodes[0].translation.y, nodes[0].translation.z), (1., 0., 0.));
+
+  animations[0].apply(2., &mut nodes);
+  assert_eq!(nodes[0].translation.x, 2.);
+}
+
+const GLTF_SKIN: &'static str = r#"{
+  "asset": { "version": "2.0" },
+  "scenes": [{ "nodes": [0] }],
+  "nodes": [
+    { "name": "armature", "children": [1, 3], "translation": [0, 0, 5] },
+    { "name": "hip", "children": [2], "translation": [0, 1, 0] },
+    { "name": "knee", "translation": [0, 1, 0] },
+    { "name": "body", "mesh": 0, "skin": 0 }
+  ],
+  "meshes": [{ "primitives": [{ "attributes": { "POSITION": 0, "JOINTS_0": 1, "WEIGHTS_0": 2 } }] }],
+  "skins": [{ "name": "rig", "joints": [1, 2], "inverseBindMatrices": 3 }],
+  "buffers": [{
+    "byteLength": 256,
+    "uri": "data:application/octet-stream;base64,AAAAAAAAAAAAAAAAAACAPwAAAAAAAAAAAAAAAAAAgD8AAAAAAAEAAAABAAAAAQAAAAAAQAAAAEAAAAAAAAAAAAAAgD8AAAAAAAAAAAAAAAAAAAAAAACAQAAAAAAAAAAAAACAPwAAAAAAAAAAAAAAAAAAAAAAAIA/AAAAAAAAAAAAAAAAAAAAAAAAgD8AAAAAAAAAAAAAgL8AAKDAAACAPwAAgD8AAAAAAAAAAAAAAAAAAAAAAACAPwAAAAAAAAAAAAAAAAAAAAAAAIA/AAAAAAAAAAAAAADAAACgwAAAgD8AAAAAAACAPwAAAAAAAIA/AAAAAAAAAAAAAEBAAAAAAA=="
+  }],
+  "bufferViews": [
+    { "buffer": 0, "byteOffset": 0, "byteLength": 36 },
+    { "buffer": 0, "byteOffset": 36, "byteLength": 12 },
+    { "buffer": 0, "byteOffset": 48, "byteLength": 48 },
+    { "buffer": 0, "byteOffset": 96, "byteLength": 128 },
+    { "buffer": 0, "byteOffset": 224, "byteLength": 32 }
+  ],
+  "accessors": [
+    { "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3" },
+    { "bufferView": 1, "componentType": 5121, "count": 3, "type": "VEC4" },
+    { "bufferView": 2, "componentType": 5126, "count": 3, "type": "VEC4" },
+    { "bufferView": 3, "componentType": 5126, "count": 2, "type": "MAT4" },
+    { "bufferView": 4, "componentType": 5126, "count": 2, "type": "SCALAR" },
+    { "bufferView": 4, "byteOffset": 8, "componentType": 5126, "count": 2, "type": "VEC3" }
+  ],
+  "animations": [{
+    "channels": [
+      { "sampler": 0, "target": { "node": 2, "path": "translation" } },
+      { "sampler": 0, "target": { "node": 0, "path": "translation" } }
+    ],
+    "samplers": [{ "input": 4, "output": 5 }]
+  }]
+}"#;
+
+const IDENTITY: [[f32; 4]; 4] = [[1., 0., 0., 0.], [0., 1., 0., 0.], [0., 0., 1., 0.], [0., 0., 0., 1.]];
+
+fn assert_matrix_eq(a: &[[f32; 4]; 4], b: &[[f32; 4]; 4]) {
+  for c in 0..4 {
+    for r in 0..4 {
+      assert!((a[c][r] - b[c][r]).abs() < 1e-5, "{:?} != {:?}", a, b);
+    }
+  }
+}
+
+#[test]
+fn skeleton_palette() {
+  let half = (0.5f32).sqrt();
+  let rest = JointTransform::new(Vector3::new(0., 1., 0.), UnitQuaternion::new(&Quaternion::new(1., 0., 0., 0.)), Scale::default());
+  // children may come before their parent
+  let tip = Joint::new(Some("tip".to_owned()), Some(1), rest, [[1., 0., 0., 0.], [0., 1., 0., 0.], [0., 0., 1., 0.], [0., -2., 0., 1.]]);
+  let root = Joint::new(Some("root".to_owned()), None, rest, [[1., 0., 0., 0.], [0., 1., 0., 0.], [0., 0., 1., 0.], [0., -1., 0., 1.]]);
+  let skeleton = Skeleton::new(None, vec![tip, root]).unwrap();
+
+  assert_eq!(skeleton.joint_index("root"), Some(1));
+
+  // the inverse bind matrices match the rest pose
+  for m in skeleton.palette(&skeleton.rest_pose()) {
+    assert_matrix_eq(&m, &IDENTITY);
+  }
+
+  // a quarter turn of the root around Z moves the tip from (0, 2, 0) to (-1, 1, 0)
+  let mut pose = skeleton.rest_pose();
+  pose[1].rotation = UnitQuaternion::new(&Quaternion::new(half, 0., 0., half));
+
+  let world = skeleton.world_transforms(&pose);
+  assert_matrix_eq(&world[0], &[[0., 1., 0., 0.], [-1., 0., 0., 0.], [0., 0., 1., 0.], [-1., 1., 0., 1.]]);
+
+  let palette = skeleton.palette(&pose);
+  let m = &palette[0];
+  let p = [m[1][0] * 2. + m[3][0], m[1][1] * 2. + m[3][1], m[1][2] * 2. + m[3][2]];
+  assert!((p[0] + 1.).abs() < 1e-5 && (p[1] - 1.).abs() < 1e-5 && p[2].abs() < 1e-5);
+
+  // unknown parents and cycles are rejected
+  assert!(Skeleton::new(None, vec![Joint::new(None, Some(1), rest, IDENTITY)]).is_none());
+  assert!(Skeleton::new(None, vec![Joint::new(None, Some(1), rest, IDENTITY), Joint::new(None, Some(0), rest, IDENTITY)]).is_none());
+}
+
+#[test]
+fn gltf_skin() {
+  let gltf = Gltf::from_slice(GLTF_SKIN.as_bytes(), Path::new("")).unwrap();
+
+  let (nodes, _) = gltf.nodes().unwrap();
+  assert_eq!(nodes[3].skin, Some(0));
+
+  let Primitive { skin, .. } = gltf.primitive(0, 0).unwrap();
+  assert_eq!(skin.unwrap(), vec![([0, 1, 0, 0], [0.5, 0.5, 0., 0.]), ([0, 1, 0, 0], [1., 0., 0., 0.]), ([0, 1, 0, 0], [0., 1., 0., 0.])]);
+
+  let skins = gltf.skins().unwrap();
+  assert_eq!(skins.len(), 1);
+
+  let skeleton = &skins[0];
+  assert_eq!(skeleton.name, Some("rig".to_owned()));
+
+  let joints = skeleton.joints();
+  assert_eq!((joints[0].name.clone(), joints[0].parent, joints[0].node), (Some("hip".to_owned()), None, Some(1)));
+  assert_eq!((joints[1].parent, joints[1].node), (Some(0), Some(2)));
+  // the armature isn’t a joint; its transform is folded into the hip
+  assert_matrix_eq(&joints[0].offset, &[[1., 0., 0., 0.], [0., 1., 0., 0.], [0., 0., 1., 0.], [0., 0., 5., 1.]]);
+  assert_matrix_eq(&joints[1].offset, &IDENTITY);
+
+  for m in skeleton.palette(&skeleton.rest_pose()) {
+    assert_matrix_eq(&m, &IDENTITY);
+  }
+
+  // only the channel driving a joint is kept
+  let animations = gltf.animations().unwrap();
+  let clip = skeleton.clip(&animations[0]);
+  assert_eq!(clip.channels.len(), 1);
+  assert_eq!(clip.channels[0].node, 1);
+  assert_eq!(clip.duration(), 1.);
+
+  let palette = skeleton.palette(&clip.pose(skeleton, 1.));
+  assert_matrix_eq(&palette[0], &IDENTITY);
+  assert_matrix_eq(&palette[1], &[[1., 0., 0., 0.], [0., 1., 0., 0.], [0., 0., 1., 0.], [0., 2., 0., 1.]]);
+}
+
+const GLTF_MORPH: &'static str = r#"{
+  "asset": { "version": "2.0" },
+  "nodes": [
+    { "name": "default", "mesh": 0 },
+    { "name": "weighted", "mesh": 0, "weights": [1, 1] }
+  ],
+  "meshes": [{
+    "primitives": [{ "attributes": { "POSITION": 0 }, "targets": [{ "POSITION": 1 }, { "POSITION": 1 }] }],
+    "weights": [0.25, 0]
+  }],
+  "buffers": [{
+    "byteLength": 96,
+    "uri": "data:application/octet-stream;base64,AAAAAAAAAAAAAAAAAACAPwAAAAAAAAAAAAAAAAAAgD8AAAAAAAAAAAAAAAAAAIA/AAAAAAAAAAAAAIA/AAAAAAAAAAAAAIA/AAAAAAAAgD8AAAAAAACAPwAAgD8AAAAA"
+  }],
+  "bufferViews": [
+    { "buffer": 0, "byteOffset": 0, "byteLength": 72 },
+    { "buffer": 0, "byteOffset": 72, "byteLength": 24 }
+  ],
+  "accessors": [
+    { "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3" },
+    { "bufferView": 0, "byteOffset": 36, "componentType": 5126, "count": 3, "type": "VEC3" },
+    { "bufferView": 1, "componentType": 5126, "count": 2, "type": "SCALAR" },
+    { "bufferView": 1, "byteOffset": 8, "componentType": 5126, "count": 4, "type": "SCALAR" }
+  ],
+  "animations": [{
+    "channels": [{ "sampler": 0, "target": { "node": 0, "path": "weights" } }],
+    "samplers": [{ "input": 2, "output": 3 }]
+  }]
+}"#;
+
+#[test]
+fn morph_blending() {
+  let base = MeshData::new(Mode::Triangle, vec![([0., 0., 0.], [0., 0., 1.], [0., 0.]), ([1., 0., 0.], [0., 0., 1.], [1., 0.]), ([0., 1., 0.], [0., 0., 1.], [0., 1.])], None);
+  let shape = MeshData::new(Mode::Triangle, vec![([0., 0., 2.], [0., 1., 0.], [0., 0.]), ([1., 0., 2.], [0., 1., 0.], [1., 0.]), ([0., 1., 2.], [0., 1., 0.], [0., 1.])], None);
+  let target = MorphTarget::difference(None, &base, &shape).unwrap();
+
+  assert_eq!(target.positions, vec![[0., 0., 2.]; 3]);
+  assert_eq!(target.normals, Some(vec![[0., 1., -1.]; 3]));
+  assert!(MorphTarget::difference(None, &base, &MeshData::new(Mode::Triangle, Vec::new(), None)).is_none());
+
+  let morph = MorphMesh::new(base.clone(), vec![target.clone()], vec![0.]).unwrap();
+
+  // missing weights are null
+  assert_eq!(morph.blend(&[]).vertices, base.vertices);
+  assert_eq!(morph.blend(&[1.]).vertices, shape.vertices);
+
+  let half = morph.blend(&[0.5]);
+  assert_eq!(half.vertices[1].0, [1., 0., 1.]);
+  assert!((half.vertices[1].1[1] - (0.5f32).sqrt()).abs() < 1e-6);
+  assert_eq!(half.bounds.max[2], 1.);
+
+  // the morph layout extends the skinned one, which extends the tangent one
+  let skin = [([1, 0, 0, 0], [1., 0., 0., 0.]), ([2, 0, 0, 0], [1., 0., 0., 0.]), ([3, 0, 0, 0], [1., 0., 0., 0.])];
+  let gpu = morph.gpu_mesh(None, Some(&skin));
+  assert_eq!(gpu.vertices[0], ([0., 0., 0.], [0., 0., 1.], [0., 0.], [1., 0., 0., 1.], [1, 0, 0, 0], [1., 0., 0., 0.], [0., 0., 2.],
+                               [0., 1., -1.], [0., 0., 0.], [0., 0., 0.]));
+  assert_eq!(gpu.vertices[2].4, [3, 0, 0, 0]);
+
+  let (skinned, sources) = skinned_mesh(&base, Some(vec![[0., 1., 0., -1.]; 3]), None);
+  assert_eq!(skinned.vertices[1], ([1., 0., 0.], [0., 0., 1.], [1., 0.], [0., 1., 0., -1.], [0; 4], [0.; 4]));
+  assert_eq!(sources, vec![0, 1, 2]);
+
+  let short = MorphTarget { name: None, positions: vec![[0., 0., 0.]], normals: None };
+  assert!(MorphMesh::new(base, vec![short], Vec::new()).is_none());
+}
+
+#[test]
+fn gltf_attribute_counts() {
+  // accessor 3 only has two VEC3s, 4 two VEC2s and 5 two VEC4s for a triangle
+  let gltf_with = |attributes: &str| {
+    let json = GLTF_TRIANGLE
+      .replace(r#""attributes": { "POSITION": 0 }"#, &format!(r#""attributes": {{ "POSITION": 0, {} }}"#, attributes))
+      .replace(r#""count": 2, "type": "VEC3" }"#, r#""count": 2, "type": "VEC3" },
+    { "bufferView": 2, "componentType": 5126, "count": 2, "type": "VEC2" },
+    { "bufferView": 2, "componentType": 5126, "count": 2, "type": "VEC4" }"#);
+    Gltf::from_slice(json.as_bytes(), Path::new("")).unwrap()
+  };
+
+  assert!(gltf_with(r#""NORMAL": 3"#).primitive(0, 0).is_err());
+  assert!(gltf_with(r#""TEXCOORD_0": 4"#).primitive(0, 0).is_err());
+  assert!(gltf_with(r#""NORMAL": 0, "TANGENT": 5"#).primitive(0, 0).is_err());
+}
+
+#[test]
+fn gltf_morph_targets() {
+  let gltf = Gltf::from_slice(GLTF_MORPH.as_bytes(), Path::new("")).unwrap();
+
+  // flat normals split the vertices; the normal displacements are dropped
+  let Primitive { mesh, targets, .. } = gltf.primitive(0, 0).unwrap();
+  assert_eq!(targets.len(), 2);
+  assert_eq!(targets[0].positions, vec![[0., 0., 1.]; 3]);
+  assert_eq!(targets[1].normals, None);
+
+  let morph = MorphMesh::new(mesh, targets, vec![0.25, 0.]).unwrap();
+  assert_eq!(morph.blend(&[0.5, 0.25]).vertices[2].0, [0., 1., 0.75]);
+
+  let (mut nodes, _) = gltf.nodes().unwrap();
+  assert_eq!(nodes[0].weights, vec![0.25, 0.]);
+  assert_eq!(nodes[1].weights, vec![1., 1.]);
+
+  // one channel per target
+  let animations = gltf.animations().unwrap();
+  assert_eq!(animations[0].channels.len(), 2);
+  assert_eq!(animations[0].duration(), 1.);
+
+  animations[0].apply(0.5, &mut nodes);
+  assert_eq!(nodes[0].weights, vec![0.5, 0.5]);
+
+  animations[0].apply(1., &mut nodes);
+  assert_eq!(nodes[0].weights, vec![1., 0.]);
+  assert_eq!(nodes[1].weights, vec![1., 1.]);
+}
+
+#[test]
+fn mesh_data() {
+  let cube = new_cube();
+  assert_eq!(cube.vertices.len(), 24);
+  assert_eq!(cube.index_count(), 36);
+  assert_eq!(cube.bounds, Bounds { min: [-1., -1., -1.], max: [1., 1., 1.] });
+  assert_eq!(cube.bounds.center(), [0., 0., 0.]);
+
+  let plane = new_plane();
+  assert_eq!(plane.indices(), vec![0, 1, 2, 3]);
+  assert_eq!(plane.bounds.size(), [2., 2., 0.]);
+
+  let moved = plane.map_vertices(|p| [p[0] + 1., p[1], p[2] - 1.]);
+  assert_eq!(moved.bounds, Bounds { min: [0., -1., -1.], max: [2., 1., -1.] });
+
+  let empty: MeshData<[f32; 3]> = MeshData::new(moved.mode, Vec::new(), None);
+  assert!(empty.bounds.is_empty());
+}
+
+#[test]
+fn obj_conversion() {
+  let src = "o quad\nv 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nf 1 2 3\nf 1 3 4\n";
+  let obj_set = obj::parse(src.to_owned()).unwrap();
+  let meshes = convert_obj(&obj_set, &MaterialLibrary::default(), Normals::Smooth).unwrap();
+
+  assert_eq!(meshes.len(), 1);
+
+  let (ref mesh, material) = meshes[0];
+  assert_eq!(material, None);
+  assert_eq!(mesh.vertices.len(), 4);
+  assert_eq!(mesh.indices, Some(vec![0, 1, 2, 0, 2, 3]));
+  assert_eq!(mesh.bounds, Bounds { min: [0., 0., 0.], max: [1., 1., 0.] });
+  assert!(mesh.vertices.iter().all(|v| v.1 == [0., 0., 1.]));
+}
+
+// Two triangles without normals sharing the edge from the origin to +X, folded by 30°. The first
+// one faces +Z.
+const CREASE_OBJ: &'static str = "o crease\nv 0 0 0\nv 1 0 0\nv 0 1 0\nv 0 -0.8660254 0.5\nf 1 2 3\nf 2 1 4\n";
+
+// Normal at the origin in the triangle facing +Z and in the other one.
+fn crease_normals(normals: Normals) -> ([f32; 3], [f32; 3]) {
+  let obj_set = obj::parse(CREASE_OBJ.to_owned()).unwrap();
+  let meshes = convert_obj(&obj_set, &MaterialLibrary::default(), normals).unwrap();
+  let mesh = &meshes[0].0;
+  let mut origin_normals = ([0.; 3], [0.; 3]);
+
+  for triangle in mesh.indices.as_ref().unwrap().chunks(3) {
+    let vertices: Vec<_> = triangle.iter().map(|&i| mesh.vertices[i as usize]).collect();
+    let origin = vertices.iter().find(|v| v.0 == [0., 0., 0.]).unwrap().1;
+
+    if vertices.iter().any(|v| v.0 == [0., 1., 0.]) {
+      origin_normals.0 = origin;
+    } else {
+      origin_normals.1 = origin;
+    }
+  }
+
+  origin_normals
+}
+
+fn assert_normal_eq(a: [f32; 3], b: [f32; 3]) {
+  assert!((0..3).all(|i| (a[i] - b[i]).abs() < 1e-5), "{:?} != {:?}", a, b);
+}
+
+#[test]
+fn generated_normals() {
+  let face_a = [0., 0., 1.];
+  let face_b = [0., 0.5, 0.8660254];
+  let smooth = [0., 0.25881904, 0.9659258];
+
+  let (a, b) = crease_normals(Normals::Flat);
+  assert_normal_eq(a, face_a);
+  assert_normal_eq(b, face_b);
+
+  let (a, b) = crease_normals(Normals::Smooth);
+  assert_normal_eq(a, smooth);
+  assert_normal_eq(b, smooth);
+
+  // the crease is sharper than the threshold
+  let (a, b) = crease_normals(Normals::Angle(29.));
+  assert_normal_eq(a, face_a);
+  assert_normal_eq(b, face_b);
+
+  // the crease is smoother than the threshold
+  let (a, b) = crease_normals(Normals::Angle(31.));
+  assert_normal_eq(a, smooth);
+  assert_normal_eq(b, smooth);
+}
+
+#[test]
+fn degenerate_normals() {
+  let (a, b, c) = ([0., 0., 0.], [1., 1., 1.], [2., 2., 2.]);
+
+  assert_eq!(vector::normalize(vector::cross(vector::sub(b, a), vector::sub(c, a))), [0., 0., 0.]);
+  assert_eq!(vector::normalize([0., 0., 2.]), [0., 0., 1.]);
+}
+
+// Primitives of a mesh as lists of positions, along with the number of vertices of its primitives.
+fn mesh_primitives(mesh: &MeshData<Vertex>) -> (usize, Vec<Vec<[f32; 3]>>) {
+  let arity = match mesh.mode {
+    Mode::Point => 1,
+    Mode::Line => 2,
+    Mode::Triangle => 3,
+    _ => panic!("unexpected mode")
+  };
+
+  let primitives = mesh.indices().chunks(arity).map(|primitive| {
+    primitive.iter().map(|&i| mesh.vertices[i as usize].0).collect()
+  }).collect();
+
+  (arity, primitives)
+}
+
+#[test]
+fn obj_mixed_primitives() {
+  let src = "o mixed\nv 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nl 1 2\nf 1 2 3\np 4\nl 3 4\nf 1 3 4\n";
+  let obj_set = obj::parse(src.to_owned()).unwrap();
+  let meshes = convert_obj(&obj_set, &MaterialLibrary::default(), Normals::Smooth).unwrap();
+
+  let p = [[0., 0., 0.], [1., 0., 0.], [1., 1., 0.], [0., 1., 0.]];
+  let expected = vec![
+    (2, vec![vec![p[0], p[1]], vec![p[2], p[3]]]),
+    (3, vec![vec![p[0], p[1], p[2]], vec![p[0], p[2], p[3]]]),
+    (1, vec![vec![p[3]]])
+  ];
+
+  let primitives: Vec<_> = meshes.iter().map(|&(ref mesh, _)| mesh_primitives(mesh)).collect();
+  assert_eq!(primitives, expected);
+}
+
+#[test]
+fn binary_model_round_trip() {
+  let root = temp_files("binary_model", &[
+    ("quad.obj", "mtllib quad.mtl\nv 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nusemtl red\nf 1 2 3\nf 1 3 4\nl 1 3\n"),
+    ("quad.mtl", "newmtl red\nKd 1 0 0\nmap_Kd red.png\n")
+  ]);
+
+  let output = root.join("quad.smesh");
+  binary::convert_obj_file(root.join("quad.obj"), &output, Normals::Flat).unwrap();
+
+  let (source, _) = ModelData::from_obj(root.join("quad.obj"), Normals::Flat).unwrap();
+  let (data, source_name) = binary::read_file(&output).unwrap();
+
+  assert_eq!(source_name, Some("quad.obj".to_owned()));
+  assert_eq!(data.materials, source.materials);
+  assert_eq!(data.materials[0].diffuse_map, Some("red.png".to_owned()));
+  assert_eq!(data.bounds(), Bounds { min: [0., 0., 0.], max: [1., 1., 0.] });
+  assert_eq!(data.parts.len(), 2);
+
+  for (&(ref read, read_material), &(ref converted, converted_material)) in data.parts.iter().zip(&source.parts) {
+    assert_eq!(read_material, converted_material);
+    assert_eq!(read.vertices, converted.vertices);
+    assert_eq!(read.indices, converted.indices);
+    assert_eq!(read.bounds, converted.bounds);
+    assert_eq!(mesh_primitives(read), mesh_primitives(converted));
+  }
+
+  // a version mismatch or a truncated file is an error
+  let mut bytes = Vec::new();
+  binary::write(&data, None, &mut bytes).unwrap();
+  assert!(binary::read(&bytes).is_ok());
+  assert!(binary::read(&bytes[..bytes.len() - 1]).is_err());
+  bytes[4] = 42;
+  assert!(binary::read(&bytes).is_err());
+}
+
+// Triangle soup of a n × n grid, with every triangle having its own vertices and the triangles
+// shuffled.
+fn grid_soup(n: usize) -> MeshData<Vertex> {
+  let mut triangles = Vec::new();
+
+  for i in 0..n {
+    for j in 0..n {
+      let p = |di: usize, dj: usize| ([(i + di) as f32, (j + dj) as f32, 0.], [0., 0., 1.], [0., 0.]);
+      triangles.push([p(0, 0), p(1, 0), p(1, 1)]);
+      triangles.push([p(0, 0), p(1, 1), p(0, 1)]);
+    }
+  }
+
+  thread_rng().shuffle(&mut triangles);
+
+  let vertices: Vec<Vertex> = triangles.iter().flat_map(|triangle| triangle.iter().cloned()).collect();
+  MeshData::new(Mode::Triangle, vertices, None)
+}
+
+// Sorted triangles of a mesh, by position.
+fn sorted_triangles(mesh: &MeshData<Vertex>) -> Vec<[[i32; 3]; 3]> {
+  let mut triangles: Vec<_> = mesh.indices().chunks(3).map(|t| {
+    let p = |i: u32| {
+      let p = mesh.vertices[i as usize].0;
+      [p[0] as i32, p[1] as i32, p[2] as i32]
+    };
+
+    [p(t[0]), p(t[1]), p(t[2])]
+  }).collect();
+
+  triangles.sort();
+  triangles
+}
+
+#[test]
+fn mesh_welding() {
+  let soup = grid_soup(4);
+  let welded = weld(&soup, 0.);
+
+  assert_eq!(welded.vertices.len(), 25);
+  assert_eq!(sorted_triangles(&welded), sorted_triangles(&soup));
+
+  // close vertices only merge with a tolerance; the collapsed triangle goes away
+  let mesh = MeshData::new(Mode::Triangle, vec![
+    ([0., 0., 0.], [0., 0., 1.], [0., 0.]),
+    ([1., 0., 0.], [0., 0., 1.], [0., 0.]),
+    ([1.001, 0., 0.], [0., 0., 1.], [0., 0.]),
+    ([0., 1., 0.], [0., 0., 1.], [0., 0.])
+  ], Some(vec![0, 1, 3, 0, 1, 2]));
+
+  assert_eq!(weld(&mesh, 0.).vertices.len(), 4);
+
+  let welded = weld(&mesh, 0.01);
+  assert_eq!(welded.vertices.len(), 3);
+  assert_eq!(welded.indices, Some(vec![0, 1, 2]));
+}
+
+#[test]
+fn vertex_cache_optimization() {
+  let welded = weld(&grid_soup(32), 0.);
+  let indices = welded.indices();
+  let optimized = optimize_vertex_cache(&indices, welded.vertices.len());
+
+  // same triangles, better cache use
+  let mut before: Vec<_> = indices.chunks(3).map(|t| t.to_vec()).collect();
+  let mut after: Vec<_> = optimized.chunks(3).map(|t| t.to_vec()).collect();
+  before.sort();
+  after.sort();
+  assert_eq!(before, after);
+
+  let acmr_before = acmr(&indices, CACHE_SIZE);
+  let acmr_after = acmr(&optimized, CACHE_SIZE);
+  assert!(acmr_after < acmr_before);
+  assert!(acmr_after >= 0.5 && acmr_after < 1., "ACMR {}", acmr_after);
+
+  // vertices end up in fetch order
+  let (vertices, fetched) = optimize_vertex_fetch(&welded.vertices, &optimized);
+  assert_eq!(vertices.len(), welded.vertices.len());
+  assert_eq!(&fetched[..3], &[0, 1, 2]);
+
+  let (optimized_mesh, stats) = optimize(&grid_soup(8), 0.);
+  assert_eq!(stats.vertices_before, 8 * 8 * 6);
+  assert_eq!(stats.vertices_after, 81);
+  assert_eq!(optimized_mesh.vertices.len(), 81);
+  assert!(stats.acmr_after.unwrap() < stats.acmr_before.unwrap());
+}
+
+// Check that every triangle faces the way its vertex normals point and that normals are unit.
+fn assert_shape(mesh: &MeshData<Vertex>, vertices: usize, triangles: usize) {
+  let indices = mesh.indices.as_ref().unwrap();
+
+  assert_eq!(mesh.vertices.len(), vertices);
+  assert_eq!(indices.len(), triangles * 3);
+
+  for &(_, n, _) in &mesh.vertices {
+    assert!(((n[0] * n[0] + n[1] * n[1] + n[2] * n[2]).sqrt() - 1.).abs() < 1e-4);
+  }
+
+  for triangle in indices.chunks(3) {
+    let (a, b, c) = (mesh.vertices[triangle[0] as usize], mesh.vertices[triangle[1] as usize], mesh.vertices[triangle[2] as usize]);
+    let (u, v) = ([b.0[0] - a.0[0], b.0[1] - a.0[1], b.0[2] - a.0[2]], [c.0[0] - a.0[0], c.0[1] - a.0[1], c.0[2] - a.0[2]]);
+    let face = [u[1] * v[2] - u[2] * v[1], u[2] * v[0] - u[0] * v[2], u[0] * v[1] - u[1] * v[0]];
+
+    for n in &[a.1, b.1, c.1] {
+      assert!(face[0] * n[0] + face[1] * n[1] + face[2] * n[2] > 0.);
+    }
+  }
+}
+
+#[test]
+fn parametric_shapes() {
+  assert_shape(&new_uv_sphere(1., 8, 4), 45, 48);
+  assert_shape(&new_icosphere(1., 2), 162, 320);
+  assert_shape(&new_torus(1., 3., 8, 6), 63, 96);
+  assert_shape(&new_cylinder(1., 2., 8), 38, 32);
+  assert_shape(&new_cone(1., 2., 8), 27, 16);
+  assert_shape(&new_capsule(0.5, 1., 8, 3), 72, 96);
+  assert_shape(&new_grid(2., 2., 3, 2), 12, 12);
+
+  for &(p, _, _) in &new_icosphere(2., 1).vertices {
+    assert!(((p[0] * p[0] + p[1] * p[1] + p[2] * p[2]).sqrt() - 2.).abs() < 1e-4);
+  }
+}
+
+#[test]
+fn builtin_shapes() {
+  assert_eq!(builtin_mesh("torus?r=1&R=3&rings=8&sides=6").unwrap().vertices, new_torus(1., 3., 8, 6).vertices);
+  assert_eq!(builtin_mesh("sphere").unwrap().vertices, new_uv_sphere(1., 32, 16).vertices);
+  assert_eq!(builtin_mesh("cube").unwrap().vertices, new_cube().vertices);
+
+  assert!(builtin_mesh("teapot").is_err());
+  assert!(builtin_mesh("torus?radius=1").is_err());
+  assert!(builtin_mesh("torus?r=one").is_err());
+  assert!(builtin_mesh("cylinder?segments=2.5").is_err());
+
+  // counts are bounded
+  assert_eq!(builtin_mesh("sphere?sectors=1e9&stacks=4").unwrap().vertices.len(), (MAX_BUILTIN_COUNT as usize + 1) * 5);
+}
+
+#[test]
+fn heightmap_sampling() {
+  let heightmap = Heightmap::from_fn(3, 2, |u, v| u + v);
+
+  assert_eq!(heightmap.heights, vec![0., 0.5, 1., 1., 1.5, 2.]);
+  assert_eq!(heightmap.sample(-1, 5), 1.);
+  assert!((heightmap.height_at(0.25, 0.5) - 0.75).abs() < 1e-6);
+
+  assert!(Heightmap::new(2, 2, vec![0.; 3]).is_none());
+  assert!(Heightmap::new(1, 4, vec![0.; 4]).is_none());
+
+  for i in 0..100 {
+    let h = fractal_noise(i as f32 * 0.37, i as f32 * 0.11, 4, 7);
+    assert!(h >= 0. && h <= 1.);
+    assert_eq!(h, fractal_noise(i as f32 * 0.37, i as f32 * 0.11, 4, 7));
+  }
+}
+
+#[test]
+fn terrain_generation() {
+  let args = TerrainArgs { size: [4., 4.], height: 2., .. TerrainArgs::default() };
+
+  // a slope rising along X
+  let meshes = terrain_meshes(&Heightmap::from_fn(5, 5, |u, _| u), &args);
+  assert_eq!(meshes.len(), 1);
+  assert_eq!(meshes[0].len(), 1);
+  assert_shape(&meshes[0][0], 25, 32);
+
+  let (p, n, uv) = meshes[0][0].vertices[7];
+  assert_eq!(p, [0., 1., -1.]);
+  assert_eq!(uv, [0.5, 0.25]);
+  assert!((n[0] + 0.5 / (1.25 as f32).sqrt()).abs() < 1e-6 && n[2] == 0.);
+
+  // 2×2 chunks with two levels of detail and skirts
+  let args = TerrainArgs { chunks: 2, lods: 2, .. args };
+  let meshes = terrain_meshes(&Heightmap::from_fn(5, 5, |_, _| 0.), &args);
+  assert_eq!(meshes.len(), 4);
+
+  // skirts face outwards, so only the vertex and triangle counts are checked
+  for chunk in &meshes {
+    assert_eq!(chunk.len(), 2);
+    assert_eq!((chunk[0].vertices.len(), chunk[0].indices.as_ref().unwrap().len()), (17, 24 * 3));
+    assert_eq!((chunk[1].vertices.len(), chunk[1].indices.as_ref().unwrap().len()), (8, 10 * 3));
+  }
+
+  assert_eq!(meshes[3][0].bounds.min, [0., -1., 0.]);
+  assert_eq!(meshes[3][0].bounds.max, [2., 0., 2.]);
+}
+
+#[test]
+fn shadertoy_sources() {
+  let src = "void mainImage(out vec4 fragColor, in vec2 fragCoord) {\n  fragColor = texture(iChannel1, fragCoord / iResolution.xy) * iTime;\n}";
+  let sources = ShadertoyProgram::sources(src, Path::new("plasma.glsl"));
+
+  assert_eq!(sources.files, vec![PathBuf::from("plasma.glsl")]);
+  assert!(sources.vs.contains("gl_VertexID"));
+  assert!(sources.fs.contains(&format!("#line 1 0\n{}\n", src)));
+  assert!(sources.fs.trim_right().ends_with("mainImage(spectra_frag, gl_FragCoord.xy);\n}"));
+  assert_eq!(validate(&sources), vec![]);
+
+  let names: Vec<_> = program_uniforms(&sources).unwrap().into_iter().map(|decl| decl.name).collect();
+  for name in &["iTime", "iResolution", "iMouse", "iFrame", "iChannel0", "iChannel1", "iChannel2", "iChannel3"] {
+    assert!(names.contains(&name.to_string()), "missing {}", name);
+  }
+
+  // lines of the shader keep their own numbers
+  let lines: Vec<_> = sources.fs.lines().collect();
+  let first = lines.iter().position(|line| *line == "#line 1 0").unwrap();
+  assert_eq!(lines[first + 1], "void mainImage(out vec4 fragColor, in vec2 fragCoord) {");
+}
+
+#[test]
+fn shadertoy_pass_channels() {
+  // pass 1 reads pass 0 of this frame, itself and pass 2 of the previous frame
+  assert_eq!(Channel::Pass(0).pass_output(1, 0), Some((0, 0)));
+  assert_eq!(Channel::Pass(1).pass_output(1, 0), Some((1, 1)));
+  assert_eq!(Channel::Pass(2).pass_output(1, 1), Some((2, 0)));
+  assert_eq!(Channel::Input(0).pass_output(1, 0), None);
+  assert_eq!(Channel::Empty.pass_output(1, 0), None);
+}
+
+#[test]
+fn fallback_program_sources() {
+  let args = ProgramArgs::discover().define("A", "");
+  let sources = fallback_sources(&args);
+  assert!(sources.vs.contains("in vec3 co;"));
+  assert_eq!(validate(&sources), vec![]);
+
+  // attribute-less draws get a fullscreen quad
+  let sources = fallback_sources(&args.fullscreen());
+  assert!(!sources.vs.contains(" in "));
+  assert!(sources.vs.contains("gl_VertexID"));
+  assert_eq!(validate(&sources), vec![]);
+}